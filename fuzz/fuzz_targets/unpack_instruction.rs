@@ -0,0 +1,16 @@
+// 임의의 명령 데이터로 EscrowInstruction::unpack을 호출해
+// 어떤 입력에서도 패닉 없이 Ok 또는 Err만 반환하는지 확인
+//
+// 로컬 실행 (nightly와 cargo-fuzz 필요):
+//   cargo install cargo-fuzz
+//   cd fuzz && cargo +nightly fuzz run unpack_instruction corpus/unpack_instruction
+// 패닉을 찾으면 artifacts/unpack_instruction/에 입력이 저장되고
+// `cargo +nightly fuzz run unpack_instruction <파일>`로 다시 재현할 수 있음
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use test_escrow::intruction::EscrowInstruction;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = EscrowInstruction::unpack(data);
+});
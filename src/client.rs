@@ -0,0 +1,570 @@
+#[cfg(feature = "rent-sysvar-account")]
+use solana_program::sysvar;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use solana_rpc_client_api::filter::{Memcmp, RpcFilterType};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+use crate::{
+    intruction::EscrowInstruction,
+    pda::escrow_pda,
+    state::{Escrow, ESCROW_VERSION},
+};
+
+// 클라이언트에서 에스크로 프로그램의 명령을 만드는 함수들
+// 계정 순서와 signer/writable 플래그는 EscrowInstruction의 문서와 같아야 함
+
+/// InitEscrow 명령을 만듭니다.
+///
+/// `token_program_id`는 임시 토큰 계정을 소유한 토큰 프로그램(SPL Token 또는 Token-2022)입니다.
+#[allow(clippy::too_many_arguments)]
+pub fn init_escrow(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    temp_token_account: &Pubkey,
+    receive_account: &Pubkey,
+    escrow_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    expiry_ts: i64,
+    nonce: u64,
+    fee_bps: u16,
+    memo: [u8; 32],
+    cancel_after_ts: i64,
+    vesting: bool,
+) -> Instruction {
+    // 태그(0) + 금액(8바이트) + 만료 시각(8바이트) + 논스(8바이트) + 수수료(2바이트)
+    // + 메모(32바이트) + 취소 가능 시각(8바이트) + 베스팅(1바이트)
+    let data = EscrowInstruction::InitEscrow {
+        amount,
+        expiry_ts,
+        nonce,
+        fee_bps,
+        memo,
+        cancel_after_ts,
+        vesting,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*initializer, true),
+        AccountMeta::new(*temp_token_account, false),
+        AccountMeta::new_readonly(*receive_account, false),
+        AccountMeta::new(*escrow_account, false),
+        #[cfg(feature = "rent-sysvar-account")]
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Exchange 명령을 만듭니다.
+///
+/// `token_program_id`는 에스크로를 만들 때 기록된 토큰 프로그램과 같아야 합니다.
+/// Token-2022면 `y_mint`와 `x_mint`를 10, 11번 계정으로 더 넘깁니다.
+/// SOL 에스크로와 베스팅 에스크로는 토큰 X를 바로 보내지 않으므로 `x_mint`에 `None`을 넘깁니다.
+#[allow(clippy::too_many_arguments)]
+pub fn exchange(
+    program_id: &Pubkey,
+    taker: &Pubkey,
+    taker_send_account: &Pubkey,
+    taker_receive_account: &Pubkey,
+    pda_temp_account: &Pubkey,
+    initializer_main_account: &Pubkey,
+    initializer_receive_account: &Pubkey,
+    escrow_account: &Pubkey,
+    pda: &Pubkey,
+    fee_account: &Pubkey,
+    token_program_id: &Pubkey,
+    y_mint: &Pubkey,
+    x_mint: Option<&Pubkey>,
+    amount: u64,
+    unwrap_sol: bool,
+    decimals: Option<u8>,
+) -> Instruction {
+    // 태그(1) + 금액(8바이트) + 랩트 SOL 풀기(1바이트) + 소수점 자릿수(1바이트, 있을 때만)
+    let data = EscrowInstruction::Exchange {
+        amount,
+        unwrap_sol,
+        decimals,
+    }
+    .pack();
+
+    // 랩트 SOL을 풀면 테이커 계정이 램포트를 받으므로 writable
+    let taker_meta = if unwrap_sol {
+        AccountMeta::new(*taker, true)
+    } else {
+        AccountMeta::new_readonly(*taker, true)
+    };
+
+    let mut accounts = vec![
+        taker_meta,
+        AccountMeta::new(*taker_send_account, false),
+        AccountMeta::new(*taker_receive_account, false),
+        AccountMeta::new(*pda_temp_account, false),
+        AccountMeta::new(*initializer_main_account, false),
+        AccountMeta::new(*initializer_receive_account, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*pda, false),
+        AccountMeta::new(*fee_account, false),
+    ];
+    // Token-2022는 transfer_checked로 보내므로 민트가 필요함
+    if *token_program_id == spl_token_2022::id() {
+        accounts.push(AccountMeta::new_readonly(*y_mint, false));
+        if let Some(x_mint) = x_mint {
+            accounts.push(AccountMeta::new_readonly(*x_mint, false));
+        }
+    }
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// InitEscrowWithTransfer 명령을 만듭니다.
+///
+/// 토큰 X를 보관할 PDA의 연관 토큰 계정은 `escrow_ata`로 구하며 미리 만들어 두어야 합니다.
+/// Token-2022면 `x_mint`를 6번 계정으로 더 넘깁니다.
+#[allow(clippy::too_many_arguments)]
+pub fn init_escrow_with_transfer(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    source_token_account: &Pubkey,
+    receive_account: &Pubkey,
+    escrow_account: &Pubkey,
+    x_mint: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    deposit_amount: u64,
+    expiry_ts: i64,
+    nonce: u64,
+    fee_bps: u16,
+) -> Instruction {
+    // 태그(10) + 금액(8바이트) + 맡길 수량(8바이트) + 만료 시각(8바이트) + 논스(8바이트) + 수수료(2바이트)
+    let data = EscrowInstruction::InitEscrowWithTransfer {
+        amount,
+        deposit_amount,
+        expiry_ts,
+        nonce,
+        fee_bps,
+    }
+    .pack();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*initializer, true),
+        AccountMeta::new(*source_token_account, false),
+        AccountMeta::new_readonly(*receive_account, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new(
+            escrow_ata(program_id, initializer, nonce, x_mint, token_program_id),
+            false,
+        ),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    if *token_program_id == spl_token_2022::id() {
+        accounts.push(AccountMeta::new_readonly(*x_mint, false));
+    }
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// InitEscrowWithDelegate 명령을 만듭니다.
+///
+/// 위임자가 될 PDA는 이니셜라이저와 논스로 구합니다.
+#[allow(clippy::too_many_arguments)]
+pub fn init_escrow_with_delegate(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    source_token_account: &Pubkey,
+    receive_account: &Pubkey,
+    escrow_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    deposit_amount: u64,
+    expiry_ts: i64,
+    nonce: u64,
+    fee_bps: u16,
+) -> Instruction {
+    // 태그(13) + 금액(8바이트) + 위임할 수량(8바이트) + 만료 시각(8바이트) + 논스(8바이트) + 수수료(2바이트)
+    let data = EscrowInstruction::InitEscrowWithDelegate {
+        amount,
+        deposit_amount,
+        expiry_ts,
+        nonce,
+        fee_bps,
+    }
+    .pack();
+
+    let (pda, _) = escrow_pda(program_id, initializer, nonce);
+    let accounts = vec![
+        AccountMeta::new_readonly(*initializer, true),
+        AccountMeta::new(*source_token_account, false),
+        AccountMeta::new_readonly(*receive_account, false),
+        AccountMeta::new(*escrow_account, false),
+        AccountMeta::new_readonly(pda, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// 에스크로 PDA가 소유한 `mint`의 연관 토큰 계정(ATA) 주소를 구합니다.
+///
+/// PDA는 이니셜라이저와 논스마다 다르므로 둘 다 받습니다.
+/// ATA 주소는 토큰 프로그램마다 다르므로 민트를 소유한 토큰 프로그램도 받습니다.
+pub fn escrow_ata(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    nonce: u64,
+    mint: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Pubkey {
+    let (pda, _) = escrow_pda(program_id, initializer, nonce);
+    get_associated_token_address_with_program_id(&pda, mint, token_program_id)
+}
+
+/// 초기화된 에스크로 계정만 가져오는 `getProgramAccounts` 필터를 만듭니다.
+///
+/// 크기가 `Escrow::LEN`이고 첫 바이트(버전)가 `ESCROW_VERSION`인 계정만 고릅니다.
+/// (0으로 채워진 빈 계정은 버전이 0이므로 빠짐)
+pub fn escrow_filters() -> Vec<RpcFilterType> {
+    vec![
+        RpcFilterType::DataSize(Escrow::LEN as u64),
+        RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, vec![ESCROW_VERSION])),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::EscrowStatus;
+    use solana_program::program_pack::Pack;
+
+    #[test]
+    fn init_escrow_data_round_trips() {
+        let ix = init_escrow(
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &spl_token::id(),
+            500,
+            1_700_000_000,
+            3,
+            250,
+            [7; 32],
+            1_700_000_100,
+            false,
+        );
+
+        assert_eq!(
+            EscrowInstruction::unpack(&ix.data),
+            Ok(EscrowInstruction::InitEscrow {
+                amount: 500,
+                expiry_ts: 1_700_000_000,
+                nonce: 3,
+                fee_bps: 250,
+                memo: [7; 32],
+                cancel_after_ts: 1_700_000_100,
+                vesting: false,
+            })
+        );
+        // rent-sysvar-account 기능을 켜면 렌트 시스템 변수 어카운트가 하나 더 들어감
+        let expected_len = if cfg!(feature = "rent-sysvar-account") {
+            6
+        } else {
+            5
+        };
+        assert_eq!(ix.accounts.len(), expected_len);
+        assert_eq!(ix.accounts[expected_len - 1].pubkey, spl_token::id());
+        assert!(ix.accounts[0].is_signer);
+        assert!(ix.accounts[1].is_writable);
+        assert!(ix.accounts[3].is_writable);
+    }
+
+    #[test]
+    fn init_escrow_passes_token_program_and_vesting() {
+        let ix = init_escrow(
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &spl_token_2022::id(),
+            500,
+            1_700_000_000,
+            3,
+            0,
+            [0; 32],
+            0,
+            true,
+        );
+
+        assert_eq!(
+            EscrowInstruction::unpack(&ix.data),
+            Ok(EscrowInstruction::InitEscrow {
+                amount: 500,
+                expiry_ts: 1_700_000_000,
+                nonce: 3,
+                fee_bps: 0,
+                memo: [0; 32],
+                cancel_after_ts: 0,
+                vesting: true,
+            })
+        );
+        assert_eq!(ix.accounts.last().unwrap().pubkey, spl_token_2022::id());
+    }
+
+    #[test]
+    fn exchange_data_round_trips() {
+        let taker = Pubkey::new_unique();
+        let pda_temp_account = Pubkey::new_unique();
+        let escrow_account = Pubkey::new_unique();
+        let pda = Pubkey::new_unique();
+        let fee_account = Pubkey::new_unique();
+        let ix = exchange(
+            &Pubkey::new_unique(),
+            &taker,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &pda_temp_account,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &escrow_account,
+            &pda,
+            &fee_account,
+            &spl_token::id(),
+            &Pubkey::new_unique(),
+            Some(&Pubkey::new_unique()),
+            75,
+            false,
+            Some(6),
+        );
+
+        assert_eq!(
+            EscrowInstruction::unpack(&ix.data),
+            Ok(EscrowInstruction::Exchange {
+                amount: 75,
+                unwrap_sol: false,
+                decimals: Some(6),
+            })
+        );
+        assert!(!ix.accounts[0].is_writable);
+        assert_eq!(ix.accounts.len(), 10);
+        assert_eq!(ix.accounts[0].pubkey, taker);
+        assert!(ix.accounts[0].is_signer);
+        assert_eq!(ix.accounts[3].pubkey, pda_temp_account);
+        assert!(ix.accounts[3].is_writable);
+        assert_eq!(ix.accounts[6].pubkey, escrow_account);
+        assert!(ix.accounts[6].is_writable);
+        assert_eq!(ix.accounts[7].pubkey, spl_token::id());
+        assert_eq!(ix.accounts[8].pubkey, pda);
+        assert!(!ix.accounts[8].is_writable);
+        assert_eq!(ix.accounts[9].pubkey, fee_account);
+        assert!(ix.accounts[9].is_writable);
+    }
+
+    #[test]
+    fn exchange_passes_mints_for_token_2022() {
+        let y_mint = Pubkey::new_unique();
+        let x_mint = Pubkey::new_unique();
+        let exchange_ix = |x_mint: Option<&Pubkey>| {
+            exchange(
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &spl_token_2022::id(),
+                &y_mint,
+                x_mint,
+                75,
+                false,
+                None,
+            )
+        };
+
+        let ix = exchange_ix(Some(&x_mint));
+        assert_eq!(ix.accounts.len(), 12);
+        assert_eq!(ix.accounts[7].pubkey, spl_token_2022::id());
+        assert_eq!(ix.accounts[10].pubkey, y_mint);
+        assert!(!ix.accounts[10].is_writable);
+        assert_eq!(ix.accounts[11].pubkey, x_mint);
+        assert!(!ix.accounts[11].is_writable);
+
+        // SOL 에스크로나 베스팅 에스크로는 토큰 Y의 민트만 넘김
+        let ix = exchange_ix(None);
+        assert_eq!(ix.accounts.len(), 11);
+        assert_eq!(ix.accounts[10].pubkey, y_mint);
+    }
+
+    #[test]
+    fn init_escrow_with_transfer_uses_escrow_ata() {
+        let program_id = Pubkey::new_unique();
+        let initializer = Pubkey::new_unique();
+        let x_mint = Pubkey::new_unique();
+        let ix = init_escrow_with_transfer(
+            &program_id,
+            &initializer,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &x_mint,
+            &spl_token::id(),
+            10,
+            100,
+            0,
+            2,
+            0,
+        );
+
+        assert_eq!(
+            EscrowInstruction::unpack(&ix.data),
+            Ok(EscrowInstruction::InitEscrowWithTransfer {
+                amount: 10,
+                deposit_amount: 100,
+                expiry_ts: 0,
+                nonce: 2,
+                fee_bps: 0,
+            })
+        );
+        assert_eq!(ix.accounts.len(), 6);
+        assert_eq!(
+            ix.accounts[4].pubkey,
+            escrow_ata(&program_id, &initializer, 2, &x_mint, &spl_token::id())
+        );
+        assert!(ix.accounts[4].is_writable);
+    }
+
+    #[test]
+    fn init_escrow_with_transfer_passes_mint_for_token_2022() {
+        let program_id = Pubkey::new_unique();
+        let initializer = Pubkey::new_unique();
+        let x_mint = Pubkey::new_unique();
+        let ix = init_escrow_with_transfer(
+            &program_id,
+            &initializer,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &x_mint,
+            &spl_token_2022::id(),
+            10,
+            100,
+            0,
+            2,
+            0,
+        );
+
+        assert_eq!(ix.accounts.len(), 7);
+        assert_eq!(
+            ix.accounts[4].pubkey,
+            escrow_ata(&program_id, &initializer, 2, &x_mint, &spl_token_2022::id())
+        );
+        assert_eq!(ix.accounts[5].pubkey, spl_token_2022::id());
+        assert_eq!(ix.accounts[6].pubkey, x_mint);
+    }
+
+    #[test]
+    fn init_escrow_with_delegate_passes_pda() {
+        let program_id = Pubkey::new_unique();
+        let initializer = Pubkey::new_unique();
+        let ix = init_escrow_with_delegate(
+            &program_id,
+            &initializer,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &spl_token::id(),
+            10,
+            100,
+            0,
+            2,
+            0,
+        );
+
+        assert_eq!(
+            EscrowInstruction::unpack(&ix.data),
+            Ok(EscrowInstruction::InitEscrowWithDelegate {
+                amount: 10,
+                deposit_amount: 100,
+                expiry_ts: 0,
+                nonce: 2,
+                fee_bps: 0,
+            })
+        );
+        assert_eq!(ix.accounts.len(), 6);
+        assert_eq!(
+            ix.accounts[4].pubkey,
+            escrow_pda(&program_id, &initializer, 2).0
+        );
+        assert!(!ix.accounts[4].is_writable);
+    }
+
+    #[test]
+    fn escrow_ata_matches_manual_derivation() {
+        let program_id = Pubkey::new_unique();
+        let initializer = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+
+        // PDA를 구한 뒤 ATA 시드(소유자, 토큰 프로그램, 민트)로 직접 찾은 주소와 같아야 함
+        let (pda, _) = escrow_pda(&program_id, &initializer, 4);
+        let (expected, _) = Pubkey::find_program_address(
+            &[pda.as_ref(), spl_token::id().as_ref(), mint.as_ref()],
+            &spl_associated_token_account::id(),
+        );
+        assert_eq!(
+            escrow_ata(&program_id, &initializer, 4, &mint, &spl_token::id()),
+            expected
+        );
+    }
+
+    #[test]
+    fn escrow_filters_match_only_initialized_escrows() {
+        let filters = escrow_filters();
+        assert_eq!(filters.len(), 2);
+        assert_eq!(filters[0], RpcFilterType::DataSize(Escrow::LEN as u64));
+
+        let RpcFilterType::Memcmp(memcmp) = &filters[1] else {
+            panic!("expected a memcmp filter");
+        };
+        // 버전 바이트는 계정 데이터의 맨 앞
+        assert_eq!(memcmp.offset(), 0);
+
+        let escrow = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: Pubkey::new_unique(),
+            expected_amount: 10,
+            remaining_amount: 10,
+            ..Escrow::default()
+        };
+        let mut initialized = [0u8; Escrow::LEN];
+        Escrow::pack(escrow, &mut initialized).unwrap();
+        assert!(memcmp.bytes_match(&initialized));
+        assert!(!memcmp.bytes_match(&[0u8; Escrow::LEN]));
+    }
+}
@@ -1,5 +1,5 @@
 use solana_program::{
-    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult, instruction, pubkey::Pubkey,
+    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult, pubkey::Pubkey,
 };
 
 use crate::processor::Processor;
@@ -16,5 +16,13 @@ fn process_instruction(
     // 명령 데이터 (state?)
     instruction_data: &[u8],
 ) -> ProgramResult {
+    // 실패한 트랜잭션을 디버깅할 때 실제로 어떤 값이 들어왔는지 확인
+    #[cfg(feature = "debug-logs")]
+    solana_program::msg!(
+        "Program {} received {} accounts",
+        program_id,
+        accounts.len()
+    );
+
     Processor::process(program_id, accounts, instruction_data)
 }
@@ -1,22 +1,323 @@
 use solana_program::program_error::ProgramError;
 use thiserror::Error;
 
+// 에러 코드는 ProgramError::Custom(n)으로 클라이언트에 전달되므로
+// 각 값을 명시해두고 새 에러는 항상 마지막에 추가함
 #[derive(Error, Debug, Copy, Clone)]
+#[repr(u32)]
 pub enum EscrowError {
-    // 유효하지 않은 명령에 대한 에러
+    // 0: 유효하지 않은 명령에 대한 에러
     #[error("Invalid Instruction")]
-    InvalidInstruction,
+    InvalidInstruction = 0,
 
-    // 임대료(렌트비) 면제 아님
+    // 1: 임대료(렌트비) 면제 아님
     #[error("Not Rent Exempt")]
-    NotRentExcept,
+    NotRentExcept = 1,
+
+    // 2: 에스크로에 기록된 임시 토큰 계정과 다른 계정
+    #[error("Invalid Temp Token Account")]
+    InvalidTempTokenAccount = 2,
+
+    // 3: 테이커가 보낸 수량이 예상 수량과 다름
+    #[error("Amount does not match expected")]
+    ExpectedAmountMismatch = 3,
+
+    // 4: 만료된 에스크로
+    #[error("Escrow Expired")]
+    EscrowExpired = 4,
+
+    // 5: 거래 대기 중(Active)이 아닌 에스크로
+    #[error("Escrow Not Active")]
+    EscrowNotActive = 5,
+
+    // 6: 토큰 계정의 민트가 에스크로에 기록된 민트와 다름
+    #[error("Token Mint Mismatch")]
+    TokenMintMismatch = 6,
+
+    // 7: 명령 데이터가 태그에 필요한 길이보다 짧음
+    #[error("Instruction Too Short")]
+    InstructionTooShort = 7,
+
+    // 8: 수수료(베이시스 포인트)가 10_000(100%)을 넘음
+    #[error("Invalid Fee")]
+    InvalidFee = 8,
+
+    // 9: 수량이나 램포트를 더하고 곱하다 u64 범위를 넘음
+    #[error("Amount Overflow")]
+    AmountOverflow = 9,
+
+    // 10: 임시 토큰 계정의 현재 소유자가 이니셜라이저가 아니라 권한을 옮길 수 없음
+    #[error("Invalid Authority")]
+    InvalidAuthority = 10,
+
+    // 11: 이니셜라이저가 자기 에스크로를 테이커로 교환하려 함
+    #[error("Self Trade Not Allowed")]
+    SelfTradeNotAllowed = 11,
+
+    // 12: 에스크로 금액이 MAX_ESCROW_AMOUNT를 넘음
+    #[error("Amount Too Large")]
+    AmountTooLarge = 12,
+
+    // 13: 토큰 프로그램이 SPL Token 또는 Token-2022가 아님
+    #[error("Invalid Token Program")]
+    InvalidTokenProgram = 13,
+
+    // 14: 넘겨 받은 PDA 계정이 에스크로의 PDA가 아님
+    #[error("Invalid PDA")]
+    InvalidPda = 14,
+
+    // 15: 한 번에 만들 에스크로 수가 MAX_BATCH_SIZE를 넘음
+    #[error("Batch Too Large")]
+    BatchTooLarge = 15,
+
+    // 16: 이미 초기화된 에스크로 계정으로 다시 초기화하려 함
+    #[error("Escrow Already Initialized")]
+    EscrowAlreadyInitialized = 16,
+
+    // 17: 취소 가능 시각(cancel_after_ts) 전에 취소하려 함
+    #[error("Cancel Too Early")]
+    CancelTooEarly = 17,
+
+    // 18: 테이커가 계산한 토큰 Y의 소수점 자릿수가 에스크로에 기록된 민트의 자릿수와 다름
+    #[error("Decimals Mismatch")]
+    DecimalsMismatch = 18,
+
+    // 19: 만료 시각이 지금보다 미래가 아님
+    #[error("Invalid Expiry")]
+    InvalidExpiry = 19,
+
+    // 20: 민트의 소수점 자릿수가 MAX_DECIMALS보다 큼
+    #[error("Unsupported Decimals")]
+    UnsupportedDecimals = 20,
+
+    // 21: 베스팅 에스크로에 테이커가 찾아가지 않은 토큰 X가 남아 있음
+    #[error("Vesting Not Claimed")]
+    VestingNotClaimed = 21,
+
+    // 22: 에스크로가 만들어진 지 MAX_ESCROW_AGE_SECS보다 오래됨
+    #[error("Escrow is older than the maximum age")]
+    EscrowStale = 22,
+
+    // 23: set_authority CPI 뒤에도 토큰 계정의 소유자가 PDA가 아님
+    #[error("Token account owner did not change to the PDA")]
+    AuthorityTransferFailed = 23,
+
+    // 24: 위임으로 맡긴 에스크로의 위임이 바뀌어 PDA가 내보낼 토큰 X를 보낼 수 없음
+    #[error("Delegation Changed")]
+    DelegationChanged = 24,
+}
+
+impl EscrowError {
+    // 모든 에러 (코드 순서, ALL[n]의 코드가 n)
+    // 클라이언트가 Custom(n)을 에러로 되돌릴 때 씀, 새 에러를 추가하면 여기에도 추가
+    pub const ALL: [EscrowError; 25] = [
+        EscrowError::InvalidInstruction,
+        EscrowError::NotRentExcept,
+        EscrowError::InvalidTempTokenAccount,
+        EscrowError::ExpectedAmountMismatch,
+        EscrowError::EscrowExpired,
+        EscrowError::EscrowNotActive,
+        EscrowError::TokenMintMismatch,
+        EscrowError::InstructionTooShort,
+        EscrowError::InvalidFee,
+        EscrowError::AmountOverflow,
+        EscrowError::InvalidAuthority,
+        EscrowError::SelfTradeNotAllowed,
+        EscrowError::AmountTooLarge,
+        EscrowError::InvalidTokenProgram,
+        EscrowError::InvalidPda,
+        EscrowError::BatchTooLarge,
+        EscrowError::EscrowAlreadyInitialized,
+        EscrowError::CancelTooEarly,
+        EscrowError::DecimalsMismatch,
+        EscrowError::InvalidExpiry,
+        EscrowError::UnsupportedDecimals,
+        EscrowError::VestingNotClaimed,
+        EscrowError::EscrowStale,
+        EscrowError::AuthorityTransferFailed,
+        EscrowError::DelegationChanged,
+    ];
+
+    // ProgramError::Custom으로 전달되는 에러 코드
+    pub fn code(&self) -> u32 {
+        *self as u32
+    }
+
+    // 클라이언트가 사용자에게 보여줄 짧은 메시지 (#[error]의 메시지와 같음)
+    pub fn message(&self) -> &'static str {
+        match self {
+            EscrowError::InvalidInstruction => "Invalid Instruction",
+            EscrowError::NotRentExcept => "Not Rent Exempt",
+            EscrowError::InvalidTempTokenAccount => "Invalid Temp Token Account",
+            EscrowError::ExpectedAmountMismatch => "Amount does not match expected",
+            EscrowError::EscrowExpired => "Escrow Expired",
+            EscrowError::EscrowNotActive => "Escrow Not Active",
+            EscrowError::TokenMintMismatch => "Token Mint Mismatch",
+            EscrowError::InstructionTooShort => "Instruction Too Short",
+            EscrowError::InvalidFee => "Invalid Fee",
+            EscrowError::AmountOverflow => "Amount Overflow",
+            EscrowError::InvalidAuthority => "Invalid Authority",
+            EscrowError::SelfTradeNotAllowed => "Self Trade Not Allowed",
+            EscrowError::AmountTooLarge => "Amount Too Large",
+            EscrowError::InvalidTokenProgram => "Invalid Token Program",
+            EscrowError::InvalidPda => "Invalid PDA",
+            EscrowError::BatchTooLarge => "Batch Too Large",
+            EscrowError::EscrowAlreadyInitialized => "Escrow Already Initialized",
+            EscrowError::CancelTooEarly => "Cancel Too Early",
+            EscrowError::DecimalsMismatch => "Decimals Mismatch",
+            EscrowError::InvalidExpiry => "Invalid Expiry",
+            EscrowError::UnsupportedDecimals => "Unsupported Decimals",
+            EscrowError::VestingNotClaimed => "Vesting Not Claimed",
+            EscrowError::EscrowStale => "Escrow is older than the maximum age",
+            EscrowError::AuthorityTransferFailed => "Token account owner did not change to the PDA",
+            EscrowError::DelegationChanged => "Delegation Changed",
+        }
+    }
+
+    // 에러 코드로 에러를 찾음 (이 프로그램의 에러가 아니면 None)
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::ALL.get(code as usize).copied()
+    }
 }
 
 // From은 무엇?
 // *** ProgramError에 EsocrowError를 확장해 추가(?)
 impl From<EscrowError> for ProgramError {
     // EscrowError 형식을 받아 ProgramError로 반환
+    // 위에 명시한 에러 값이 그대로 Custom 코드가 됨
     fn from(e: EscrowError) -> Self {
         ProgramError::Custom(e as u32)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_codes_are_stable() {
+        assert_eq!(
+            ProgramError::from(EscrowError::InvalidInstruction),
+            ProgramError::Custom(0)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::NotRentExcept),
+            ProgramError::Custom(1)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::InvalidTempTokenAccount),
+            ProgramError::Custom(2)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::ExpectedAmountMismatch),
+            ProgramError::Custom(3)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::EscrowExpired),
+            ProgramError::Custom(4)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::EscrowNotActive),
+            ProgramError::Custom(5)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::TokenMintMismatch),
+            ProgramError::Custom(6)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::InstructionTooShort),
+            ProgramError::Custom(7)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::InvalidFee),
+            ProgramError::Custom(8)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::AmountOverflow),
+            ProgramError::Custom(9)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::InvalidAuthority),
+            ProgramError::Custom(10)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::SelfTradeNotAllowed),
+            ProgramError::Custom(11)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::AmountTooLarge),
+            ProgramError::Custom(12)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::InvalidTokenProgram),
+            ProgramError::Custom(13)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::InvalidPda),
+            ProgramError::Custom(14)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::BatchTooLarge),
+            ProgramError::Custom(15)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::EscrowAlreadyInitialized),
+            ProgramError::Custom(16)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::CancelTooEarly),
+            ProgramError::Custom(17)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::DecimalsMismatch),
+            ProgramError::Custom(18)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::InvalidExpiry),
+            ProgramError::Custom(19)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::UnsupportedDecimals),
+            ProgramError::Custom(20)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::VestingNotClaimed),
+            ProgramError::Custom(21)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::EscrowStale),
+            ProgramError::Custom(22)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::AuthorityTransferFailed),
+            ProgramError::Custom(23)
+        );
+        assert_eq!(
+            ProgramError::from(EscrowError::DelegationChanged),
+            ProgramError::Custom(24)
+        );
+    }
+
+    #[test]
+    fn code_matches_program_error_mapping() {
+        for (i, error) in EscrowError::ALL.iter().enumerate() {
+            assert_eq!(error.code(), i as u32);
+            assert_eq!(
+                ProgramError::from(*error),
+                ProgramError::Custom(error.code())
+            );
+            assert_eq!(
+                EscrowError::from_code(error.code()).unwrap().code(),
+                i as u32
+            );
+        }
+        assert!(EscrowError::from_code(EscrowError::ALL.len() as u32).is_none());
+    }
+
+    #[test]
+    fn message_matches_display() {
+        for error in EscrowError::ALL {
+            assert_eq!(error.message(), error.to_string());
+        }
+    }
+}
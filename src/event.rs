@@ -0,0 +1,55 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{log::sol_log_data, pubkey::Pubkey};
+
+// 오프체인 리스너가 msg! 문자열을 파싱하지 않고도
+// 무슨 일이 있었는지 알 수 있도록 borsh로 직렬화한 이벤트를 로그로 남김
+// (로그에는 "Program data: <base64>" 형태로 찍힘)
+//
+// 첫 바이트는 borsh의 이넘 판별값(variant 순서)이므로
+// 순서를 바꾸지 말고 새 이벤트는 항상 마지막에 추가함
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub enum EscrowEvent {
+    // 0: 에스크로가 만들어짐 (에스크로 계정, 예상 금액)
+    Initialized { pubkey: Pubkey, amount: u64 },
+
+    // 1: 남은 수량을 모두 채워 교환이 완료됨
+    Completed,
+
+    // 2: 이니셜라이저가 에스크로를 취소함
+    Cancelled,
+}
+
+impl EscrowEvent {
+    /// 이벤트를 borsh로 직렬화해 `sol_log_data`로 남깁니다.
+    pub fn emit(&self) {
+        // Vec에 쓰는 직렬화는 실패하지 않지만 이벤트 때문에 명령이 실패하지 않도록 에러는 무시
+        if let Ok(data) = borsh::to_vec(self) {
+            sol_log_data(&[&data]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn initialized_event_round_trips() {
+        let event = EscrowEvent::Initialized {
+            pubkey: Pubkey::new_unique(),
+            amount: 42,
+        };
+
+        let data = borsh::to_vec(&event).unwrap();
+        // 판별값(1) + Pubkey(32) + u64(8)
+        assert_eq!(data.len(), 41);
+        assert_eq!(data[0], 0);
+        assert_eq!(borsh::from_slice::<EscrowEvent>(&data).unwrap(), event);
+    }
+
+    #[test]
+    fn event_discriminants_are_stable() {
+        assert_eq!(borsh::to_vec(&EscrowEvent::Completed).unwrap(), vec![1]);
+        assert_eq!(borsh::to_vec(&EscrowEvent::Cancelled).unwrap(), vec![2]);
+    }
+}
@@ -1,8 +1,82 @@
 use std::convert::TryInto;
-use solana_program::program_error::ProgramError;
+use borsh::BorshDeserialize;
+use solana_program::{
+    program_error::ProgramError,
+    pubkey::{Pubkey, MAX_SEED_LEN},
+};
 
-use crate::error::EscrowError::InvalidInstruction;
+use crate::error::EscrowError::{InstructionTooShort, InvalidInstruction};
 
+/// 명령 데이터의 첫 바이트(태그) 값
+/// unpack과 클라이언트의 명령 빌더가 같은 값을 쓰도록 한 곳에 모아 둠
+///
+/// 금액 필드(InitEscrow, Exchange, UpdateExpectedAmount, InitSolEscrow, InitEscrowWithSeed,
+/// InitEscrowWithTransfer, InitEscrowWithDelegate의 금액)는 [`amount_from_bytes`]로 읽습니다.
+/// 기본은 리틀 엔디언이고 `be-amounts` 기능을 켜면 빅 엔디언입니다.
+/// 만료 시각, 논스, 수수료 등 나머지 정수와 borsh로 인코딩하는 InitEscrowBatch, ReprovisionEscrow의 값은
+/// 항상 리틀 엔디언입니다.
+pub mod tag {
+    pub const INIT_ESCROW: u8 = 0;
+    pub const EXCHANGE: u8 = 1;
+    pub const CANCEL_ESCROW: u8 = 2;
+    pub const UPDATE_EXPECTED_AMOUNT: u8 = 3;
+    pub const CLOSE_ESCROW: u8 = 4;
+    pub const INIT_SOL_ESCROW: u8 = 5;
+    pub const QUERY_ESCROW: u8 = 6;
+    pub const INIT_ESCROW_WITH_SEED: u8 = 7;
+    pub const TRANSFER_INITIALIZER: u8 = 8;
+    pub const INIT_ESCROW_BATCH: u8 = 9;
+    pub const INIT_ESCROW_WITH_TRANSFER: u8 = 10;
+    pub const VALIDATE_INIT: u8 = 11;
+    pub const REPROVISION_ESCROW: u8 = 12;
+    pub const INIT_ESCROW_WITH_DELEGATE: u8 = 13;
+    pub const CLAIM_VESTED: u8 = 14;
+    pub const SWEEP_PDA: u8 = 15;
+    pub const CANCEL_EXPIRED: u8 = 16;
+}
+
+/// InitEscrow 데이터의 최대 길이 (태그 제외, 메모, 취소 가능 시각, 베스팅까지 모두 붙인 길이)
+pub const INIT_ESCROW_LEN: usize = 67;
+
+/// InitEscrowBatch에서 금액들 뒤에 붙는 데이터의 길이 (첫 논스 8 + 만료 시각 8 + 수수료 2)
+const INIT_ESCROW_BATCH_TAIL_LEN: usize = 18;
+
+/// 버전 바이트가 붙은 InitEscrow의 현재 마이너 버전
+///
+/// 태그 0 뒤의 데이터가 [`INIT_ESCROW_LEN`]보다 길면 버전이 붙은 형식으로 읽습니다.
+/// 태그(1) + 마이너 버전(1) + 지금의 InitEscrow 데이터(67바이트, 금액부터 베스팅까지) 순서이고
+/// 앞으로 필드를 더하면 마이너 버전을 올리고 그 뒤에 붙입니다.
+/// 이 프로그램보다 새로운 마이너 버전이면 알고 있는 67바이트만 읽고 뒤에 붙은 바이트는 무시하므로
+/// 새 클라이언트가 보낸 명령도 예전 프로그램에서 그대로 동작합니다.
+/// 마이너 버전 0은 쓰지 않고, 현재 버전과 같으면 뒤에 남는 바이트가 없어야 합니다.
+/// (버전이 없는 예전 형식은 최대 67바이트이므로 길이로 둘을 구분함)
+pub const INIT_ESCROW_MINOR_VERSION: u8 = 1;
+
+/// 금액 필드 8바이트를 u64로 변환 (기본 리틀 엔디언)
+#[cfg(not(feature = "be-amounts"))]
+pub fn amount_from_bytes(bytes: [u8; 8]) -> u64 {
+    u64::from_le_bytes(bytes)
+}
+
+/// 금액 필드 8바이트를 u64로 변환 (빅 엔디언을 쓰는 브리지 도구용)
+#[cfg(feature = "be-amounts")]
+pub fn amount_from_bytes(bytes: [u8; 8]) -> u64 {
+    u64::from_be_bytes(bytes)
+}
+
+/// amount_from_bytes의 반대, 클라이언트가 금액 필드를 만들 때 사용
+#[cfg(not(feature = "be-amounts"))]
+pub fn amount_to_bytes(amount: u64) -> [u8; 8] {
+    amount.to_le_bytes()
+}
+
+/// amount_from_bytes의 반대, 클라이언트가 금액 필드를 만들 때 사용
+#[cfg(feature = "be-amounts")]
+pub fn amount_to_bytes(amount: u64) -> [u8; 8] {
+    amount.to_be_bytes()
+}
+
+#[derive(Debug, PartialEq)]
 pub enum EscrowInstruction {
     /// 에스크로 계정을 생성 및 채우고 주어진 임시 토큰 계정의 소유권을 PDA로 이전하여 거래를 시작합니다.
     ///
@@ -10,37 +84,718 @@ pub enum EscrowInstruction {
     /// 예상 계정:
     ///
     /// 0. `[signer]` 에스크로를 초기화하는 사람의 계정
-    /// 1. `[writable]` 이 명령어 이전에 생성되어야 하고 이니셜라이저가 소유해야 하는 임시 토큰 계정
+    /// 1. `[writable]` 이 명령어 이전에 생성되어야 하고 이니셜라이저가 소유해야 하는 임시 토큰 계정 (렌트비 면제)
     /// 2. `[]` 거래가 진행되면 받을 토큰에 대한 이니셜라이저의 토큰 계정
     /// 3. `[writable]` 에스크로 계정은 거래에 필요한 모든 정보를 보유합니다.
-    /// 4. `[]` 임대 시스템 변수
-    /// 5. `[]` 토큰 프로그램
+    /// 4. `[]` 토큰 프로그램
+    /// 5. `[]` (선택) 토큰 Y의 민트. 넘기면 소수점 자릿수를 기록해 교환 때 확인합니다.
+    /// 6. `[]` (선택) 토큰 X의 민트. 5번을 넘길 때만 넘길 수 있습니다.
+    /// 7. `[writable]` (선택) 이니셜라이저의 레지스트리 PDA (시드 `["registry", 이니셜라이저]`)
+    /// 8. `[]` 시스템 프로그램 (7번을 넘길 때)
+    ///
+    /// 넘긴 민트의 소수점 자릿수가 18보다 크면 `UnsupportedDecimals` 에러로 거절합니다.
+    ///
+    /// 레지스트리를 넘기면 새 에스크로를 열린 에스크로 목록에 추가합니다. 민트 없이 5, 6번에 넘겨도 됩니다.
+    /// 레지스트리를 처음 만들거나 늘릴 때 렌트비를 내므로 이때 0번은 writable이어야 합니다.
+    ///
+    /// 임대 시스템 변수는 `Rent::get()`으로 읽습니다.
+    /// `rent-sysvar-account` 기능을 켜면 예전처럼 4번에 임대 시스템 변수를 받고 토큰 프로그램은 5번이 됩니다.
+    ///
+    /// 다른 프로그램이 CPI로 호출할 때는 0번에 그 프로그램의 PDA를 넘기고
+    /// `invoke_signed`에 그 PDA의 시드(범프 포함)를 넘겨 서명합니다.
+    /// 이때 1번 임시 토큰 계정의 소유자도 그 PDA여야 하고, 서명 권한이 set_authority CPI로 이어집니다.
     ///
     /// ***이넘인데 스트럭트(?)
     InitEscrow {
         /// 당사자 A가 받게 될 토큰 Y의 예상하는 금액
         amount: u64,
+        /// 에스크로 만료 시각 (유닉스 타임스탬프, 0이면 만료되지 않음)
+        expiry_ts: i64,
+        /// PDA 시드에 들어갈 논스 (이니셜라이저의 에스크로마다 달라야 함)
+        nonce: u64,
+        /// 교환 때 토큰 Y에서 떼는 수수료 (베이시스 포인트, 최대 10_000)
+        fee_bps: u16,
+        /// 오프체인 참조용 메모 (선택, 생략하면 0으로 채워져 메모 없음)
+        memo: [u8; 32],
+        /// 이 시각부터 취소할 수 있음 (선택, 메모 뒤에 붙음, 생략하거나 0이면 언제든 취소 가능)
+        cancel_after_ts: i64,
+        /// 베스팅 에스크로 여부 (선택, 취소 가능 시각 뒤에 붙는 1바이트, 생략하면 false)
+        /// true면 교환 때 토큰 X를 바로 보내지 않고 만든 시각부터 `expiry_ts`까지 선형으로 풀어
+        /// 테이커가 ClaimVested로 찾아갑니다. 이때 `expiry_ts`는 지금보다 미래여야 합니다.
+        vesting: bool,
+    },
+
+    /// 교환을 수락합니다.
+    ///
+    ///
+    /// 예상 계정:
+    ///
+    /// 0. `[signer]` 거래를 수락하는 사람(테이커)의 계정 (`unwrap_sol`이면 writable)
+    /// 1. `[writable]` 테이커가 보낼 토큰 Y의 토큰 계정
+    /// 2. `[writable]` 테이커가 받을 토큰 X의 토큰 계정
+    /// 3. `[writable]` PDA가 소유한 임시 토큰 계정 (토큰 X를 보관)
+    /// 4. `[writable]` 이니셜라이저의 메인 계정 (임시 계정이 닫히면 렌트비를 돌려받음)
+    /// 5. `[writable]` 토큰 Y를 받을 이니셜라이저의 토큰 계정
+    /// 6. `[writable]` 거래 정보를 보유한 에스크로 계정
+    /// 7. `[]` 토큰 프로그램 (이니셜라이즈 때 기록한 토큰 프로그램과 같아야 함)
+    /// 8. `[]` PDA 계정
+    /// 9. `[writable]` 수수료(토큰 Y)를 받을 토큰 계정
+    /// 10. `[]` (Token-2022일 때) 토큰 Y의 민트
+    /// 11. `[]` (Token-2022이고 토큰 X를 바로 내보낼 때) 토큰 X의 민트
+    ///
+    /// Token-2022는 전송 수수료나 전송 훅 확장이 붙은 민트에서도 동작하도록 `transfer_checked`로 보내므로
+    /// 민트를 더 받습니다. SOL 에스크로와 베스팅 에스크로는 11번이 필요 없습니다.
+    ///
+    /// 잠긴 토큰 X가 랩트 SOL(네이티브 민트)이고 `unwrap_sol`이 켜져 있으면
+    /// 받은 뒤 2번 계정을 닫아 램포트로 풀어서 0번 테이커 계정으로 보냅니다.
+    ///
+    /// `decimals`를 넘기면 에스크로에 기록된 토큰 Y 민트의 자릿수와 같아야 합니다.
+    ///
+    /// 베스팅 에스크로는 남은 수량을 한 번에 채워야 하고, 토큰 X는 바로 보내지 않고
+    /// 테이커가 ClaimVested로 풀린 만큼씩 찾아갑니다.
+    ///
+    /// 만든 지 `MAX_ESCROW_AGE_SECS`보다 오래된 에스크로는 만료 시각과 상관없이 교환할 수 없습니다.
+    Exchange {
+        /// 테이커가 보내는 토큰 Y의 수량 (에스크로의 남은 수량 이하, 일부만 채울 수 있음)
+        amount: u64,
+        /// 받은 랩트 SOL을 램포트로 풀지 여부 (선택, 생략하면 false)
+        unwrap_sol: bool,
+        /// 테이커가 금액을 계산할 때 쓴 토큰 Y의 소수점 자릿수 (선택, unwrap_sol 뒤에 붙음)
+        decimals: Option<u8>,
+    },
+
+    /// 에스크로를 취소하고 임시 토큰 계정의 소유권을 이니셜라이저에게 되돌립니다.
+    /// 에스크로 계정은 `Cancelled` 상태로 남고 렌트비는 CloseEscrow로 돌려받습니다.
+    /// InitEscrow의 `cancel_after_ts` 전에는 취소할 수 없습니다.
+    ///
+    ///
+    /// 예상 계정:
+    ///
+    /// 0. `[signer]` 에스크로를 초기화했던 사람의 계정
+    /// 1. `[writable]` PDA가 소유한 임시 토큰 계정
+    /// 2. `[writable]` 거래 정보를 보유한 에스크로 계정
+    /// 3. `[]` 토큰 프로그램
+    /// 4. `[]` PDA 계정
+    ///
+    /// InitEscrowWithTransfer로 토큰을 옮겨 맡긴 에스크로는 1번에 PDA의 보관 계정을 넘기고
+    /// 5번 `[writable]`에 토큰 X를 돌려받을 이니셜라이저의 토큰 계정을 더 받습니다.
+    /// Token-2022면 그 뒤 6번 `[]`에 토큰 X의 민트도 받습니다.
+    /// 비워진 보관 계정은 닫히고 렌트비는 0번 계정(이때는 writable)으로 돌아갑니다.
+    ///
+    /// SOL 에스크로는 1번에 에스크로 계정을 넘기고 3, 4번은 필요 없습니다.
+    /// (맡긴 램포트는 CloseEscrow로 에스크로 계정을 닫을 때 돌려받습니다.)
+    ///
+    /// 필요한 계정 뒤에 `[writable]` 이니셜라이저의 레지스트리 PDA를 넘기면 목록에서 에스크로를 뺍니다.
+    CancelEscrow,
+
+    /// 거래가 진행되기 전에 이니셜라이저가 예상 금액을 바꿉니다.
+    ///
+    ///
+    /// 예상 계정:
+    ///
+    /// 0. `[signer]` 에스크로를 초기화했던 사람의 계정
+    /// 1. `[writable]` 거래 정보를 보유한 에스크로 계정
+    UpdateExpectedAmount {
+        /// 새로 바꿀 토큰 Y의 예상 금액
+        new_amount: u64,
+    },
+
+    /// 완료되거나 취소된 에스크로 계정을 닫고 렌트비를 이니셜라이저에게 돌려줍니다.
+    ///
+    ///
+    /// 예상 계정:
+    ///
+    /// 0. `[signer, writable]` 에스크로를 초기화했던 사람의 계정 (3번을 넘기면 writable이 아니어도 됨)
+    /// 1. `[writable]` 거래 정보를 보유한 에스크로 계정
+    /// 2. `[writable]` (선택) 이니셜라이저의 레지스트리 PDA. 넘기면 목록에서 에스크로를 뺍니다.
+    /// 3. `[writable]` (선택) 렌트비를 대신 받을 계정 (예: 계정 생성비를 낸 트레저리나 릴레이어)
+    ///
+    /// 2, 3번은 각각 따로 넘길 수 있습니다. 이 프로그램이 소유한 계정은 레지스트리로,
+    /// 아닌 계정은 렌트비를 받을 계정으로 봅니다.
+    ///
+    /// 교환된 베스팅 에스크로는 테이커가 토큰 X를 모두 찾아간 뒤에만 닫을 수 있습니다.
+    CloseEscrow,
+
+    /// 토큰 X 대신 SOL(램포트)을 맡기고 토큰 Y를 기다리는 에스크로를 시작합니다.
+    /// 램포트는 에스크로 계정 자체에 (렌트비 위로) 보관됩니다.
+    ///
+    ///
+    /// 예상 계정:
+    ///
+    /// 0. `[signer, writable]` 에스크로를 초기화하는 사람의 계정 (램포트를 보냄)
+    /// 1. `[]` 거래가 진행되면 받을 토큰에 대한 이니셜라이저의 토큰 계정
+    /// 2. `[writable]` 에스크로 계정은 거래에 필요한 모든 정보와 맡긴 램포트를 보유합니다.
+    /// 3. `[]` 토큰 프로그램
+    /// 4. `[]` 시스템 프로그램
+    /// 5. `[writable]` (선택) 이니셜라이저의 레지스트리 PDA. 넘기면 새 에스크로를 열린 에스크로 목록에 추가합니다.
+    ///
+    /// 교환할 때는 Exchange의 2번에 램포트를 받을 테이커의 계정, 3번에 에스크로 계정을 넘깁니다.
+    InitSolEscrow {
+        /// 에스크로에 맡길 램포트
+        amount: u64,
+        /// 당사자 A가 받게 될 토큰 Y의 예상하는 금액
+        expected_amount: u64,
+    },
+
+    /// 에스크로를 바꾸지 않고 읽기만 합니다.
+    /// `(expected_amount: u64 LE, status: u8, initializer_pubkey: [u8; 32])` 41바이트를
+    /// `set_return_data`로 남기므로 CPI로 호출한 프로그램이 `get_return_data`로 읽을 수 있습니다.
+    ///
+    ///
+    /// 예상 계정:
+    ///
+    /// 0. `[]` 거래 정보를 보유한 에스크로 계정
+    QueryEscrow,
+
+    /// InitEscrow와 같지만 에스크로 계정을 미리 만들지 않아도 됩니다.
+    /// 프로그램이 `create_account_with_seed`로 에스크로 계정을 만들고
+    /// 렌트비 면제 램포트를 채운 뒤 같은 명령 안에서 초기화합니다.
+    ///
+    ///
+    /// 예상 계정:
+    ///
+    /// 0. `[signer, writable]` 에스크로를 초기화하는 사람의 계정 (렌트비를 냄, 시드의 베이스)
+    /// 1. `[writable]` 이 명령어 이전에 생성되어야 하고 이니셜라이저가 소유해야 하는 임시 토큰 계정
+    /// 2. `[]` 거래가 진행되면 받을 토큰에 대한 이니셜라이저의 토큰 계정
+    /// 3. `[writable]` `Pubkey::create_with_seed(이니셜라이저, seed, 프로그램)` 주소의 에스크로 계정
+    /// 4. `[]` 토큰 프로그램
+    /// 5. `[]` 시스템 프로그램
+    ///
+    /// InitEscrow처럼 레지스트리에 추가하려면 시스템 프로그램 바로 앞에 이니셜라이저의 레지스트리 PDA를 넣습니다.
+    /// 마지막 시스템 프로그램은 에스크로 계정과 레지스트리를 만들 때 같이 씁니다.
+    ///
+    /// `rent-sysvar-account` 기능을 켜면 InitEscrow처럼 4번에 임대 시스템 변수를 받고 하나씩 밀립니다.
+    InitEscrowWithSeed {
+        /// 당사자 A가 받게 될 토큰 Y의 예상하는 금액
+        amount: u64,
+        /// 에스크로 만료 시각 (유닉스 타임스탬프, 0이면 만료되지 않음)
+        expiry_ts: i64,
+        /// PDA 시드에 들어갈 논스 (이니셜라이저의 에스크로마다 달라야 함)
+        nonce: u64,
+        /// 교환 때 토큰 Y에서 떼는 수수료 (베이시스 포인트, 최대 10_000)
+        fee_bps: u16,
+        /// 에스크로 계정 주소를 만들 시드 (1~32바이트 UTF-8)
+        seed: String,
+    },
+
+    /// 에스크로의 이니셜라이저 권리를 다른 사람에게 넘깁니다.
+    /// 넘긴 뒤에는 새 이니셜라이저만 취소, 금액 변경, 닫기를 할 수 있고
+    /// 임시 토큰 계정과 거래 뒤 남는 렌트비도 새 이니셜라이저에게 돌아갑니다.
+    ///
+    ///
+    /// 예상 계정:
+    ///
+    /// 0. `[signer]` 지금 에스크로의 이니셜라이저 계정
+    /// 1. `[writable]` 거래 정보를 보유한 에스크로 계정
+    /// 2. `[]` (선택) 새 이니셜라이저가 토큰 Y를 받을 토큰 계정
+    /// 3. `[writable]` (선택) 지금 이니셜라이저의 레지스트리 PDA
+    /// 4. `[writable]` (선택) 새 이니셜라이저의 레지스트리 PDA (아직 만들지 않았어도 됨)
+    /// 5. `[]` 시스템 프로그램 (3, 4번을 넘길 때)
+    ///
+    /// 2번을 넘기지 않으면 토큰 Y는 계속 예전 받을 계정으로 들어갑니다.
+    /// 레지스트리를 넘기면 에스크로를 지금 이니셜라이저의 목록에서 새 이니셜라이저의 목록으로 옮깁니다.
+    /// 2번 없이 3, 4, 5번만 넘겨도 되고, 새 레지스트리를 만들거나 늘리는 렌트비를 내므로 이때 0번은 writable이어야 합니다.
+    TransferInitializer {
+        /// 새 이니셜라이저
+        new_initializer: Pubkey,
+    },
+
+    /// 한 명령으로 여러 에스크로를 초기화합니다. (각각 InitEscrow와 같음)
+    /// i번째 에스크로는 논스 `base_nonce + i`를 쓰고 만료 시각과 수수료는 모든 에스크로가 같습니다.
+    /// 배치마다, 그리고 따로 연 InitEscrow와도 논스가 겹치지 않도록 `base_nonce`를 골라야 합니다.
+    ///
+    ///
+    /// 예상 계정:
+    ///
+    /// 0. `[signer]` 에스크로를 초기화하는 사람의 계정
+    /// 1 + 3i. `[writable]` i번째 에스크로의 임시 토큰 계정 (이니셜라이저 소유)
+    /// 2 + 3i. `[]` i번째 에스크로에서 받을 토큰에 대한 이니셜라이저의 토큰 계정
+    /// 3 + 3i. `[writable]` i번째 에스크로 계정
+    /// 1 + 3n. `[]` 토큰 프로그램 (n은 에스크로 수)
+    ///
+    /// 토큰 프로그램 뒤의 계정들은 모든 에스크로가 InitEscrow처럼 같이 씁니다.
+    /// 그래서 토큰 프로그램 뒤에 `[writable]` 이니셜라이저의 레지스트리 PDA와 시스템 프로그램을 넘기면
+    /// 모든 에스크로를 열린 에스크로 목록에 추가합니다. (이때 0번은 writable)
+    ///
+    /// `rent-sysvar-account` 기능을 켜면 토큰 프로그램 앞에 임대 시스템 변수를 받습니다.
+    InitEscrowBatch {
+        /// 에스크로마다 당사자 A가 받게 될 토큰 Y의 예상하는 금액 (borsh `Vec<u64>`)
+        amounts: Vec<u64>,
+        /// 첫 번째 에스크로의 논스 (금액들 뒤에 붙는 borsh `u64`)
+        base_nonce: u64,
+        /// 모든 에스크로의 만료 시각 (유닉스 타임스탬프, 0이면 만료되지 않음)
+        expiry_ts: i64,
+        /// 모든 에스크로의 수수료 (베이시스 포인트, 최대 10_000)
+        fee_bps: u16,
+    },
+
+    /// InitEscrow와 같지만 토큰 계정의 소유권 대신 토큰 자체를 PDA 소유의 연관 토큰 계정(ATA)으로 옮깁니다.
+    /// 이니셜라이저의 토큰 계정은 소유자가 바뀌지 않고 그대로 남습니다.
+    /// 교환은 InitEscrow와 같고, 취소하면 토큰을 5번 계정으로 돌려보내고 ATA를 닫습니다.
+    ///
+    ///
+    /// 예상 계정:
+    ///
+    /// 0. `[signer]` 에스크로를 초기화하는 사람의 계정
+    /// 1. `[writable]` 이니셜라이저가 소유한 토큰 X의 계정 (토큰을 보냄)
+    /// 2. `[]` 거래가 진행되면 받을 토큰에 대한 이니셜라이저의 토큰 계정
+    /// 3. `[writable]` 에스크로 계정은 거래에 필요한 모든 정보를 보유합니다.
+    /// 4. `[writable]` 미리 만들어 둔 PDA의 토큰 X 연관 토큰 계정
+    /// 5. `[]` 토큰 프로그램
+    /// 6. `[]` (Token-2022일 때) 토큰 X의 민트
+    ///
+    /// 필요한 계정 뒤에 InitEscrow처럼 `[writable]` 이니셜라이저의 레지스트리 PDA와 시스템 프로그램을 넘기면
+    /// 새 에스크로를 열린 에스크로 목록에 추가합니다. (이때 0번은 writable)
+    InitEscrowWithTransfer {
+        /// 당사자 A가 받게 될 토큰 Y의 예상하는 금액
+        amount: u64,
+        /// PDA의 연관 토큰 계정으로 옮길 토큰 X의 수량
+        deposit_amount: u64,
+        /// 에스크로 만료 시각 (유닉스 타임스탬프, 0이면 만료되지 않음)
+        expiry_ts: i64,
+        /// PDA 시드에 들어갈 논스 (이니셜라이저의 에스크로마다 달라야 함)
+        nonce: u64,
+        /// 교환 때 토큰 Y에서 떼는 수수료 (베이시스 포인트, 최대 10_000)
+        fee_bps: u16,
+    },
+
+    /// InitEscrow를 보내기 전에 계정들을 미리 확인합니다.
+    /// InitEscrow와 같은 확인(서명자, 소유자, 렌트비, 프로그램 ID 등)을 모두 거치지만
+    /// 상태를 바꾸거나 CPI를 호출하지 않고, 모두 통과하면 성공합니다.
+    ///
+    ///
+    /// 예상 계정:
+    ///
+    /// InitEscrow와 같음
+    ValidateInit {
+        /// 당사자 A가 받게 될 토큰 Y의 예상하는 금액
+        amount: u64,
+        /// 교환 때 토큰 Y에서 떼는 수수료 (베이시스 포인트, 최대 10_000)
+        fee_bps: u16,
+    },
+
+    /// 거래 대기 중인 에스크로의 예상 금액과 만료 시각을 한 번에 바꿉니다.
+    /// `None`인 값은 그대로 둡니다. (가격을 바꾸면서 기한을 늘리는 흐름을 명령 하나로 처리)
+    ///
+    ///
+    /// 예상 계정:
+    ///
+    /// 0. `[signer]` 에스크로를 초기화했던 사람의 계정
+    /// 1. `[writable]` 거래 정보를 보유한 에스크로 계정
+    ReprovisionEscrow {
+        /// 새 토큰 Y의 예상 금액 (0이면 안 됨, 남은 수량도 이 값으로 다시 시작)
+        new_amount: Option<u64>,
+        /// 새 만료 시각 (유닉스 타임스탬프, 지금보다 미래여야 함)
+        new_expiry: Option<i64>,
     },
+
+    /// InitEscrow와 같지만 토큰 계정의 소유권을 넘기지 않고 PDA를 `deposit_amount`만큼의 위임자(delegate)로 승인합니다.
+    /// 이니셜라이저는 토큰 계정의 소유자로 남고, 교환 때 PDA가 위임 권한으로 토큰 X를 테이커에게 보냅니다.
+    /// 교환은 InitEscrow와 같고 3번에 1번 계정을 넘깁니다. 취소하면 위임을 철회합니다.
+    /// 승인한 수량은 이니셜라이저가 다른 곳에 써 버릴 수 있으므로 교환 때 잔액이 모자라면 교환이 실패합니다.
+    /// 위임한 수량은 에스크로에 저장해 두고 교환 때 이 값에서 채운 비율만큼 내보냅니다.
+    /// 이니셜라이저가 위임을 철회하거나 더 적은 수량으로 다시 승인했다면 `DelegationChanged` 에러로 교환이 실패합니다.
+    ///
+    ///
+    /// 예상 계정:
+    ///
+    /// 0. `[signer]` 에스크로를 초기화하는 사람의 계정
+    /// 1. `[writable]` 이니셜라이저가 소유한 토큰 X의 계정 (PDA를 위임자로 승인)
+    /// 2. `[]` 거래가 진행되면 받을 토큰에 대한 이니셜라이저의 토큰 계정
+    /// 3. `[writable]` 에스크로 계정은 거래에 필요한 모든 정보를 보유합니다.
+    /// 4. `[]` 이 에스크로의 PDA (위임자)
+    /// 5. `[]` 토큰 프로그램
+    /// 6. `[writable]` (선택) 이니셜라이저의 레지스트리 PDA
+    /// 7. `[]` 시스템 프로그램 (6번을 넘길 때)
+    ///
+    /// 레지스트리를 넘기면 새 에스크로를 열린 에스크로 목록에 추가합니다. (이때 0번은 writable)
+    InitEscrowWithDelegate {
+        /// 당사자 A가 받게 될 토큰 Y의 예상하는 금액
+        amount: u64,
+        /// PDA에게 위임할 토큰 X의 수량
+        deposit_amount: u64,
+        /// 에스크로 만료 시각 (유닉스 타임스탬프, 0이면 만료되지 않음)
+        expiry_ts: i64,
+        /// PDA 시드에 들어갈 논스 (이니셜라이저의 에스크로마다 달라야 함)
+        nonce: u64,
+        /// 교환 때 토큰 Y에서 떼는 수수료 (베이시스 포인트, 최대 10_000)
+        fee_bps: u16,
+    },
+
+    /// 교환된 베스팅 에스크로에서 지금까지 풀린 토큰 X 중 아직 찾아가지 않은 만큼을 테이커에게 보냅니다.
+    /// 풀린 수량은 `vesting_total * (지금 - created_at) / (expiry_ts - created_at)`이고
+    /// 새로 찾아갈 수량이 없으면 아무것도 하지 않습니다.
+    /// 모두 찾아가면 비워진 임시 토큰 계정을 닫고 렌트비를 이니셜라이저에게 돌려줍니다.
+    ///
+    ///
+    /// 예상 계정:
+    ///
+    /// 0. `[signer]` 에스크로를 교환한 테이커의 계정
+    /// 1. `[writable]` 테이커가 받을 토큰 X의 토큰 계정
+    /// 2. `[writable]` PDA가 소유한 임시 토큰 계정 (토큰 X를 보관)
+    /// 3. `[writable]` 이니셜라이저의 메인 계정 (임시 계정이 닫히면 렌트비를 돌려받음)
+    /// 4. `[writable]` 거래 정보를 보유한 에스크로 계정
+    /// 5. `[]` 토큰 프로그램
+    /// 6. `[]` PDA 계정
+    /// 7. `[]` (Token-2022일 때) 토큰 X의 민트
+    ClaimVested,
+
+    /// 에스크로 PDA에 잘못 보내진 램포트 중 렌트 면제 최소 금액을 넘는 만큼을 받을 계정으로 옮깁니다.
+    /// 관리자(빌드할 때 `ESCROW_ADMIN_PUBKEY`로 지정한 `ADMIN_PUBKEY`)만 호출할 수 있습니다.
+    ///
+    ///
+    /// 예상 계정:
+    ///
+    /// 0. `[signer]` 관리자 계정
+    /// 1. `[writable]` 램포트가 남은 에스크로 PDA
+    /// 2. `[writable]` 램포트를 받을 계정
+    /// 3. `[]` 시스템 프로그램
+    SweepPda {
+        /// PDA 시드의 이니셜라이저
+        initializer: Pubkey,
+        /// PDA 시드의 논스
+        nonce: u64,
+    },
+
+    /// 만료된 에스크로 여러 개를 한 번에 취소합니다. (정리 봇용, 서명 필요 없음)
+    /// 만료된 에스크로는 임시 토큰 계정의 소유권을 이니셜라이저에게 되돌리고 `Cancelled` 상태로 바꿉니다.
+    /// 아직 만료되지 않았거나, 거래 대기 중이 아니거나, 취소 가능 시각 전이거나,
+    /// 토큰을 옮기거나 위임해서 맡긴 에스크로는 실패하지 않고 건너뜁니다.
+    /// 닫혔거나 이 프로그램의 에스크로가 아닌 계정, 기록과 다른 임시 토큰 계정이나 PDA,
+    /// 넘기지 않은 토큰 프로그램의 에스크로도 배치를 실패시키지 않고 건너뜁니다.
+    /// 한 번에 `MAX_BATCH_SIZE`개까지 넘길 수 있습니다.
+    ///
+    /// 이니셜라이저의 서명 없이 호출하므로 레지스트리는 건드리지 않습니다.
+    /// 여기서 취소된 에스크로는 이니셜라이저가 CloseEscrow에 레지스트리를 넘겨 닫을 때 목록에서 빠집니다.
+    ///
+    ///
+    /// 예상 계정:
+    ///
+    /// 0. `[]` 토큰 프로그램
+    /// 1. `[]` (선택) 다른 토큰 프로그램. SPL Token과 Token-2022 에스크로를 섞을 때 둘 다 넘깁니다.
+    /// k + 3i. `[writable]` i번째 에스크로 계정 (k는 토큰 프로그램 수)
+    /// k + 1 + 3i. `[writable]` i번째 에스크로의 임시 토큰 계정 (SOL 에스크로면 에스크로 계정)
+    /// k + 2 + 3i. `[]` i번째 에스크로의 PDA 계정
+    CancelExpired,
 }
 
 impl EscrowInstruction {
     /// 바이트 버퍼를 [EscrowInstruction](enum.EscrowInstruction.html)안으로 압축을 풉니다.
     /// 버퍼 u8타입의 배열을 받아서 Result로 반환
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        // 빈 데이터는 태그조차 없으므로 길이 에러 반환
+        if input.is_empty() {
+            return Err(InstructionTooShort.into());
+        }
+
         // 입력 받은 값을 까봐서(unwrap) 정상적이면 넘어감(ok) 또는 커스텀 에러 발생
         let (tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
 
+        // 태그 뒤에 와야 하는 데이터의 길이
+        let payload_len = match tag {
+            // InitEscrow: 금액(8바이트) + 만료 시각(8바이트) + 논스(8바이트) + 수수료(2바이트)
+            // 뒤에 메모(32바이트), 취소 가능 시각(8바이트), 베스팅 여부(1바이트)가 차례로 붙을 수 있음
+            // (셋 다 없던 예전 클라이언트도 그대로 동작)
+            tag::INIT_ESCROW => match rest.len() {
+                0..=26 => 26,
+                27..=58 => 58,
+                59..=66 => 66,
+                67 => INIT_ESCROW_LEN,
+                // 더 길면 버전 바이트가 붙은 형식 (INIT_ESCROW_MINOR_VERSION 참고)
+                _ => return Self::unpack_versioned_init_escrow(rest),
+            },
+            // Exchange: 금액(8바이트) + 랩트 SOL 풀기(1바이트) + 소수점 자릿수(1바이트)
+            // 뒤의 두 값은 차례로 생략 가능
+            tag::EXCHANGE if rest.len() > 9 => 10,
+            tag::EXCHANGE if rest.len() > 8 => 9,
+            // Exchange, UpdateExpectedAmount: 금액(8바이트)
+            tag::EXCHANGE | tag::UPDATE_EXPECTED_AMOUNT => 8,
+            // CancelEscrow, CloseEscrow, QueryEscrow: 데이터 없음
+            tag::CANCEL_ESCROW | tag::CLOSE_ESCROW | tag::QUERY_ESCROW => 0,
+            // InitSolEscrow: 램포트(8바이트) + 예상 금액(8바이트)
+            tag::INIT_SOL_ESCROW => 16,
+            // InitEscrowWithSeed: InitEscrow와 같은 26바이트 + 시드(1~32바이트)
+            // 시드 길이가 범위 안이면 받은 길이 그대로, 아니면 가장 가까운 경계값을 기대함
+            tag::INIT_ESCROW_WITH_SEED => 26 + rest.len().saturating_sub(26).clamp(1, MAX_SEED_LEN),
+            // TransferInitializer: 새 이니셜라이저(32바이트)
+            tag::TRANSFER_INITIALIZER => 32,
+            // InitEscrowBatch: borsh Vec<u64> = 개수(4바이트) + 금액(8바이트) * 개수
+            // 뒤에 첫 논스(8바이트) + 만료 시각(8바이트) + 수수료(2바이트)
+            tag::INIT_ESCROW_BATCH => rest
+                .get(..4)
+                .and_then(|slice| slice.try_into().ok())
+                .map(u32::from_le_bytes)
+                .map_or(4, |count| 4 + 8 * count as usize)
+                .saturating_add(INIT_ESCROW_BATCH_TAIL_LEN),
+            // InitEscrowWithTransfer: 금액(8바이트) + 맡길 수량(8바이트) + 만료 시각(8바이트)
+            // + 논스(8바이트) + 수수료(2바이트)
+            tag::INIT_ESCROW_WITH_TRANSFER => 34,
+            // ValidateInit: 금액(8바이트) + 수수료(2바이트)
+            tag::VALIDATE_INIT => 10,
+            // ReprovisionEscrow: borsh (Option<u64>, Option<i64>)
+            // 값마다 플래그(1바이트) 뒤에 Some(1)이면 8바이트가 붙음
+            tag::REPROVISION_ESCROW => {
+                let amount_len = if rest.first() == Some(&1) { 9 } else { 1 };
+                let expiry_len = if rest.get(amount_len) == Some(&1) {
+                    9
+                } else {
+                    1
+                };
+                amount_len + expiry_len
+            }
+            // InitEscrowWithDelegate: InitEscrowWithTransfer와 같은 34바이트
+            tag::INIT_ESCROW_WITH_DELEGATE => 34,
+            // ClaimVested: 데이터 없음
+            tag::CLAIM_VESTED => 0,
+            // SweepPda: 이니셜라이저(32바이트) + 논스(8바이트)
+            tag::SWEEP_PDA => 40,
+            // CancelExpired: 데이터 없음
+            tag::CANCEL_EXPIRED => 0,
+            // 그 외의 태그면 커스텀 에러 타입(EscrowError) 전송
+            // into: 타입을 반환 InvalidInstruction의 타입인 EscrowError 반환
+            _ => return Err(InvalidInstruction.into()),
+        };
+
+        // 데이터가 모자라면 어떤 값이 잘렸는지 알 수 있도록 길이 에러 반환
+        if rest.len() < payload_len {
+            return Err(InstructionTooShort.into());
+        }
+
+        // 필요한 것보다 긴 데이터는 클라이언트의 실수이므로 에러 반환
+        if rest.len() > payload_len {
+            return Err(InvalidInstruction.into());
+        }
+
         Ok(match tag {
             // 태그가 0이면 EscrowInstruction의 InitEscrow
-            0 => Self::InitEscrow {
+            tag::INIT_ESCROW => Self::unpack_init_escrow(rest)?,
+            // 태그가 1이면 EscrowInstruction의 Exchange
+            tag::EXCHANGE => Self::Exchange {
                 amount: Self::unpack_amount(rest)?,
+                unwrap_sol: Self::unpack_bool(&rest[8..])?,
+                decimals: rest.get(9).copied(),
             },
-            // 태그가 0이 아니면 커스텀 에러 타입(EscrowError) 전송
-            // into: 타입을 반환 InvalidInstruction의 타입인 EscrowError 반환
+            // 태그가 2이면 EscrowInstruction의 CancelEscrow
+            tag::CANCEL_ESCROW => Self::CancelEscrow,
+            // 태그가 3이면 EscrowInstruction의 UpdateExpectedAmount
+            tag::UPDATE_EXPECTED_AMOUNT => Self::UpdateExpectedAmount {
+                new_amount: Self::unpack_amount(rest)?,
+            },
+            // 태그가 4이면 EscrowInstruction의 CloseEscrow
+            tag::CLOSE_ESCROW => Self::CloseEscrow,
+            // 태그가 5이면 EscrowInstruction의 InitSolEscrow
+            tag::INIT_SOL_ESCROW => Self::InitSolEscrow {
+                amount: Self::unpack_amount(rest)?,
+                expected_amount: Self::unpack_amount(&rest[8..])?,
+            },
+            // 태그가 6이면 EscrowInstruction의 QueryEscrow
+            tag::QUERY_ESCROW => Self::QueryEscrow,
+            // 태그가 7이면 EscrowInstruction의 InitEscrowWithSeed
+            tag::INIT_ESCROW_WITH_SEED => Self::InitEscrowWithSeed {
+                amount: Self::unpack_amount(rest)?,
+                expiry_ts: Self::unpack_timestamp(&rest[8..])?,
+                nonce: Self::unpack_nonce(&rest[16..])?,
+                fee_bps: Self::unpack_fee_bps(&rest[24..])?,
+                seed: Self::unpack_seed(&rest[26..])?,
+            },
+            // 태그가 8이면 EscrowInstruction의 TransferInitializer
+            tag::TRANSFER_INITIALIZER => Self::TransferInitializer {
+                new_initializer: Self::unpack_pubkey(rest)?,
+            },
+            // 태그가 9이면 EscrowInstruction의 InitEscrowBatch
+            tag::INIT_ESCROW_BATCH => Self::unpack_init_escrow_batch(rest)?,
+            // 태그가 10이면 EscrowInstruction의 InitEscrowWithTransfer
+            tag::INIT_ESCROW_WITH_TRANSFER => Self::InitEscrowWithTransfer {
+                amount: Self::unpack_amount(rest)?,
+                deposit_amount: Self::unpack_amount(&rest[8..])?,
+                expiry_ts: Self::unpack_timestamp(&rest[16..])?,
+                nonce: Self::unpack_nonce(&rest[24..])?,
+                fee_bps: Self::unpack_fee_bps(&rest[32..])?,
+            },
+            // 태그가 11이면 EscrowInstruction의 ValidateInit
+            tag::VALIDATE_INIT => Self::ValidateInit {
+                amount: Self::unpack_amount(rest)?,
+                fee_bps: Self::unpack_fee_bps(&rest[8..])?,
+            },
+            // 태그가 12이면 EscrowInstruction의 ReprovisionEscrow
+            tag::REPROVISION_ESCROW => {
+                let (new_amount, new_expiry) = Self::unpack_reprovision(rest)?;
+                Self::ReprovisionEscrow {
+                    new_amount,
+                    new_expiry,
+                }
+            }
+            // 태그가 13이면 EscrowInstruction의 InitEscrowWithDelegate
+            tag::INIT_ESCROW_WITH_DELEGATE => Self::InitEscrowWithDelegate {
+                amount: Self::unpack_amount(rest)?,
+                deposit_amount: Self::unpack_amount(&rest[8..])?,
+                expiry_ts: Self::unpack_timestamp(&rest[16..])?,
+                nonce: Self::unpack_nonce(&rest[24..])?,
+                fee_bps: Self::unpack_fee_bps(&rest[32..])?,
+            },
+            // 태그가 14이면 EscrowInstruction의 ClaimVested
+            tag::CLAIM_VESTED => Self::ClaimVested,
+            // 태그가 15이면 EscrowInstruction의 SweepPda
+            tag::SWEEP_PDA => Self::SweepPda {
+                initializer: Self::unpack_pubkey(rest)?,
+                nonce: Self::unpack_nonce(&rest[32..])?,
+            },
+            // 태그가 16이면 EscrowInstruction의 CancelExpired
+            tag::CANCEL_EXPIRED => Self::CancelExpired,
             _ => return Err(InvalidInstruction.into()),
         })
     }
 
+    /// [EscrowInstruction](enum.EscrowInstruction.html)을 명령 데이터로 압축합니다. (unpack의 반대)
+    /// 선택 값도 모두 채워서 쓰므로 `unpack(&instruction.pack())`은 원래 명령과 같습니다.
+    /// (Exchange의 `decimals`만 `Some`일 때 붙임)
+    pub fn pack(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        match self {
+            Self::InitEscrow {
+                amount,
+                expiry_ts,
+                nonce,
+                fee_bps,
+                memo,
+                cancel_after_ts,
+                vesting,
+            } => {
+                data.push(tag::INIT_ESCROW);
+                data.extend_from_slice(&amount_to_bytes(*amount));
+                data.extend_from_slice(&expiry_ts.to_le_bytes());
+                data.extend_from_slice(&nonce.to_le_bytes());
+                data.extend_from_slice(&fee_bps.to_le_bytes());
+                data.extend_from_slice(memo);
+                data.extend_from_slice(&cancel_after_ts.to_le_bytes());
+                data.push(*vesting as u8);
+            }
+            Self::Exchange {
+                amount,
+                unwrap_sol,
+                decimals,
+            } => {
+                data.push(tag::EXCHANGE);
+                data.extend_from_slice(&amount_to_bytes(*amount));
+                data.push(*unwrap_sol as u8);
+                if let Some(decimals) = decimals {
+                    data.push(*decimals);
+                }
+            }
+            Self::CancelEscrow => data.push(tag::CANCEL_ESCROW),
+            Self::UpdateExpectedAmount { new_amount } => {
+                data.push(tag::UPDATE_EXPECTED_AMOUNT);
+                data.extend_from_slice(&amount_to_bytes(*new_amount));
+            }
+            Self::CloseEscrow => data.push(tag::CLOSE_ESCROW),
+            Self::InitSolEscrow {
+                amount,
+                expected_amount,
+            } => {
+                data.push(tag::INIT_SOL_ESCROW);
+                data.extend_from_slice(&amount_to_bytes(*amount));
+                data.extend_from_slice(&amount_to_bytes(*expected_amount));
+            }
+            Self::QueryEscrow => data.push(tag::QUERY_ESCROW),
+            Self::InitEscrowWithSeed {
+                amount,
+                expiry_ts,
+                nonce,
+                fee_bps,
+                seed,
+            } => {
+                data.push(tag::INIT_ESCROW_WITH_SEED);
+                data.extend_from_slice(&amount_to_bytes(*amount));
+                data.extend_from_slice(&expiry_ts.to_le_bytes());
+                data.extend_from_slice(&nonce.to_le_bytes());
+                data.extend_from_slice(&fee_bps.to_le_bytes());
+                data.extend_from_slice(seed.as_bytes());
+            }
+            Self::TransferInitializer { new_initializer } => {
+                data.push(tag::TRANSFER_INITIALIZER);
+                data.extend_from_slice(new_initializer.as_ref());
+            }
+            Self::InitEscrowBatch {
+                amounts,
+                base_nonce,
+                expiry_ts,
+                fee_bps,
+            } => {
+                // borsh Vec<u64>: 개수(u32 LE) + 금액(u64 LE)들, 뒤에 첫 논스, 만료 시각, 수수료
+                data.push(tag::INIT_ESCROW_BATCH);
+                data.extend_from_slice(&(amounts.len() as u32).to_le_bytes());
+                for amount in amounts {
+                    data.extend_from_slice(&amount.to_le_bytes());
+                }
+                data.extend_from_slice(&base_nonce.to_le_bytes());
+                data.extend_from_slice(&expiry_ts.to_le_bytes());
+                data.extend_from_slice(&fee_bps.to_le_bytes());
+            }
+            Self::InitEscrowWithTransfer {
+                amount,
+                deposit_amount,
+                expiry_ts,
+                nonce,
+                fee_bps,
+            } => {
+                data.push(tag::INIT_ESCROW_WITH_TRANSFER);
+                data.extend_from_slice(&amount_to_bytes(*amount));
+                data.extend_from_slice(&amount_to_bytes(*deposit_amount));
+                data.extend_from_slice(&expiry_ts.to_le_bytes());
+                data.extend_from_slice(&nonce.to_le_bytes());
+                data.extend_from_slice(&fee_bps.to_le_bytes());
+            }
+            Self::ValidateInit { amount, fee_bps } => {
+                data.push(tag::VALIDATE_INIT);
+                data.extend_from_slice(&amount_to_bytes(*amount));
+                data.extend_from_slice(&fee_bps.to_le_bytes());
+            }
+            Self::ReprovisionEscrow {
+                new_amount,
+                new_expiry,
+            } => {
+                // borsh (Option<u64>, Option<i64>): 값마다 플래그(1바이트), Some이면 뒤에 LE 8바이트
+                data.push(tag::REPROVISION_ESCROW);
+                match new_amount {
+                    Some(new_amount) => {
+                        data.push(1);
+                        data.extend_from_slice(&new_amount.to_le_bytes());
+                    }
+                    None => data.push(0),
+                }
+                match new_expiry {
+                    Some(new_expiry) => {
+                        data.push(1);
+                        data.extend_from_slice(&new_expiry.to_le_bytes());
+                    }
+                    None => data.push(0),
+                }
+            }
+            Self::InitEscrowWithDelegate {
+                amount,
+                deposit_amount,
+                expiry_ts,
+                nonce,
+                fee_bps,
+            } => {
+                data.push(tag::INIT_ESCROW_WITH_DELEGATE);
+                data.extend_from_slice(&amount_to_bytes(*amount));
+                data.extend_from_slice(&amount_to_bytes(*deposit_amount));
+                data.extend_from_slice(&expiry_ts.to_le_bytes());
+                data.extend_from_slice(&nonce.to_le_bytes());
+                data.extend_from_slice(&fee_bps.to_le_bytes());
+            }
+            Self::ClaimVested => data.push(tag::CLAIM_VESTED),
+            Self::SweepPda { initializer, nonce } => {
+                data.push(tag::SWEEP_PDA);
+                data.extend_from_slice(initializer.as_ref());
+                data.extend_from_slice(&nonce.to_le_bytes());
+            }
+            Self::CancelExpired => data.push(tag::CANCEL_EXPIRED),
+        }
+        data
+    }
+
     pub fn unpack_amount(input: &[u8]) -> Result<u64, ProgramError> {
         // input 으로 부터 값을 받음
         let amount = input
@@ -50,9 +805,833 @@ impl EscrowInstruction {
             // try_info: 가져온 u8안의 u8(slice)를
             .and_then(|slice| slice.try_into().ok())
             // Result, Option의 값에 함수를 적용
-            .map(u64::from_le_bytes)
+            .map(amount_from_bytes)
             // 성공하면 값 리턴 혹은 에러 발생
             .ok_or(InvalidInstruction)?;
+
+        // 수량이 0인 에스크로는 의미가 없으므로 에러 반환
+        if amount == 0 {
+            return Err(InvalidInstruction.into());
+        }
         Ok(amount)
     }
+    pub fn unpack_nonce(input: &[u8]) -> Result<u64, ProgramError> {
+        // 논스는 0도 허용되므로 unpack_amount를 쓰지 않음
+        let nonce = input
+            .get(..8)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u64::from_le_bytes)
+            .ok_or(InvalidInstruction)?;
+        Ok(nonce)
+    }
+
+    pub fn unpack_timestamp(input: &[u8]) -> Result<i64, ProgramError> {
+        // unpack_amount와 같은 방식으로 8바이트를 i64로 변환
+        let timestamp = input
+            .get(..8)
+            .and_then(|slice| slice.try_into().ok())
+            .map(i64::from_le_bytes)
+            .ok_or(InvalidInstruction)?;
+        Ok(timestamp)
+    }
+
+    pub fn unpack_seed(input: &[u8]) -> Result<String, ProgramError> {
+        // create_with_seed는 &str 시드를 받으므로 UTF-8이 아니면 에러 반환
+        let seed = std::str::from_utf8(input).map_err(|_| InvalidInstruction)?;
+        Ok(seed.to_string())
+    }
+
+    pub fn unpack_pubkey(input: &[u8]) -> Result<Pubkey, ProgramError> {
+        // 32바이트를 Pubkey로 변환
+        let pubkey = input
+            .get(..32)
+            .and_then(|slice| slice.try_into().ok())
+            .map(Pubkey::new_from_array)
+            .ok_or(InvalidInstruction)?;
+        Ok(pubkey)
+    }
+
+    // 태그 뒤의 InitEscrow 데이터 (길이는 unpack에서 확인한 26, 58, 66, 67바이트 중 하나)
+    fn unpack_init_escrow(rest: &[u8]) -> Result<Self, ProgramError> {
+        Ok(Self::InitEscrow {
+            amount: Self::unpack_amount(rest)?,
+            expiry_ts: Self::unpack_timestamp(&rest[8..])?,
+            nonce: Self::unpack_nonce(&rest[16..])?,
+            fee_bps: Self::unpack_fee_bps(&rest[24..])?,
+            memo: Self::unpack_memo(&rest[26..]),
+            cancel_after_ts: rest.get(58..66).map_or(Ok(0), Self::unpack_timestamp)?,
+            vesting: rest.get(66..).map_or(Ok(false), Self::unpack_bool)?,
+        })
+    }
+
+    // 마이너 버전 바이트가 붙은 InitEscrow 데이터
+    // INIT_ESCROW_LEN보다 긴 경우만 여기로 오므로 버전 뒤에는 항상 INIT_ESCROW_LEN 바이트 이상이 있음
+    fn unpack_versioned_init_escrow(rest: &[u8]) -> Result<Self, ProgramError> {
+        let (version, body) = rest.split_first().ok_or(InvalidInstruction)?;
+
+        // 버전 0은 예약된 값이고, 현재 버전인데 데이터가 더 길면 클라이언트의 실수
+        // 더 새로운 버전이면 뒤에 붙은 모르는 필드를 무시함
+        if *version == 0 || (*version == INIT_ESCROW_MINOR_VERSION && body.len() > INIT_ESCROW_LEN)
+        {
+            return Err(InvalidInstruction.into());
+        }
+        Self::unpack_init_escrow(&body[..INIT_ESCROW_LEN])
+    }
+
+    pub fn unpack_bool(input: &[u8]) -> Result<bool, ProgramError> {
+        // 생략하면 false, 0과 1만 허용
+        match input.first() {
+            None | Some(0) => Ok(false),
+            Some(1) => Ok(true),
+            Some(_) => Err(InvalidInstruction.into()),
+        }
+    }
+
+    pub fn unpack_memo(input: &[u8]) -> [u8; 32] {
+        // 메모가 없으면 0으로 채운 메모 (메모 없음)
+        input
+            .get(..32)
+            .and_then(|slice| slice.try_into().ok())
+            .unwrap_or([0; 32])
+    }
+
+    pub fn unpack_init_escrow_batch(input: &[u8]) -> Result<Self, ProgramError> {
+        // 금액들 뒤에 첫 논스, 만료 시각, 수수료가 붙음 (길이는 unpack에서 확인함)
+        let tail = input
+            .len()
+            .checked_sub(INIT_ESCROW_BATCH_TAIL_LEN)
+            .ok_or(InstructionTooShort)?;
+        let (amounts, rest) = input.split_at(tail);
+        Ok(Self::InitEscrowBatch {
+            amounts: Self::unpack_amounts(amounts)?,
+            base_nonce: Self::unpack_nonce(rest)?,
+            expiry_ts: Self::unpack_timestamp(&rest[8..])?,
+            fee_bps: Self::unpack_fee_bps(&rest[16..])?,
+        })
+    }
+
+    pub fn unpack_amounts(input: &[u8]) -> Result<Vec<u64>, ProgramError> {
+        // borsh로 인코딩된 Vec<u64> (개수 u32 LE + 금액들)
+        let amounts = Vec::<u64>::try_from_slice(input).map_err(|_| InvalidInstruction)?;
+
+        // 빈 배치나 수량이 0인 에스크로는 의미가 없으므로 에러 반환
+        if amounts.is_empty() || amounts.contains(&0) {
+            return Err(InvalidInstruction.into());
+        }
+        Ok(amounts)
+    }
+
+    pub fn unpack_reprovision(input: &[u8]) -> Result<(Option<u64>, Option<i64>), ProgramError> {
+        // borsh로 인코딩된 (Option<u64>, Option<i64>)
+        // 플래그가 0, 1이 아니면 borsh가 에러를 내므로 InvalidInstruction으로 바꿈
+        <(Option<u64>, Option<i64>)>::try_from_slice(input).map_err(|_| InvalidInstruction.into())
+    }
+
+    pub fn unpack_fee_bps(input: &[u8]) -> Result<u16, ProgramError> {
+        // 2바이트를 u16으로 변환 (범위 확인은 프로세서에서 함)
+        let fee_bps = input
+            .get(..2)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u16::from_le_bytes)
+            .ok_or(InvalidInstruction)?;
+        Ok(fee_bps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn init_escrow_data(memo: &[u8]) -> Vec<u8> {
+        let mut data = vec![0];
+        data.extend_from_slice(&amount_to_bytes(10));
+        data.extend_from_slice(&0i64.to_le_bytes());
+        data.extend_from_slice(&3u64.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(memo);
+        data
+    }
+
+    #[test]
+    fn unpack_init_escrow_with_optional_memo() {
+        // 메모가 없는 예전 형식은 0으로 채운 메모
+        assert_eq!(
+            EscrowInstruction::unpack(&init_escrow_data(&[])),
+            Ok(EscrowInstruction::InitEscrow {
+                amount: 10,
+                expiry_ts: 0,
+                nonce: 3,
+                fee_bps: 0,
+                memo: [0; 32],
+                cancel_after_ts: 0,
+                vesting: false,
+            })
+        );
+        assert_eq!(
+            EscrowInstruction::unpack(&init_escrow_data(&[7; 32])),
+            Ok(EscrowInstruction::InitEscrow {
+                amount: 10,
+                expiry_ts: 0,
+                nonce: 3,
+                fee_bps: 0,
+                memo: [7; 32],
+                cancel_after_ts: 0,
+                vesting: false,
+            })
+        );
+
+        // 잘린 메모
+        assert_eq!(
+            EscrowInstruction::unpack(&init_escrow_data(&[7; 31])),
+            Err(ProgramError::from(InstructionTooShort))
+        );
+    }
+
+    #[test]
+    fn unpack_init_escrow_with_cancel_after_ts() {
+        let mut tail = vec![0; 32];
+        tail.extend_from_slice(&1_700_000_000i64.to_le_bytes());
+        assert_eq!(
+            EscrowInstruction::unpack(&init_escrow_data(&tail)),
+            Ok(EscrowInstruction::InitEscrow {
+                amount: 10,
+                expiry_ts: 0,
+                nonce: 3,
+                fee_bps: 0,
+                memo: [0; 32],
+                cancel_after_ts: 1_700_000_000,
+                vesting: false,
+            })
+        );
+
+        // 잘린 취소 가능 시각
+        tail.pop();
+        assert_eq!(
+            EscrowInstruction::unpack(&init_escrow_data(&tail)),
+            Err(ProgramError::from(InstructionTooShort))
+        );
+    }
+
+    #[test]
+    fn unpack_init_escrow_with_vesting() {
+        let mut tail = vec![0; 32];
+        tail.extend_from_slice(&0i64.to_le_bytes());
+        tail.push(1);
+        assert_eq!(
+            EscrowInstruction::unpack(&init_escrow_data(&tail)),
+            Ok(EscrowInstruction::InitEscrow {
+                amount: 10,
+                expiry_ts: 0,
+                nonce: 3,
+                fee_bps: 0,
+                memo: [0; 32],
+                cancel_after_ts: 0,
+                vesting: true,
+            })
+        );
+
+        // 0, 1이 아닌 베스팅 플래그
+        *tail.last_mut().unwrap() = 2;
+        assert_eq!(
+            EscrowInstruction::unpack(&init_escrow_data(&tail)),
+            Err(ProgramError::from(InvalidInstruction))
+        );
+    }
+
+    // 태그 + 마이너 버전 + 67바이트 InitEscrow 데이터 + 뒤에 붙는 바이트
+    fn versioned_init_escrow_data(version: u8, trailing: &[u8]) -> Vec<u8> {
+        let instruction = EscrowInstruction::InitEscrow {
+            amount: 10,
+            expiry_ts: 1_700_000_000,
+            nonce: 3,
+            fee_bps: 250,
+            memo: [7; 32],
+            cancel_after_ts: 0,
+            vesting: false,
+        };
+        let mut data = instruction.pack();
+        data.insert(1, version);
+        data.extend_from_slice(trailing);
+        data
+    }
+
+    #[test]
+    fn unpack_versioned_init_escrow() {
+        let expected = EscrowInstruction::InitEscrow {
+            amount: 10,
+            expiry_ts: 1_700_000_000,
+            nonce: 3,
+            fee_bps: 250,
+            memo: [7; 32],
+            cancel_after_ts: 0,
+            vesting: false,
+        };
+
+        // 현재 버전(v1)
+        let v1 = versioned_init_escrow_data(INIT_ESCROW_MINOR_VERSION, &[]);
+        assert_eq!(v1.len(), 2 + INIT_ESCROW_LEN);
+        assert_eq!(EscrowInstruction::unpack(&v1).as_ref(), Ok(&expected));
+
+        // 새 클라이언트가 필드를 더 붙인 v2는 모르는 바이트를 무시하고 같은 값을 읽음
+        let v2 =
+            versioned_init_escrow_data(INIT_ESCROW_MINOR_VERSION + 1, &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(EscrowInstruction::unpack(&v2).as_ref(), Ok(&expected));
+    }
+
+    #[test]
+    fn unpack_versioned_init_escrow_rejects_bad_version_or_trailing_bytes() {
+        // 예약된 버전 0
+        assert_eq!(
+            EscrowInstruction::unpack(&versioned_init_escrow_data(0, &[])),
+            Err(ProgramError::from(InvalidInstruction))
+        );
+
+        // 현재 버전인데 뒤에 바이트가 더 붙은 데이터
+        assert_eq!(
+            EscrowInstruction::unpack(&versioned_init_escrow_data(INIT_ESCROW_MINOR_VERSION, &[0])),
+            Err(ProgramError::from(InvalidInstruction))
+        );
+    }
+
+    #[test]
+    fn unpack_claim_vested() {
+        assert_eq!(
+            EscrowInstruction::unpack(&[tag::CLAIM_VESTED]),
+            Ok(EscrowInstruction::ClaimVested)
+        );
+        assert_eq!(
+            EscrowInstruction::unpack(&[tag::CLAIM_VESTED, 0]),
+            Err(ProgramError::from(InvalidInstruction))
+        );
+    }
+
+    #[test]
+    fn unpack_sweep_pda() {
+        let initializer = Pubkey::new_unique();
+        let mut data = vec![tag::SWEEP_PDA];
+        data.extend_from_slice(initializer.as_ref());
+        data.extend_from_slice(&7u64.to_le_bytes());
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Ok(EscrowInstruction::SweepPda {
+                initializer,
+                nonce: 7,
+            })
+        );
+
+        // 논스가 잘린 데이터
+        assert_eq!(
+            EscrowInstruction::unpack(&data[..33]),
+            Err(ProgramError::from(InstructionTooShort))
+        );
+    }
+
+    #[test]
+    fn unpack_rejects_zero_amount() {
+        let mut data = vec![1];
+        data.extend_from_slice(&0u64.to_le_bytes());
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Err(ProgramError::from(InvalidInstruction))
+        );
+    }
+
+    #[test]
+    fn unpack_rejects_over_length_input() {
+        let mut data = vec![1];
+        data.extend_from_slice(&amount_to_bytes(10));
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Ok(EscrowInstruction::Exchange {
+                amount: 10,
+                unwrap_sol: false,
+                decimals: None,
+            })
+        );
+
+        // 생략 가능한 unwrap_sol, 소수점 자릿수 바이트 뒤에 더 붙은 데이터
+        data.extend_from_slice(&[0, 0, 0]);
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Err(ProgramError::from(InvalidInstruction))
+        );
+        assert_eq!(
+            EscrowInstruction::unpack(&[2, 0]),
+            Err(ProgramError::from(InvalidInstruction))
+        );
+    }
+    #[test]
+    fn unpack_exchange_with_unwrap_sol() {
+        let mut data = vec![1];
+        data.extend_from_slice(&amount_to_bytes(10));
+        data.push(1);
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Ok(EscrowInstruction::Exchange {
+                amount: 10,
+                unwrap_sol: true,
+                decimals: None,
+            })
+        );
+
+        // bool은 0, 1만 허용
+        data[9] = 2;
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Err(ProgramError::from(InvalidInstruction))
+        );
+    }
+
+    #[test]
+    fn unpack_exchange_with_decimals() {
+        let mut data = vec![1];
+        data.extend_from_slice(&amount_to_bytes(10));
+        data.extend_from_slice(&[0, 6]);
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Ok(EscrowInstruction::Exchange {
+                amount: 10,
+                unwrap_sol: false,
+                decimals: Some(6),
+            })
+        );
+    }
+
+    #[test]
+    fn unpack_init_escrow_from_tag_constant() {
+        let mut data = vec![tag::INIT_ESCROW];
+        data.extend_from_slice(&amount_to_bytes(10));
+        data.extend_from_slice(&[0; 18]);
+        assert!(matches!(
+            EscrowInstruction::unpack(&data),
+            Ok(EscrowInstruction::InitEscrow { amount: 10, .. })
+        ));
+    }
+
+    // 0x0102030405060708 을 각 바이트 순서로 쓴 값
+    const KNOWN_AMOUNT_BYTES: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    #[cfg(not(feature = "be-amounts"))]
+    #[test]
+    fn amount_decodes_little_endian_by_default() {
+        assert_eq!(amount_from_bytes(KNOWN_AMOUNT_BYTES), 0x0807_0605_0403_0201);
+
+        let mut data = vec![tag::EXCHANGE];
+        data.extend_from_slice(&KNOWN_AMOUNT_BYTES);
+        assert!(matches!(
+            EscrowInstruction::unpack(&data),
+            Ok(EscrowInstruction::Exchange {
+                amount: 0x0807_0605_0403_0201,
+                ..
+            })
+        ));
+    }
+
+    #[cfg(feature = "be-amounts")]
+    #[test]
+    fn amount_decodes_big_endian_with_feature() {
+        assert_eq!(amount_from_bytes(KNOWN_AMOUNT_BYTES), 0x0102_0304_0506_0708);
+
+        let mut data = vec![tag::EXCHANGE];
+        data.extend_from_slice(&KNOWN_AMOUNT_BYTES);
+        assert!(matches!(
+            EscrowInstruction::unpack(&data),
+            Ok(EscrowInstruction::Exchange {
+                amount: 0x0102_0304_0506_0708,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn amount_bytes_round_trip() {
+        for amount in [1, 10, 0x0102_0304_0506_0708, u64::MAX] {
+            assert_eq!(amount_from_bytes(amount_to_bytes(amount)), amount);
+        }
+    }
+
+    #[test]
+    fn unpack_init_escrow_with_transfer() {
+        let mut data = vec![tag::INIT_ESCROW_WITH_TRANSFER];
+        data.extend_from_slice(&amount_to_bytes(10));
+        data.extend_from_slice(&amount_to_bytes(100));
+        data.extend_from_slice(&0i64.to_le_bytes());
+        data.extend_from_slice(&3u64.to_le_bytes());
+        data.extend_from_slice(&250u16.to_le_bytes());
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Ok(EscrowInstruction::InitEscrowWithTransfer {
+                amount: 10,
+                deposit_amount: 100,
+                expiry_ts: 0,
+                nonce: 3,
+                fee_bps: 250,
+            })
+        );
+
+        // 수수료가 잘린 데이터
+        assert_eq!(
+            EscrowInstruction::unpack(&data[..33]),
+            Err(ProgramError::from(InstructionTooShort))
+        );
+    }
+
+    #[test]
+    fn unpack_init_escrow_with_delegate() {
+        let mut data = vec![tag::INIT_ESCROW_WITH_DELEGATE];
+        data.extend_from_slice(&amount_to_bytes(10));
+        data.extend_from_slice(&amount_to_bytes(100));
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes());
+        data.extend_from_slice(&3u64.to_le_bytes());
+        data.extend_from_slice(&250u16.to_le_bytes());
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Ok(EscrowInstruction::InitEscrowWithDelegate {
+                amount: 10,
+                deposit_amount: 100,
+                expiry_ts: 1_700_000_000,
+                nonce: 3,
+                fee_bps: 250,
+            })
+        );
+
+        // 뒤에 바이트가 더 붙은 데이터
+        data.push(0);
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Err(ProgramError::from(InvalidInstruction))
+        );
+    }
+
+    fn reprovision_data(new_amount: Option<u64>, new_expiry: Option<i64>) -> Vec<u8> {
+        let mut data = vec![tag::REPROVISION_ESCROW];
+        data.extend_from_slice(&borsh::to_vec(&(new_amount, new_expiry)).unwrap());
+        data
+    }
+
+    #[test]
+    fn unpack_reprovision_escrow_each_combination() {
+        for (new_amount, new_expiry) in [
+            (None, None),
+            (Some(25), None),
+            (None, Some(1_700_000_000)),
+            (Some(25), Some(1_700_000_000)),
+        ] {
+            assert_eq!(
+                EscrowInstruction::unpack(&reprovision_data(new_amount, new_expiry)),
+                Ok(EscrowInstruction::ReprovisionEscrow {
+                    new_amount,
+                    new_expiry,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn unpack_reprovision_escrow_rejects_bad_length_and_flags() {
+        // Some 금액의 8바이트가 잘린 데이터
+        let data = reprovision_data(Some(25), None);
+        assert_eq!(
+            EscrowInstruction::unpack(&data[..5]),
+            Err(ProgramError::from(InstructionTooShort))
+        );
+
+        // 필요한 것보다 긴 데이터
+        let mut data = reprovision_data(None, None);
+        data.push(0);
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Err(ProgramError::from(InvalidInstruction))
+        );
+
+        // 0, 1이 아닌 Option 플래그
+        assert_eq!(
+            EscrowInstruction::unpack(&[tag::REPROVISION_ESCROW, 2, 0]),
+            Err(ProgramError::from(InvalidInstruction))
+        );
+    }
+
+    #[test]
+    fn unpack_validate_init() {
+        let mut data = vec![tag::VALIDATE_INIT];
+        data.extend_from_slice(&amount_to_bytes(10));
+        data.extend_from_slice(&250u16.to_le_bytes());
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Ok(EscrowInstruction::ValidateInit {
+                amount: 10,
+                fee_bps: 250,
+            })
+        );
+
+        // 수수료가 잘린 데이터
+        assert_eq!(
+            EscrowInstruction::unpack(&data[..9]),
+            Err(ProgramError::from(InstructionTooShort))
+        );
+    }
+
+    #[test]
+    fn unpack_never_panics_on_random_input() {
+        // cargo-fuzz(fuzz/) 없이도 돌 수 있도록 간단한 xorshift로 임의의 입력을 만듦
+        // 패닉 없이 Ok 또는 Err만 반환해야 함
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..10_000 {
+            let len = (next() % 80) as usize;
+            let mut data: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+            // 알려진 태그가 자주 나오도록 첫 바이트를 태그 범위 근처로 좁힘
+            if let Some(tag) = data.first_mut() {
+                *tag %= 17;
+            }
+            let _ = EscrowInstruction::unpack(&data);
+        }
+    }
+
+    #[test]
+    fn pack_round_trips_every_variant() {
+        let instructions = [
+            EscrowInstruction::InitEscrow {
+                amount: 10,
+                expiry_ts: 1_700_000_000,
+                nonce: 3,
+                fee_bps: 250,
+                memo: [7; 32],
+                cancel_after_ts: 1_700_000_100,
+                vesting: true,
+            },
+            EscrowInstruction::Exchange {
+                amount: 10,
+                unwrap_sol: false,
+                decimals: None,
+            },
+            EscrowInstruction::Exchange {
+                amount: 10,
+                unwrap_sol: true,
+                decimals: Some(6),
+            },
+            EscrowInstruction::CancelEscrow,
+            EscrowInstruction::UpdateExpectedAmount { new_amount: 20 },
+            EscrowInstruction::CloseEscrow,
+            EscrowInstruction::InitSolEscrow {
+                amount: 1_000_000,
+                expected_amount: 10,
+            },
+            EscrowInstruction::QueryEscrow,
+            EscrowInstruction::InitEscrowWithSeed {
+                amount: 10,
+                expiry_ts: 0,
+                nonce: 3,
+                fee_bps: 0,
+                seed: "escrow-1".to_string(),
+            },
+            EscrowInstruction::TransferInitializer {
+                new_initializer: Pubkey::new_unique(),
+            },
+            EscrowInstruction::InitEscrowBatch {
+                amounts: vec![10, 20, 30],
+                base_nonce: 7,
+                expiry_ts: 1_700_000_000,
+                fee_bps: 250,
+            },
+            EscrowInstruction::InitEscrowWithTransfer {
+                amount: 10,
+                deposit_amount: 100,
+                expiry_ts: 0,
+                nonce: 3,
+                fee_bps: 250,
+            },
+            EscrowInstruction::ValidateInit {
+                amount: 10,
+                fee_bps: 250,
+            },
+            EscrowInstruction::ReprovisionEscrow {
+                new_amount: Some(25),
+                new_expiry: None,
+            },
+            EscrowInstruction::ReprovisionEscrow {
+                new_amount: None,
+                new_expiry: Some(1_700_000_000),
+            },
+            EscrowInstruction::InitEscrowWithDelegate {
+                amount: 10,
+                deposit_amount: 60,
+                expiry_ts: 0,
+                nonce: 3,
+                fee_bps: 0,
+            },
+            EscrowInstruction::ClaimVested,
+            EscrowInstruction::SweepPda {
+                initializer: Pubkey::new_unique(),
+                nonce: 3,
+            },
+            EscrowInstruction::CancelExpired,
+        ];
+
+        for instruction in instructions {
+            assert_eq!(
+                EscrowInstruction::unpack(&instruction.pack()),
+                Ok(instruction)
+            );
+        }
+    }
+
+    #[test]
+    fn pack_matches_borsh_for_batch_and_reprovision() {
+        let batch = EscrowInstruction::InitEscrowBatch {
+            amounts: vec![10, 20],
+            base_nonce: 7,
+            expiry_ts: -1,
+            fee_bps: 250,
+        };
+        assert_eq!(
+            batch.pack()[1..],
+            borsh::to_vec(&(vec![10u64, 20], 7u64, -1i64, 250u16)).unwrap()[..]
+        );
+
+        let reprovision = EscrowInstruction::ReprovisionEscrow {
+            new_amount: Some(25),
+            new_expiry: Some(-1),
+        };
+        assert_eq!(reprovision.pack(), reprovision_data(Some(25), Some(-1)));
+    }
+
+    #[test]
+    fn unpack_rejects_empty_input() {
+        assert_eq!(
+            EscrowInstruction::unpack(&[]),
+            Err(ProgramError::from(InstructionTooShort))
+        );
+    }
+
+    #[test]
+    fn unpack_rejects_truncated_amount() {
+        assert_eq!(
+            EscrowInstruction::unpack(&[0, 1, 2, 3]),
+            Err(ProgramError::from(InstructionTooShort))
+        );
+        assert_eq!(
+            EscrowInstruction::unpack(&[1, 10, 0, 0, 0, 0, 0, 0]),
+            Err(ProgramError::from(InstructionTooShort))
+        );
+    }
+
+    fn init_with_seed_data(seed: &[u8]) -> Vec<u8> {
+        let mut data = vec![7];
+        data.extend_from_slice(&amount_to_bytes(10));
+        data.extend_from_slice(&0i64.to_le_bytes());
+        data.extend_from_slice(&3u64.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(seed);
+        data
+    }
+
+    #[test]
+    fn unpack_init_escrow_with_seed() {
+        assert_eq!(
+            EscrowInstruction::unpack(&init_with_seed_data(b"escrow-1")),
+            Ok(EscrowInstruction::InitEscrowWithSeed {
+                amount: 10,
+                expiry_ts: 0,
+                nonce: 3,
+                fee_bps: 0,
+                seed: "escrow-1".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn unpack_transfer_initializer() {
+        let new_initializer = Pubkey::new_unique();
+        let mut data = vec![8];
+        data.extend_from_slice(new_initializer.as_ref());
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Ok(EscrowInstruction::TransferInitializer { new_initializer })
+        );
+
+        data.pop();
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Err(ProgramError::from(InstructionTooShort))
+        );
+    }
+
+    fn init_batch_data(amounts: &[u64]) -> Vec<u8> {
+        let mut data = vec![9];
+        data.extend_from_slice(&(amounts.len() as u32).to_le_bytes());
+        for amount in amounts {
+            data.extend_from_slice(&amount.to_le_bytes());
+        }
+        data.extend_from_slice(&7u64.to_le_bytes());
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes());
+        data.extend_from_slice(&250u16.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn unpack_init_escrow_batch() {
+        assert_eq!(
+            EscrowInstruction::unpack(&init_batch_data(&[10, 20, 30])),
+            Ok(EscrowInstruction::InitEscrowBatch {
+                amounts: vec![10, 20, 30],
+                base_nonce: 7,
+                expiry_ts: 1_700_000_000,
+                fee_bps: 250,
+            })
+        );
+
+        // 첫 논스, 만료 시각, 수수료가 없는 데이터는 에러
+        let data = init_batch_data(&[10, 20]);
+        assert_eq!(
+            EscrowInstruction::unpack(&data[..data.len() - 18]),
+            Err(ProgramError::from(InstructionTooShort))
+        );
+
+        // 개수보다 금액이 모자라거나 남으면 에러
+        let mut data = init_batch_data(&[10, 20]);
+        data.pop();
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Err(ProgramError::from(InstructionTooShort))
+        );
+        let mut data = init_batch_data(&[10, 20]);
+        data.push(0);
+        assert_eq!(
+            EscrowInstruction::unpack(&data),
+            Err(ProgramError::from(InvalidInstruction))
+        );
+    }
+
+    #[test]
+    fn unpack_rejects_empty_or_zero_batch() {
+        assert_eq!(
+            EscrowInstruction::unpack(&init_batch_data(&[])),
+            Err(ProgramError::from(InvalidInstruction))
+        );
+        assert_eq!(
+            EscrowInstruction::unpack(&init_batch_data(&[10, 0])),
+            Err(ProgramError::from(InvalidInstruction))
+        );
+    }
+
+    #[test]
+    fn unpack_rejects_bad_seed_length() {
+        // 빈 시드
+        assert_eq!(
+            EscrowInstruction::unpack(&init_with_seed_data(b"")),
+            Err(ProgramError::from(InstructionTooShort))
+        );
+        // MAX_SEED_LEN보다 긴 시드
+        assert_eq!(
+            EscrowInstruction::unpack(&init_with_seed_data(&[b'a'; MAX_SEED_LEN + 1])),
+            Err(ProgramError::from(InvalidInstruction))
+        );
+    }
 }
@@ -1,6 +1,14 @@
+// 쓰지 않는 import가 다른 중요한 경고를 가리지 않도록 컴파일 에러로 처리
+#![deny(unused_imports)]
+
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
 pub mod error;
+pub mod event;
 pub mod intruction;
+pub mod pda;
 pub mod processor;
 pub mod state;
 
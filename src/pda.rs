@@ -0,0 +1,171 @@
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+// 에스크로 PDA
+// 임시 토큰 계정의 소유권을 넘겨 받는 프로그램 주소
+// 이니셜라이저와 논스를 시드에 넣어 에스크로마다 다른 PDA를 사용함
+// 프로세서와 클라이언트가 같은 주소를 얻을 수 있도록 한 곳에서 구함
+
+/// 에스크로 PDA 시드의 첫 부분
+///
+/// 주소 유도와 invoke_signed 서명이 모두 이 값을 쓰므로 포크는 여기만 바꾸면 되고,
+/// 클라이언트도 직접 쓰는 대신 이 값을 참조하면 됩니다.
+pub const ESCROW_SEED: &[u8] = b"escrow";
+
+/// 에스크로 PDA의 시드를 담습니다. (시드 조각이 빌려 쓸 값을 소유)
+pub struct EscrowSeeds {
+    initializer: Pubkey,
+    nonce: [u8; 8],
+    bump: [u8; 1],
+}
+
+impl EscrowSeeds {
+    /// 주소 유도와 invoke_signed 서명에 똑같이 쓰는 시드 배열 (마지막이 범프)
+    pub fn as_slices(&self) -> [&[u8]; 4] {
+        [
+            ESCROW_SEED,
+            self.initializer.as_ref(),
+            &self.nonce,
+            &self.bump,
+        ]
+    }
+}
+
+/// 에스크로 PDA의 시드를 만듭니다.
+///
+/// 주소를 구할 때와 PDA로 서명할 때 시드가 조금이라도 다르면
+/// 서명 확인이 알아보기 힘든 에러로 실패하므로 두 곳 모두 이 함수를 씁니다.
+pub fn escrow_signer_seeds(initializer: &Pubkey, nonce: u64, bump_seed: u8) -> EscrowSeeds {
+    EscrowSeeds {
+        initializer: *initializer,
+        nonce: nonce.to_le_bytes(),
+        bump: [bump_seed],
+    }
+}
+
+/// 에스크로 PDA와 범프 시드를 반환합니다.
+pub fn escrow_pda(program_id: &Pubkey, initializer: &Pubkey, nonce: u64) -> (Pubkey, u8) {
+    // 범프를 찾기 전이므로 마지막 범프 자리는 빼고 씀
+    let seeds = escrow_signer_seeds(initializer, nonce, 0);
+    Pubkey::find_program_address(&seeds.as_slices()[..3], program_id)
+}
+
+/// 저장해둔 범프 시드로 에스크로 PDA를 다시 만듭니다.
+///
+/// find_program_address는 유효한 주소가 나올 때까지 범프를 255부터 줄여가며
+/// 해시를 반복하므로 컴퓨트 유닛을 많이 씁니다. 범프를 알고 있으면
+/// create_program_address로 해시를 한 번만 계산하면 됩니다.
+pub fn escrow_pda_with_bump(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    nonce: u64,
+    bump_seed: u8,
+) -> Result<Pubkey, ProgramError> {
+    let seeds = escrow_signer_seeds(initializer, nonce, bump_seed);
+    let pda = Pubkey::create_program_address(&seeds.as_slices(), program_id)?;
+    Ok(pda)
+}
+
+/// 이니셜라이저별 레지스트리 PDA 시드의 첫 부분
+pub const REGISTRY_SEED: &[u8] = b"registry";
+
+/// 이니셜라이저의 레지스트리 PDA와 범프 시드를 반환합니다.
+pub fn registry_pda(program_id: &Pubkey, initializer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REGISTRY_SEED, initializer.as_ref()], program_id)
+}
+
+/// 저장해둔 범프 시드로 레지스트리 PDA를 다시 만듭니다.
+pub fn registry_pda_with_bump(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    bump_seed: u8,
+) -> Result<Pubkey, ProgramError> {
+    let pda = Pubkey::create_program_address(
+        &[REGISTRY_SEED, initializer.as_ref(), &[bump_seed]],
+        program_id,
+    )?;
+    Ok(pda)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escrow_pda_is_off_curve_program_address() {
+        let program_id = Pubkey::new_unique();
+        let initializer = Pubkey::new_unique();
+        let (pda, bump_seed) = escrow_pda(&program_id, &initializer, 0);
+
+        assert!(!pda.is_on_curve());
+        assert_eq!(
+            Pubkey::create_program_address(
+                &[
+                    b"escrow",
+                    initializer.as_ref(),
+                    &0u64.to_le_bytes(),
+                    &[bump_seed]
+                ],
+                &program_id
+            ),
+            Ok(pda)
+        );
+    }
+
+    #[test]
+    fn escrow_pda_differs_per_nonce() {
+        let program_id = Pubkey::new_unique();
+        let initializer = Pubkey::new_unique();
+
+        let (first, _) = escrow_pda(&program_id, &initializer, 1);
+        let (second, _) = escrow_pda(&program_id, &initializer, 2);
+        assert_ne!(first, second);
+    }
+    #[test]
+    fn signer_seeds_reproduce_stored_pda() {
+        let program_id = Pubkey::new_unique();
+        let initializer = Pubkey::new_unique();
+        let (pda, bump_seed) = escrow_pda(&program_id, &initializer, 9);
+
+        let seeds = escrow_signer_seeds(&initializer, 9, bump_seed);
+        assert_eq!(
+            Pubkey::create_program_address(&seeds.as_slices(), &program_id),
+            Ok(pda)
+        );
+        // 예전처럼 손으로 쓴 시드와도 같아야 함
+        assert_eq!(
+            seeds.as_slices(),
+            [
+                &b"escrow"[..],
+                initializer.as_ref(),
+                &9u64.to_le_bytes(),
+                &[bump_seed]
+            ]
+        );
+    }
+
+    #[test]
+    fn stored_bump_matches_find_program_address() {
+        let program_id = Pubkey::new_unique();
+        let initializer = Pubkey::new_unique();
+        let (pda, bump_seed) = escrow_pda(&program_id, &initializer, 5);
+
+        assert_eq!(
+            escrow_pda_with_bump(&program_id, &initializer, 5, bump_seed),
+            Ok(pda)
+        );
+    }
+
+    #[test]
+    fn registry_pda_differs_from_escrow_pda_and_per_initializer() {
+        let program_id = Pubkey::new_unique();
+        let initializer = Pubkey::new_unique();
+        let (registry, bump_seed) = registry_pda(&program_id, &initializer);
+
+        assert_eq!(
+            registry_pda_with_bump(&program_id, &initializer, bump_seed),
+            Ok(registry)
+        );
+        assert_ne!(registry, escrow_pda(&program_id, &initializer, 0).0);
+        assert_ne!(registry, registry_pda(&program_id, &Pubkey::new_unique()).0);
+    }
+}
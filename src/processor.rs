@@ -1,15 +1,61 @@
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
+    instruction::Instruction,
     msg,
-    program::invoke,
+    program::{invoke, invoke_signed, set_return_data},
     program_error::ProgramError,
+    program_option::COption,
     program_pack::{IsInitialized, Pack},
     pubkey::Pubkey,
-    sysvar::{rent::Rent, Sysvar},
+    system_program,
+    sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
 
-use crate::{error::EscrowError, intruction::EscrowInstruction, state::Escrow};
+use spl_token_2022::{
+    extension::StateWithExtensions,
+    state::{Account as TokenAccount, Mint},
+};
+
+use crate::{
+    error::EscrowError,
+    event::EscrowEvent,
+    intruction::EscrowInstruction,
+    pda::{
+        escrow_pda, escrow_pda_with_bump, escrow_signer_seeds, registry_pda,
+        registry_pda_with_bump, REGISTRY_SEED,
+    },
+    state::{
+        escrow_rent_exempt_lamports, load_escrow, Escrow, EscrowStatus, Registry, ADMIN_PUBKEY,
+        DECIMALS_UNKNOWN, MAX_BATCH_SIZE, MAX_DECIMALS, MAX_ESCROW_AMOUNT, MAX_FEE_BPS,
+    },
+};
+
+// check_init_escrow가 확인을 마친 InitEscrow의 계정과 값들
+struct CheckedInitAccounts<'a, 'b> {
+    initializer: &'b AccountInfo<'a>,
+    x_token_account: &'b AccountInfo<'a>,
+    x_token_mint: Pubkey,
+    token_to_receive_account: &'b AccountInfo<'a>,
+    expected_mint: Pubkey,
+    escrow_account: &'b AccountInfo<'a>,
+    token_program: &'b AccountInfo<'a>,
+    expected_decimals: u8,
+    registry: Option<RegistryAccounts<'a, 'b>>,
+}
+
+// 초기화 경로에 넘긴 레지스트리 PDA와 (만들거나 늘릴 때 쓰는) 시스템 프로그램
+struct RegistryAccounts<'a, 'b> {
+    registry_account: &'b AccountInfo<'a>,
+    system_program_account: &'b AccountInfo<'a>,
+    bump_seed: u8,
+}
+
+// Token-2022 전송(transfer_checked)에 넘길 민트 계정과 소수점 자릿수
+struct TransferMint<'a, 'b> {
+    mint_account: &'b AccountInfo<'a>,
+    decimals: u8,
+}
 
 pub struct Processor;
 impl Processor {
@@ -28,9 +74,144 @@ impl Processor {
         // amount 값과 위에 넘겨 받은 accounts, program_id를 같이
         // process_init_escrow 함수에 넘겨서 실행
         match instruction {
-            EscrowInstruction::InitEscrow { amount } => {
+            EscrowInstruction::InitEscrow {
+                amount,
+                expiry_ts,
+                nonce,
+                fee_bps,
+                memo,
+                cancel_after_ts,
+                vesting,
+            } => {
                 msg!("Instruction: Init Escrow");
-                Self::process_init_escrow(accounts, amount, program_id)
+                Self::process_init_escrow(
+                    accounts,
+                    amount,
+                    expiry_ts,
+                    nonce,
+                    fee_bps,
+                    memo,
+                    cancel_after_ts,
+                    vesting,
+                    program_id,
+                )
+            }
+            EscrowInstruction::Exchange {
+                amount,
+                unwrap_sol,
+                decimals,
+            } => {
+                msg!("Instruction: Exchange");
+                Self::process_exchange(accounts, amount, unwrap_sol, decimals, program_id)
+            }
+            EscrowInstruction::CancelEscrow => {
+                msg!("Instruction: Cancel Escrow");
+                Self::process_cancel_escrow(accounts, program_id)
+            }
+            EscrowInstruction::UpdateExpectedAmount { new_amount } => {
+                msg!("Instruction: Update Expected Amount");
+                Self::process_update_amount(accounts, new_amount, program_id)
+            }
+            EscrowInstruction::CloseEscrow => {
+                msg!("Instruction: Close Escrow");
+                Self::process_close_escrow(accounts, program_id)
+            }
+            EscrowInstruction::InitSolEscrow {
+                amount,
+                expected_amount,
+            } => {
+                msg!("Instruction: Init Sol Escrow");
+                Self::process_init_sol_escrow(accounts, amount, expected_amount, program_id)
+            }
+            EscrowInstruction::QueryEscrow => {
+                msg!("Instruction: Query Escrow");
+                Self::process_query_escrow(accounts, program_id)
+            }
+            EscrowInstruction::InitEscrowWithSeed {
+                amount,
+                expiry_ts,
+                nonce,
+                fee_bps,
+                seed,
+            } => {
+                msg!("Instruction: Init Escrow With Seed");
+                Self::process_init_escrow_with_seed(
+                    accounts, amount, expiry_ts, nonce, fee_bps, &seed, program_id,
+                )
+            }
+            EscrowInstruction::TransferInitializer { new_initializer } => {
+                msg!("Instruction: Transfer Initializer");
+                Self::process_transfer_initializer(accounts, &new_initializer, program_id)
+            }
+            EscrowInstruction::InitEscrowBatch {
+                amounts,
+                base_nonce,
+                expiry_ts,
+                fee_bps,
+            } => {
+                msg!("Instruction: Init Escrow Batch");
+                Self::process_init_escrow_batch(
+                    accounts, &amounts, base_nonce, expiry_ts, fee_bps, program_id,
+                )
+            }
+            EscrowInstruction::InitEscrowWithTransfer {
+                amount,
+                deposit_amount,
+                expiry_ts,
+                nonce,
+                fee_bps,
+            } => {
+                msg!("Instruction: Init Escrow With Transfer");
+                Self::process_init_escrow_with_transfer(
+                    accounts,
+                    amount,
+                    deposit_amount,
+                    expiry_ts,
+                    nonce,
+                    fee_bps,
+                    program_id,
+                )
+            }
+            EscrowInstruction::ValidateInit { amount, fee_bps } => {
+                msg!("Instruction: Validate Init");
+                Self::process_validate_init(accounts, amount, fee_bps, program_id)
+            }
+            EscrowInstruction::ReprovisionEscrow {
+                new_amount,
+                new_expiry,
+            } => {
+                msg!("Instruction: Reprovision Escrow");
+                Self::process_reprovision_escrow(accounts, new_amount, new_expiry, program_id)
+            }
+            EscrowInstruction::InitEscrowWithDelegate {
+                amount,
+                deposit_amount,
+                expiry_ts,
+                nonce,
+                fee_bps,
+            } => {
+                msg!("Instruction: Init Escrow With Delegate");
+                Self::process_init_escrow_with_delegate(
+                    accounts,
+                    amount,
+                    deposit_amount,
+                    expiry_ts,
+                    nonce,
+                    fee_bps,
+                    program_id,
+                )
+            }
+            EscrowInstruction::ClaimVested => {
+                msg!("Instruction: Claim Vested");
+                Self::process_claim_vested(accounts, program_id)
+            }
+            EscrowInstruction::SweepPda { initializer, nonce } => {
+                msg!("Instruction: Sweep Pda");
+                Self::process_sweep_pda(accounts, &initializer, nonce, program_id)
+            }
+            EscrowInstruction::CancelExpired => {
+                msg!("Instruction: Cancel Expired");
+                Self::process_cancel_expired(accounts, program_id)
             }
         }
     }
@@ -38,108 +219,104 @@ impl Processor {
     // 에스크로 프로세스 초기화
     // 넘겨 받은 값과 계정들이 정상적인지 확인하고
     // 값을 Escrow 구조체에 할당
+    #[allow(clippy::too_many_arguments)]
     pub fn process_init_escrow(
         // 어카운트들을 배열로 받음
         accounts: &[AccountInfo],
         amount: u64,
+        expiry_ts: i64,
+        nonce: u64,
+        fee_bps: u16,
+        memo: [u8; 32],
+        cancel_after_ts: i64,
+        vesting: bool,
         program_id: &Pubkey,
     ) -> ProgramResult {
-        // 배열로 받은 어카운트들을 분리하기 위해 반복을 돌림
-        let account_info_iter = &mut accounts.iter();
-
-        // next_account_info: AccountInfo 반복자(account_info.iter())의
-        // 다음 항목에 접근하기 위한 편의 기능
-
-        // 어카운트들을 initializer에 셋업
-        let initializer = next_account_info(account_info_iter)?;
-
-        // 어카운트 중에 signer가 없으면 서명자가 없으므로 에러 반환(?)
-        if !initializer.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
-        }
-
-        // X 토큰의 계정이 있으면 반환 후 계속
-        // 누구의 X토큰 계정(?)
-        let x_token_account = next_account_info(account_info_iter)?;
+        // 계정과 값들을 확인 (ValidateInit과 같은 확인)
+        let CheckedInitAccounts {
+            initializer,
+            x_token_account,
+            x_token_mint,
+            token_to_receive_account,
+            expected_mint,
+            escrow_account,
+            token_program,
+            expected_decimals,
+            registry,
+        } = Self::check_init_escrow(accounts, amount, fee_bps, program_id)?;
 
-        // 토큰을 받기 위한 어카운트
-        let token_to_receive_account = next_account_info(account_info_iter)?;
-        // 토큰을 받기 위한 어카운트의 오너가 spl_token::id가 아니면 에러 반환
-        if *token_to_receive_account.owner != spl_token::id() {
-            return Err(ProgramError::IncorrectProgramId);
-        }
+        // 시드 배열과 program_id를 find_program_address 함수에 전달하여 PDA를 만듭니다. (escrow_pda)
+        // 함수가 실패할 확률이 1/(2^255)인 새로운 pda와 bump_seed를 반환합니다.
+        // (Alice(초기화 실행자)의 tx에는 범프 시드가 필요하지 않지만
+        // 교환, 취소 때 PDA가 서명하려면 필요하므로 에스크로 상태에 저장합니다.)
 
-        // 에스크로 어카운트
-        let escrow_account = next_account_info(account_info_iter)?;
+        // 시드에 이니셜라이저와 논스를 넣어 에스크로마다 다른 PDA를 사용합니다.
+        // 그래서 한 사용자가 여러 에스크로를 독립적으로 열 수 있습니다.
+        let (pda, bump_seed) = escrow_pda(program_id, initializer.key, nonce);
 
-        // 어카운트 렌트, 어카운트 정보 반복에서 찾은 어카운트 정보로부터 Rent 정보 반환
-        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        // ---------------------------------------------------------
+        // 상태 직렬화를 추가하여 구조체의 필드를 채움
 
-        // 렌트비가 면제가 아니면 렌트비 비면제 에러 반환
-        if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
-            return Err(EscrowError::NotRentExcept.into());
-        }
+        // 만든 시각을 기록하기 위해 Clock 시스템 변수를 읽음
+        let clock = Clock::get()?;
 
-        // 에스크로 어카운트를 try_borrow_data(데이터 빌려쓰기?)를 통해 unpack_checked(solana)을 함
-        let mut escrow_info = Escrow::unpack_unchecked(&escrow_account.try_borrow_data()?)?;
-        // 에스크로 어카운트가 초기화 되었다면, 이미 초기화되었다는 에러 반환
-        if escrow_info.is_initialized() {
-            return Err(ProgramError::AccountAlreadyInitialized);
+        // 베스팅은 만든 시각부터 만료 시각까지 풀리므로 만료 시각이 지금보다 미래여야 함
+        if vesting && expiry_ts <= clock.unix_timestamp {
+            return Err(EscrowError::InvalidExpiry.into());
         }
 
-        // ---------------------------------------------------------
-        // 상태 직렬화를 추가하여 구조체의 필드를 채움
-
         // 넘겨 받아 체크한 값들이 문제가 없다면
-        // 위에 생성한 Escrow 구조체 (escrow_info)에 값을 각각 할당
-        escrow_info.is_initialized = true;
-        escrow_info.initializer_pubkey = *initializer.key;
-        escrow_info.x_token_account_pubkey = *x_token_account.key;
-        escrow_info.initializer_token_to_receive_account_pubkey = *token_to_receive_account.key;
-        escrow_info.expected_amount = amount;
+        // Escrow::new로 이니셜라이저, 계정들, 예상 수량, 범프 시드를 채운 뒤 나머지 값들을 할당
+        let escrow_info = Escrow {
+            expiry_ts,
+            x_token_mint,
+            nonce,
+            expected_mint,
+            token_program_id: *token_program.key,
+            fee_bps,
+            memo,
+            cancel_after_ts,
+            expected_decimals,
+            created_at: clock.unix_timestamp,
+            vesting,
+            ..Escrow::new(
+                *initializer.key,
+                *x_token_account.key,
+                *token_to_receive_account.key,
+                amount,
+                bump_seed,
+            )
+        };
+
+        // 저장하기 전에 필드 사이의 불변 조건을 확인
+        escrow_info.validate()?;
 
         // escrow_info에 할당한 값과 에스크로 어카운트 정보를 압축(직렬화)
         // try_borrow_mut_data: 변경 가능한 데이터를 빌려옴
-        Escrow::pack(escrow_info, &mut escrow_account.try_borrow_mut_data()?)?;
+        // 예전에 쓰던 계정에 남은 값이 새 에스크로로 섞이지 않도록 먼저 0으로 채움
+        escrow_account.try_borrow_mut_data()?.fill(0);
+        escrow_info.save_active(escrow_account)?;
 
         // ---------------------------------------------------
         /* X 토큰 계정의 (사용자 공간) 소유권을 PDA로 이전하기 */
 
-        // 시드 배열과 program_id를 find_program_address 함수에 전달하여 PDA를 만듭니다.
-        // 함수가 실패할 확률이 1/(2^255)인 새로운 pda와 bump_seed를 반환합니다.
-        // 시드는 정적일 수 있습니다.
-        // (Alice(초기화 실행자)의 tx에는 범프 시드가 필요하지 않습니다.)
-
-        // 관련 토큰 계정 프로그램과 같은 경우가 있습니다.
-        // 동일한 시점에 발생하는 서로 다른 에스크로에 대해
-        // N개의 X 토큰 계정을 소유할 수 있는 1개의 PDA만 있으면 됩니다.
-        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
-
-        // 토큰 프로그램을 가져옴
-        let token_program = next_account_info(account_info_iter)?;
-
         // 토큰 프로그램의 명령 (spl_token::instrction) 중 권한 설정을 호출
         // 현재 계정 권한(Alice = initializer.key) 및 마지막으로 CPI에 서명하는 공개 키.
+        // 권한 형태는 어카운트 소유자(AccountOwner)
 
-        let owner_change_ix = spl_token::instruction::set_authority(
+        let owner_change_ix = set_owner_ix(
             // token_program_id,
-            // X 토큰 프로그램 아이디
+            // X 토큰 프로그램 아이디 (SPL Token 또는 Token-2022)
             token_program.key,
             // owned_pubkey,
             // X 토큰 어카운트 소유자 = X 토큰 어카운트 (앨리스?)
             x_token_account.key,
             // new_authority_pubkey,
             // 새 권한 = PDA(프로그램 외부 계정)
-            Some(&pda),
-            // authority_type,
-            // 권한 형태 = 어카운트 소유자
-            spl_token::instruction::AuthorityType::AccountOwner,
+            &pda,
             // owner_pubkey,
             // 기존 소유자 pubkey (앨리스?)
             initializer.key,
-            // signer_pubkeys
-            // 서명자 pubkey (앨리스?)
-            &[&initializer.key],
         )?;
 
         // 토큰 계정 소유권을 이전하기 위해 토큰 프로그램을 호출하는 중...
@@ -172,11 +349,5748 @@ impl Processor {
             ],
         )?;
 
-        // CPI를 만들기 전에 token_program이 진정으로 토큰 프로그램의 계정인지 확인하는
-        // 또 다른 검사를 추가해야 합니다. 그렇지 않으면 악성 프로그램을 호출할 수 있습니다.
-        // 버전 3.1.1(이 가이드에서 수행함) 이상의 spl-token 크레이트를 사용하는 경우
-        // 명령어 빌더 기능을 사용한다면 이 작업을 수행할 필요가 없습니다.
+        // CPI가 에러 없이 끝나도 아무것도 바꾸지 않았을 수 있으므로
+        // 토큰 계정을 다시 읽어 소유자가 실제로 PDA로 바뀌었는지 확인
+        if unpack_token_account(x_token_account)?.owner != pda {
+            return Err(EscrowError::AuthorityTransferFailed.into());
+        }
+
+        // 레지스트리를 넘겼으면 열린 에스크로 목록에 추가
+        if let Some(registry) = registry {
+            add_to_registry(
+                &registry,
+                initializer,
+                initializer.key,
+                escrow_account.key,
+                program_id,
+            )?;
+        }
+
+        // 인덱서가 계정을 다시 읽지 않아도 되도록
+        // 직렬화된 에스크로 상태와 PDA를 리턴 데이터로 남김
+        set_return_data(&init_return_data(&escrow_account.try_borrow_data()?, &pda));
+
+        EscrowEvent::Initialized {
+            pubkey: *escrow_account.key,
+            amount,
+        }
+        .emit();
+
+        // 메모가 있으면 오프체인에서 에스크로와 주문을 연결할 수 있도록 로그로 남김
+        if memo != [0; 32] {
+            msg!("Escrow memo: {:?}", memo);
+        }
+
+        Ok(())
+    }
+
+    // InitEscrow의 모든 확인 (서명자, 소유자, 렌트비, 프로그램 ID 등)
+    // 상태를 바꾸거나 CPI를 호출하지 않으므로 ValidateInit도 그대로 씀
+    fn check_init_escrow<'a, 'b>(
+        accounts: &'b [AccountInfo<'a>],
+        amount: u64,
+        fee_bps: u16,
+        program_id: &Pubkey,
+    ) -> Result<CheckedInitAccounts<'a, 'b>, ProgramError> {
+        // 수수료는 100%(10_000 베이시스 포인트)를 넘을 수 없음
+        if fee_bps > MAX_FEE_BPS {
+            return Err(EscrowError::InvalidFee.into());
+        }
+
+        // 상한을 넘는 금액은 클라이언트 실수로 보고 자금이 잠기기 전에 거절
+        if exceeds_amount_cap(amount) {
+            return Err(EscrowError::AmountTooLarge.into());
+        }
+
+        // 배열로 받은 어카운트들을 분리하기 위해 반복을 돌림
+        let account_info_iter = &mut accounts.iter();
+
+        // next_account_info: AccountInfo 반복자(account_info.iter())의
+        // 다음 항목에 접근하기 위한 편의 기능
+
+        // 어카운트들을 initializer에 셋업
+        let initializer = next_account_info(account_info_iter)?;
+
+        // 어카운트 중에 signer가 없으면 서명자가 없으므로 에러 반환(?)
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // X 토큰의 계정이 있으면 반환 후 계속
+        // 누구의 X토큰 계정(?)
+        let x_token_account = next_account_info(account_info_iter)?;
+        check_writable(x_token_account)?;
+
+        // 이니셜라이저가 실제로 소유하지 않은 토큰 계정은 잠글 수 없음
+        // 아래의 set_authority CPI가 알아보기 힘든 토큰 프로그램 에러로 실패하기 전에 미리 확인
+        let x_token_account_info = unpack_token_account(x_token_account)?;
+        if x_token_account_info.owner != *initializer.key {
+            return Err(EscrowError::InvalidAuthority.into());
+        }
+
+        // 토큰을 받기 위한 어카운트
+        let token_to_receive_account = next_account_info(account_info_iter)?;
+        // 토큰을 받기 위한 어카운트의 오너가 토큰 프로그램(SPL Token, Token-2022)이 아니면 에러 반환
+        if !is_token_program(token_to_receive_account.owner) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        // 임시 토큰 계정을 받을 계정으로 다시 넘기면 교환이 자기 자신에게 보내는 거래가 되므로 에러 반환
+        if x_token_account.key == token_to_receive_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // 이니셜라이저가 받을 토큰 Y의 민트를 기록하기 위해 토큰 계정을 풂
+        // 토큰 프로그램 소유라도 초기화되지 않았거나 토큰 계정이 아닌 데이터(민트 등)면
+        // 교환 때 토큰 Y를 받을 수 없으므로 InvalidAccountData 에러 반환
+        let token_to_receive_account_info = unpack_token_account(token_to_receive_account)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+
+        // 에스크로 어카운트
+        let escrow_account = next_account_info(account_info_iter)?;
+
+        // 에스크로 계정과 임시 토큰 계정이 같으면 상태와 토큰 계정 데이터가 서로 덮어써지므로 에러 반환
+        if escrow_account.key == x_token_account.key {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        check_writable(escrow_account)?;
+
+        // 어카운트 렌트
+        // 렌트 시스템 변수를 어카운트로 받지 않고 Rent::get()으로 바로 읽음
+        // (rent-sysvar-account 기능을 켜면 예전처럼 어카운트 정보에서 Rent 정보를 읽음)
+        #[cfg(feature = "rent-sysvar-account")]
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        #[cfg(not(feature = "rent-sysvar-account"))]
+        let rent = &Rent::get()?;
+
+        // 에스크로 계정의 소유자, 크기, 렌트비, 초기화 여부를 정해진 순서대로 확인
+        check_init_escrow_account(escrow_account, rent, program_id)?;
+
+        // 임시 토큰 계정도 렌트비 면제가 아니면 정리되면서 맡긴 토큰이 사라질 수 있으므로 에러 반환
+        if !rent.is_exempt(x_token_account.lamports(), x_token_account.data_len()) {
+            return Err(EscrowError::NotRentExcept.into());
+        }
+
+        // 토큰 프로그램을 가져옴
+        let token_program = next_account_info(account_info_iter)?;
+
+        // 토큰 프로그램이 진짜 SPL Token 또는 Token-2022 프로그램이 아니면 에러 반환
+        // 확인하지 않으면 악성 프로그램으로 CPI를 호출할 수 있음
+        if !is_token_program(token_program.key) {
+            return Err(EscrowError::InvalidTokenProgram.into());
+        }
+
+        // 남은 선택 계정들: [토큰 Y의 민트 [토큰 X의 민트]] [레지스트리 PDA, 시스템 프로그램]
+        // 레지스트리는 뒤에 시스템 프로그램이 오는 것으로 민트와 구별함
+        // 토큰 X의 민트는 임시 토큰 계정의 민트와 주소가 같은 것으로 구별함
+        let optional_accounts = account_info_iter.as_slice();
+        let (mint_account, optional_accounts) = match optional_accounts {
+            [_, system_program_account, ..]
+                if system_program::check_id(system_program_account.key) =>
+            {
+                (None, optional_accounts)
+            }
+            [mint_account, rest @ ..] => (Some(mint_account), rest),
+            [] => (None, optional_accounts),
+        };
+
+        let (x_mint_account, optional_accounts) = match optional_accounts {
+            [x_mint_account, rest @ ..]
+                if mint_account.is_some() && *x_mint_account.key == x_token_account_info.mint =>
+            {
+                (Some(x_mint_account), rest)
+            }
+            _ => (None, optional_accounts),
+        };
+
+        // 토큰 Y의 민트 (선택)
+        // 넘기면 받을 토큰 계정의 민트와 같은지 확인하고 소수점 자릿수를 기록
+        let expected_decimals = match mint_account {
+            Some(mint_account) => {
+                if *mint_account.key != token_to_receive_account_info.mint {
+                    return Err(EscrowError::TokenMintMismatch.into());
+                }
+                if !is_token_program(mint_account.owner) {
+                    return Err(ProgramError::IncorrectProgramId);
+                }
+                check_decimals(unpack_mint(mint_account)?.decimals)?
+            }
+            None => DECIMALS_UNKNOWN,
+        };
+
+        // 토큰 X의 민트 (선택, 토큰 Y의 민트 다음에만 넘길 수 있음)
+        // 자릿수를 기록하지는 않고 너무 큰지만 확인
+        if let Some(x_mint_account) = x_mint_account {
+            if !is_token_program(x_mint_account.owner) {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            check_decimals(unpack_mint(x_mint_account)?.decimals)?;
+        }
+
+        // 이니셜라이저의 레지스트리 (선택)
+        // 넘기면 초기화 뒤 에스크로를 목록에 추가함 (레지스트리를 만들거나 늘릴 때 이니셜라이저가 렌트비를 냄)
+        let (registry, trailing_accounts) =
+            split_registry_accounts(optional_accounts, initializer.key, program_id)?;
+
+        // 엄격 모드에서는 필요한 계정 뒤에 다른 계정이 더 붙어 있으면 에러 반환
+        // (잘못된 계정을 덧붙인 클라이언트 버그가 조용히 묻히지 않게 함)
+        if cfg!(feature = "strict-accounts") && !trailing_accounts.is_empty() {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Ok(CheckedInitAccounts {
+            initializer,
+            x_token_account,
+            x_token_mint: x_token_account_info.mint,
+            token_to_receive_account,
+            expected_mint: token_to_receive_account_info.mint,
+            escrow_account,
+            token_program,
+            expected_decimals,
+            registry,
+        })
+    }
+
+    // 초기화 확인만 하는 프로세스
+    // 확인이 모두 통과하면 아무것도 바꾸지 않고 Ok(()) 반환
+    pub fn process_validate_init(
+        accounts: &[AccountInfo],
+        amount: u64,
+        fee_bps: u16,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        Self::check_init_escrow(accounts, amount, fee_bps, program_id)?;
+        msg!("Init escrow accounts are valid");
+        Ok(())
+    }
+
+    // 교환 프로세스
+    // 테이커(Bob)가 토큰 Y를 이니셜라이저에게 보내고
+    // PDA가 보관 중인 토큰 X를 채운 비율만큼 테이커에게 보냄
+    // 남은 수량을 모두 채우면 임시 토큰 계정을 닫고 에스크로를 완료함
+    // 토큰 X가 랩트 SOL이면 unwrap_sol로 받은 토큰을 바로 램포트로 풀 수 있음
+    pub fn process_exchange(
+        accounts: &[AccountInfo],
+        amount: u64,
+        unwrap_sol: bool,
+        decimals: Option<u8>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        // 테이커는 반드시 서명해야 함
+        let taker = next_account_info(account_info_iter)?;
+        if !taker.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // 테이커가 토큰 Y를 보낼 계정
+        let takers_sending_token_account = next_account_info(account_info_iter)?;
+
+        // 테이커가 토큰 X를 받을 계정
+        let takers_token_to_receive_account = next_account_info(account_info_iter)?;
+
+        // PDA가 소유한 임시 토큰 계정 (토큰 X 보관)
+        // SOL 에스크로에서는 램포트를 보관한 에스크로 계정
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        check_writable(pdas_temp_token_account)?;
+
+        // 이니셜라이저의 메인 계정 (렌트비를 돌려받음)
+        let initializers_main_account = next_account_info(account_info_iter)?;
+
+        // 이니셜라이저가 토큰 Y를 받을 계정
+        let initializers_token_to_receive_account = next_account_info(account_info_iter)?;
+
+        // 에스크로 계정
+        let escrow_account = next_account_info(account_info_iter)?;
+        check_writable(escrow_account)?;
+
+        // 초기화된 에스크로만 교환할 수 있으므로 load_escrow(unpack checked)를 사용
+        let mut escrow_info = load_escrow(escrow_account, program_id)?;
+
+        // 이미 완료되었거나 취소된 에스크로는 교환할 수 없음
+        if escrow_info.status != EscrowStatus::Active {
+            return Err(EscrowError::EscrowNotActive.into());
+        }
+
+        // 이니셜라이저가 자기 에스크로를 직접 교환하는 것은 의미 없는 거래이므로 막음
+        if *taker.key == escrow_info.initializer_pubkey {
+            return Err(EscrowError::SelfTradeNotAllowed.into());
+        }
+
+        // 넘겨 받은 계정들이 에스크로에 기록된 계정들과 같은지 확인
+        // 에스크로에 기록된 임시 토큰 계정이 아니면 에러 반환
+        // (다른 토큰 계정으로 바꿔치기하는 것을 막음)
+        if escrow_info.x_token_account_pubkey != *pdas_temp_token_account.key {
+            return Err(EscrowError::InvalidTempTokenAccount.into());
+        }
+
+        if escrow_info.initializer_pubkey != *initializers_main_account.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if escrow_info.initializer_token_to_receive_account_pubkey
+            != *initializers_token_to_receive_account.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // 테이커는 남은 수량 이하라면 일부만 채울 수 있음 (부분 체결)
+        // 남은 수량보다 많이 보내려 하면 에러 반환
+        if amount > escrow_info.remaining_amount {
+            return Err(EscrowError::ExpectedAmountMismatch.into());
+        }
+
+        // 만료 시각이 지났으면 교환할 수 없음 (취소만 가능)
+        let clock = Clock::get()?;
+        if escrow_info.is_expired(clock.unix_timestamp) {
+            return Err(EscrowError::EscrowExpired.into());
+        }
+
+        // 만료 시각과 상관없이 너무 오래된 에스크로는 가격이 낡았을 수 있으므로 교환할 수 없음
+        if escrow_info.is_stale(clock.unix_timestamp) {
+            return Err(EscrowError::EscrowStale.into());
+        }
+
+        // 테이커가 보내는 토큰이 이니셜라이저가 기대한 토큰 Y의 민트인지 확인
+        // (가치 없는 다른 민트로 수량만 맞추는 것을 막음)
+        let takers_sending_token_account_info = unpack_token_account(takers_sending_token_account)?;
+        if takers_sending_token_account_info.mint != escrow_info.expected_mint {
+            return Err(EscrowError::TokenMintMismatch.into());
+        }
+
+        // 테이커가 금액을 계산할 때 쓴 자릿수가 토큰 Y 민트의 자릿수와 다르면 에러 반환
+        // (자릿수를 모르고 만든 에스크로는 확인할 수 없으므로 넘어감)
+        if let Some(decimals) = decimals {
+            if escrow_info.expected_decimals != DECIMALS_UNKNOWN
+                && decimals != escrow_info.expected_decimals
+            {
+                return Err(EscrowError::DecimalsMismatch.into());
+            }
+        }
+
+        // 테이커가 받을 계정이 잠긴 토큰 X와 같은 민트인지 확인
+        // (SOL 에스크로는 램포트를 받으므로 토큰 계정이 아니어도 됨)
+        if !escrow_info.is_native {
+            let takers_token_to_receive_account_info =
+                unpack_token_account(takers_token_to_receive_account)?;
+            if takers_token_to_receive_account_info.mint != escrow_info.x_token_mint {
+                return Err(EscrowError::TokenMintMismatch.into());
+            }
+        }
+
+        // 토큰 프로그램
+        // 진짜 토큰 프로그램이 아니면 테이커의 토큰 Y가 엉뚱한 프로그램으로 넘어갈 수 있음
+        let token_program = next_account_info(account_info_iter)?;
+        if !is_token_program(token_program.key) {
+            return Err(EscrowError::InvalidTokenProgram.into());
+        }
+        // 이니셜라이즈 때 기록한 토큰 프로그램과 같아야 함
+        // (테이커가 다른 토큰 프로그램을 넘겨 자산의 프로그램을 바꿔치기하지 못하도록 에스크로가 끝날 때까지 고정)
+        if *token_program.key != escrow_info.token_program_id {
+            return Err(EscrowError::InvalidTokenProgram.into());
+        }
+
+        // 이번에 채우는 토큰 Y에서 수수료를 떼고 나머지를 이니셜라이저에게 보냄
+        let (fee, initializer_amount) = split_fee(amount, escrow_info.fee_bps)?;
+
+        // PDA 계정
+        let pda_account = next_account_info(account_info_iter)?;
+
+        // 초기화 때 저장한 범프 시드로 PDA를 다시 만듦
+        // (find_program_address로 다시 찾는 것보다 컴퓨트 유닛을 훨씬 적게 씀)
+        // 넘겨 받은 PDA 계정이 이 에스크로의 PDA가 아니면 상태를 바꾸거나 CPI를 호출하기 전에 에러 반환
+        // (SOL 에스크로는 PDA 서명 없이 램포트를 옮기므로 확인하지 않음)
+        if !escrow_info.is_native {
+            let pda = escrow_pda_with_bump(
+                program_id,
+                &escrow_info.pda_seed_pubkey,
+                escrow_info.nonce,
+                escrow_info.bump_seed,
+            )?;
+            if *pda_account.key != pda {
+                return Err(EscrowError::InvalidPda.into());
+            }
+        }
+
+        // 수수료를 받을 토큰 계정
+        let fee_account = next_account_info(account_info_iter)?;
+
+        // Token-2022 에스크로는 transfer_checked에 넘길 토큰 Y의 민트와
+        // (토큰 X를 바로 내보낼 때) 토큰 X의 민트를 차례로 받음
+        let y_mint = next_transfer_mint(
+            account_info_iter,
+            token_program.key,
+            &escrow_info.expected_mint,
+        )?;
+        let x_mint = if escrow_info.is_native || escrow_info.vesting {
+            None
+        } else {
+            next_transfer_mint(
+                account_info_iter,
+                token_program.key,
+                &escrow_info.x_token_mint,
+            )?
+        };
+
+        // 이번에 채우기 전의 남은 수량 (내보낼 비율을 계산할 때 씀)
+        let remaining_before = escrow_info.remaining_amount;
+        escrow_info.remaining_amount = remaining_before
+            .checked_sub(amount)
+            .ok_or(EscrowError::AmountOverflow)?;
+
+        // 베스팅 에스크로는 토큰 X를 받을 테이커가 한 명이어야 하므로 남은 수량을 한 번에 채워야 함
+        // 이때 임시 계정에 있는 토큰 X 전체가 베스팅으로 풀어줄 수량이 됨
+        if escrow_info.vesting {
+            if amount != remaining_before {
+                return Err(EscrowError::ExpectedAmountMismatch.into());
+            }
+            escrow_info.vesting_total = unpack_token_account(pdas_temp_token_account)?.amount;
+        }
+
+        // 위임으로 맡긴 에스크로는 초기화 때 저장한 남은 위임 수량에서 채운 비율만큼 내보냄
+        // 이니셜라이저가 초기화 뒤에 위임을 다시 승인해 수량을 줄이거나 철회할 수 있으므로
+        // 토큰 Y를 받기 전에 PDA가 아직 그만큼 보낼 수 있는지 확인
+        // (테이커가 토큰 Y를 모두 내고 토큰 X는 조금만 받는 것을 막음)
+        let delegate_release_amount = if escrow_info.delegate_custody {
+            let release_amount =
+                pro_rata_release(escrow_info.deposit_amount, amount, remaining_before)?;
+            let pdas_temp_token_account_info = unpack_token_account(pdas_temp_token_account)?;
+            if pdas_temp_token_account_info.delegate != COption::Some(*pda_account.key)
+                || pdas_temp_token_account_info.delegated_amount < release_amount
+            {
+                return Err(EscrowError::DelegationChanged.into());
+            }
+            escrow_info.deposit_amount = escrow_info
+                .deposit_amount
+                .checked_sub(release_amount)
+                .ok_or(EscrowError::AmountOverflow)?;
+            Some(release_amount)
+        } else {
+            None
+        };
+
+        // 여러 테이커가 나누어 채우는 에스크로를 추적할 수 있도록 체결 횟수와 마지막 테이커를 기록
+        escrow_info.fills = escrow_info
+            .fills
+            .checked_add(1)
+            .ok_or(EscrowError::AmountOverflow)?;
+        escrow_info.last_taker = *taker.key;
+
+        // 남은 수량을 모두 채웠으면 완료 상태로 바꿔 다시 교환할 수 없게 함
+        if escrow_info.remaining_amount == 0 {
+            escrow_info.status = EscrowStatus::Completed;
+        }
+
+        // 상태를 CPI보다 먼저 저장 (확인 -> 상태 변경 -> 외부 호출 순서)
+        // 아래의 토큰 프로그램 호출 도중에 이 프로그램이 다시 불려도
+        // 이미 줄어든 남은 수량(또는 완료 상태)을 읽으므로 같은 수량을 두 번 채울 수 없음
+        // CPI가 실패하면 트랜잭션 전체가 되돌려지므로 먼저 저장해도 안전함
+        escrow_info.save(escrow_account)?;
+
+        // 토큰 Y를 테이커 -> 이니셜라이저로 전송
+        // 테이커가 서명했으므로 서명이 CPI로 확장됨 (invoke)
+        let transfer_to_initializer_ix = transfer_ix(
+            token_program.key,
+            takers_sending_token_account.key,
+            y_mint.as_ref(),
+            initializers_token_to_receive_account.key,
+            taker.key,
+            initializer_amount,
+        )?;
+        msg!("Calling the token program to transfer tokens to the escrow's initializer...");
+        invoke(
+            &transfer_to_initializer_ix,
+            &transfer_account_infos(
+                &[
+                    takers_sending_token_account,
+                    initializers_token_to_receive_account,
+                    taker,
+                    token_program,
+                ],
+                y_mint.as_ref(),
+            ),
+        )?;
+
+        // 수수료가 있으면 토큰 Y를 테이커 -> 수수료 계정으로 전송
+        if fee > 0 {
+            let transfer_fee_ix = transfer_ix(
+                token_program.key,
+                takers_sending_token_account.key,
+                y_mint.as_ref(),
+                fee_account.key,
+                taker.key,
+                fee,
+            )?;
+            msg!("Calling the token program to transfer the protocol fee...");
+            invoke(
+                &transfer_fee_ix,
+                &transfer_account_infos(
+                    &[
+                        takers_sending_token_account,
+                        fee_account,
+                        taker,
+                        token_program,
+                    ],
+                    y_mint.as_ref(),
+                ),
+            )?;
+        }
+
+        // 베스팅 에스크로는 토큰 X를 지금 보내지 않고 임시 계정에 남겨 둠
+        // (테이커가 ClaimVested로 풀린 만큼씩 찾아감)
+        if escrow_info.vesting {
+            EscrowEvent::Completed.emit();
+            return Ok(());
+        }
+
+        // SOL 에스크로는 에스크로 계정에 맡긴 램포트를 테이커에게 직접 옮김
+        // (에스크로 계정은 이 프로그램 소유이므로 CPI 없이 램포트를 뺄 수 있음)
+        if escrow_info.is_native {
+            // 렌트비 면제에 필요한 램포트 위로 남아 있는 램포트가 맡긴 SOL
+            let rent = Rent::get()?;
+            let locked_lamports = escrow_account
+                .lamports()
+                .checked_sub(rent.minimum_balance(escrow_account.data_len()))
+                .ok_or(EscrowError::AmountOverflow)?;
+            let release_amount = pro_rata_release(locked_lamports, amount, remaining_before)?;
+
+            msg!("Transferring escrowed lamports to the taker...");
+            **escrow_account.try_borrow_mut_lamports()? = escrow_account
+                .lamports()
+                .checked_sub(release_amount)
+                .ok_or(EscrowError::AmountOverflow)?;
+            **takers_token_to_receive_account.try_borrow_mut_lamports()? =
+                takers_token_to_receive_account
+                    .lamports()
+                    .checked_add(release_amount)
+                    .ok_or(EscrowError::AmountOverflow)?;
+
+            // 완료되었으면 렌트비는 CloseEscrow로 돌려받음
+            if escrow_info.status == EscrowStatus::Completed {
+                EscrowEvent::Completed.emit();
+            }
+            return Ok(());
+        }
+
+        // 주소를 다시 만들 때와 같은 함수로 서명 시드를 만들어 둘이 어긋나지 않게 함
+        let seeds = escrow_signer_seeds(
+            &escrow_info.pda_seed_pubkey,
+            escrow_info.nonce,
+            escrow_info.bump_seed,
+        );
+        let signer_seeds: &[&[u8]] = &seeds.as_slices();
+
+        // 채운 비율만큼 임시 계정에 남은 토큰 X를 내보냄
+        // 마지막 체결에서는 남은 토큰 X가 모두 나감
+        // 위임으로 맡긴 에스크로는 위에서 저장된 위임 수량으로 계산해 둔 수량을 씀
+        // (이니셜라이저의 계정에는 맡기지 않은 토큰도 있을 수 있음)
+        let release_amount = match delegate_release_amount {
+            Some(release_amount) => release_amount,
+            None => pro_rata_release(
+                unpack_token_account(pdas_temp_token_account)?.amount,
+                amount,
+                remaining_before,
+            )?,
+        };
+
+        // 토큰 X를 PDA의 임시 계정 -> 테이커로 전송
+        // PDA는 서명할 개인키가 없으므로 시드와 범프로 invoke_signed를 사용해 서명
+        // 위임으로 맡긴 에스크로는 PDA가 소유자가 아닌 위임자로서 서명함
+        let transfer_to_taker_ix = transfer_ix(
+            token_program.key,
+            pdas_temp_token_account.key,
+            x_mint.as_ref(),
+            takers_token_to_receive_account.key,
+            pda_account.key,
+            release_amount,
+        )?;
+        msg!("Calling the token program to transfer tokens to the taker...");
+        invoke_signed(
+            &transfer_to_taker_ix,
+            &transfer_account_infos(
+                &[
+                    pdas_temp_token_account,
+                    takers_token_to_receive_account,
+                    pda_account,
+                    token_program,
+                ],
+                x_mint.as_ref(),
+            ),
+            &[signer_seeds],
+        )?;
+
+        // 받은 토큰 X가 랩트 SOL이고 테이커가 원하면 받은 계정을 닫아 램포트로 풂
+        // (네이티브 계정은 잔액이 있어도 닫을 수 있고 잔액과 렌트비가 모두 테이커에게 감)
+        // 받을 계정의 주인은 서명한 테이커이므로 invoke로 충분함
+        if unwrap_sol && is_native_mint(&escrow_info.x_token_mint) {
+            let unwrap_ix = close_account_ix(
+                token_program.key,
+                takers_token_to_receive_account.key,
+                taker.key,
+                taker.key,
+            )?;
+            msg!("Calling the token program to unwrap the taker's wrapped SOL...");
+            invoke(
+                &unwrap_ix,
+                &[
+                    takers_token_to_receive_account.clone(),
+                    taker.clone(),
+                    token_program.clone(),
+                ],
+            )?;
+        }
+
+        // 아직 남은 수량이 있으면 에스크로는 계속 거래 대기 중
+        if escrow_info.remaining_amount > 0 {
+            return Ok(());
+        }
+
+        // 위임으로 맡긴 토큰 계정은 이니셜라이저 소유라서 닫지 않음
+        // (위임 수량을 모두 쓰면 토큰 프로그램이 위임을 지움)
+        if escrow_info.delegate_custody {
+            EscrowEvent::Completed.emit();
+            return Ok(());
+        }
+
+        // 비워진 임시 토큰 계정을 닫고 렌트비를 이니셜라이저에게 돌려줌
+        let close_pdas_temp_acc_ix = close_account_ix(
+            token_program.key,
+            pdas_temp_token_account.key,
+            initializers_main_account.key,
+            pda_account.key,
+        )?;
+        msg!("Calling the token program to close pda's temp account...");
+        invoke_signed(
+            &close_pdas_temp_acc_ix,
+            &[
+                pdas_temp_token_account.clone(),
+                initializers_main_account.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[signer_seeds],
+        )?;
+
+        EscrowEvent::Completed.emit();
 
         Ok(())
     }
+
+    // 에스크로 취소 프로세스
+    // 테이커가 나타나지 않았을 때 이니셜라이저가 임시 토큰 계정의 소유권을 되찾고
+    // 에스크로를 취소 상태로 남김 (기록용으로 계정을 유지하고 렌트비는 CloseEscrow로 돌려받음)
+    pub fn process_cancel_escrow(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        // 이니셜라이저는 반드시 서명해야 함
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // PDA가 소유한 임시 토큰 계정
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        check_writable(pdas_temp_token_account)?;
+
+        // 에스크로 계정
+        let escrow_account = next_account_info(account_info_iter)?;
+        check_writable(escrow_account)?;
+
+        // 이미 닫힌(0으로 채워진) 에스크로는
+        // load_escrow(unpack checked)에서 UninitializedAccount 에러가 반환됨
+        let mut escrow_info = load_escrow(escrow_account, program_id)?;
+
+        // 이미 교환이 완료된 에스크로는 취소할 수 없음
+        if escrow_info.status != EscrowStatus::Active {
+            return Err(EscrowError::EscrowNotActive.into());
+        }
+
+        // 서명자가 에스크로를 초기화한 사람이 아니면 에러 반환
+        if escrow_info.initializer_pubkey != *initializer.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // 취소 가능 시각 전에는 취소할 수 없음 (테이커가 거래하는 도중에 취소하는 것을 막음)
+        // 만료 여부(is_expired)는 보지 않음: 만료된 에스크로도 이니셜라이저는 취소해서 토큰을 돌려받아야 함
+        let clock = Clock::get()?;
+        if cancel_too_early(&escrow_info, clock.unix_timestamp) {
+            return Err(EscrowError::CancelTooEarly.into());
+        }
+
+        // 에스크로에 기록된 임시 토큰 계정이 아니면 에러 반환
+        // (다른 토큰 계정으로 바꿔치기하는 것을 막음)
+        if escrow_info.x_token_account_pubkey != *pdas_temp_token_account.key {
+            return Err(EscrowError::InvalidTempTokenAccount.into());
+        }
+
+        // SOL 에스크로는 돌려받을 토큰 계정 권한이 없음
+        // (맡긴 램포트는 CloseEscrow로 에스크로 계정을 닫을 때 함께 돌려받음)
+        if !escrow_info.is_native {
+            // 토큰 프로그램
+            let token_program = next_account_info(account_info_iter)?;
+            if !is_token_program(token_program.key) {
+                return Err(EscrowError::InvalidTokenProgram.into());
+            }
+            // 이니셜라이즈 때 기록한 토큰 프로그램과 같아야 함 (Exchange와 같은 확인)
+            if *token_program.key != escrow_info.token_program_id {
+                return Err(EscrowError::InvalidTokenProgram.into());
+            }
+
+            // PDA 계정
+            let pda_account = next_account_info(account_info_iter)?;
+
+            // 초기화 때 저장한 범프 시드로 PDA를 다시 만듦
+            let pda = escrow_pda_with_bump(
+                program_id,
+                &escrow_info.pda_seed_pubkey,
+                escrow_info.nonce,
+                escrow_info.bump_seed,
+            )?;
+            if *pda_account.key != pda {
+                return Err(EscrowError::InvalidPda.into());
+            }
+            // 주소를 다시 만들 때와 같은 함수로 서명 시드를 만들어 둘이 어긋나지 않게 함
+            let seeds = escrow_signer_seeds(
+                &escrow_info.pda_seed_pubkey,
+                escrow_info.nonce,
+                escrow_info.bump_seed,
+            );
+            let signer_seeds: &[&[u8]] = &seeds.as_slices();
+
+            if escrow_info.delegate_custody {
+                // 위임으로 맡긴 에스크로는 PDA의 위임을 철회함
+                // 토큰 계정은 처음부터 이니셜라이저 소유이므로 서명한 이니셜라이저가 철회함 (invoke)
+                let revoke_delegate_ix = revoke_ix(
+                    token_program.key,
+                    pdas_temp_token_account.key,
+                    initializer.key,
+                )?;
+                msg!("Calling the token program to revoke pda's delegation...");
+                invoke(
+                    &revoke_delegate_ix,
+                    &[
+                        pdas_temp_token_account.clone(),
+                        initializer.clone(),
+                        token_program.clone(),
+                    ],
+                )?;
+            } else if escrow_info.vault_custody {
+                // 토큰을 옮겨 맡긴 에스크로는 PDA의 ATA에 남은 토큰 X를 이니셜라이저에게 돌려보내고
+                // 비워진 ATA를 닫아 렌트비를 이니셜라이저에게 돌려줌
+                let initializers_token_account = next_account_info(account_info_iter)?;
+                // Token-2022 에스크로는 transfer_checked에 넘길 토큰 X의 민트를 더 받음
+                let x_mint = next_transfer_mint(
+                    account_info_iter,
+                    token_program.key,
+                    &escrow_info.x_token_mint,
+                )?;
+                let vault_info = unpack_token_account(pdas_temp_token_account)?;
+
+                let refund_ix = transfer_ix(
+                    token_program.key,
+                    pdas_temp_token_account.key,
+                    x_mint.as_ref(),
+                    initializers_token_account.key,
+                    &pda,
+                    vault_info.amount,
+                )?;
+                msg!("Calling the token program to return escrowed tokens...");
+                invoke_signed(
+                    &refund_ix,
+                    &transfer_account_infos(
+                        &[
+                            pdas_temp_token_account,
+                            initializers_token_account,
+                            pda_account,
+                            token_program,
+                        ],
+                        x_mint.as_ref(),
+                    ),
+                    &[signer_seeds],
+                )?;
+
+                let close_vault_ix = close_account_ix(
+                    token_program.key,
+                    pdas_temp_token_account.key,
+                    initializer.key,
+                    &pda,
+                )?;
+                msg!("Calling the token program to close pda's vault account...");
+                invoke_signed(
+                    &close_vault_ix,
+                    &[
+                        pdas_temp_token_account.clone(),
+                        initializer.clone(),
+                        pda_account.clone(),
+                        token_program.clone(),
+                    ],
+                    &[signer_seeds],
+                )?;
+            } else {
+                // 임시 토큰 계정의 소유권을 PDA -> 이니셜라이저로 되돌림
+                let owner_change_ix = set_owner_ix(
+                    token_program.key,
+                    pdas_temp_token_account.key,
+                    initializer.key,
+                    &pda,
+                )?;
+                msg!("Calling the token program to return token account ownership...");
+                invoke_signed(
+                    &owner_change_ix,
+                    &[
+                        pdas_temp_token_account.clone(),
+                        pda_account.clone(),
+                        token_program.clone(),
+                    ],
+                    &[signer_seeds],
+                )?;
+            }
+        }
+
+        // 에스크로를 취소 상태로 바꿔 다시 교환할 수 없게 함
+        // 램포트는 건드리지 않으므로 에스크로 계정은 그대로 남음
+        escrow_info.status = EscrowStatus::Cancelled;
+        escrow_info.save(escrow_account)?;
+
+        // 레지스트리를 넘겼으면 열린 에스크로 목록에서 뺌
+        if let Some(registry_account) = account_info_iter.next() {
+            remove_from_registry(
+                registry_account,
+                initializer.key,
+                escrow_account.key,
+                program_id,
+            )?;
+        }
+
+        EscrowEvent::Cancelled.emit();
+
+        Ok(())
+    }
+
+    // 만료된 에스크로 일괄 취소 프로세스
+    // 정리 봇이 서명 없이 호출하므로 토큰은 항상 각 에스크로의 이니셜라이저에게 돌아감
+    // 레지스트리는 건드리지 않음 (이니셜라이저가 CloseEscrow로 닫을 때 목록에서 빠짐)
+    // 취소할 수 없는 에스크로는 건너뛰어서 일부만 만료된 배치도 실패하지 않게 함
+    pub fn process_cancel_expired(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        // 앞에 오는 토큰 프로그램들 (SPL Token과 Token-2022 에스크로를 섞으면 둘 다 넘김)
+        // 에스크로 계정은 이 프로그램 소유의 다른 주소이므로 토큰 프로그램 ID와 겹치지 않음
+        let token_programs_len = accounts
+            .iter()
+            .take_while(|account| is_token_program(account.key))
+            .count();
+        if token_programs_len == 0 {
+            return Err(EscrowError::InvalidTokenProgram.into());
+        }
+        let (token_programs, group_accounts) = accounts.split_at(token_programs_len);
+
+        // 에스크로마다 (에스크로 계정, 임시 토큰 계정, PDA) 3개씩
+        if group_accounts.is_empty() || group_accounts.len() % 3 != 0 {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        // 계정 수와 컴퓨트 한도를 넘지 않도록 배치 크기를 제한
+        if group_accounts.len() / 3 > MAX_BATCH_SIZE {
+            return Err(EscrowError::BatchTooLarge.into());
+        }
+
+        let clock = Clock::get()?;
+        for group in group_accounts.chunks_exact(3) {
+            let (escrow_account, pdas_temp_token_account, pda_account) =
+                (&group[0], &group[1], &group[2]);
+
+            // 닫혔거나 다른 프로그램 소유인 에스크로, 읽기 전용으로 넘긴 에스크로는 건너뜀
+            // (배치의 다른 에스크로까지 취소하지 못하게 되지 않도록 실패하지 않음)
+            if !escrow_account.is_writable {
+                continue;
+            }
+            let mut escrow_info = match load_escrow(escrow_account, program_id) {
+                Ok(escrow_info) => escrow_info,
+                Err(_) => continue,
+            };
+
+            // 거래 대기 중이고 만료되었고 취소 가능 시각이 지난 에스크로만 취소
+            // 토큰을 옮기거나 위임해서 맡긴 에스크로는 이니셜라이저의 계정이나 서명이 필요하므로 건너뜀
+            if escrow_info.status != EscrowStatus::Active
+                || !escrow_info.is_expired(clock.unix_timestamp)
+                || cancel_too_early(&escrow_info, clock.unix_timestamp)
+                || escrow_info.vault_custody
+                || escrow_info.delegate_custody
+            {
+                continue;
+            }
+
+            // 에스크로에 기록된 임시 토큰 계정이 아니면 건너뜀
+            if escrow_info.x_token_account_pubkey != *pdas_temp_token_account.key {
+                continue;
+            }
+
+            // SOL 에스크로는 돌려줄 토큰 계정 권한이 없으므로 상태만 바꿈
+            if !escrow_info.is_native {
+                // 이니셜라이즈 때 기록한 토큰 프로그램을 넘기지 않았으면 건너뜀
+                let token_program = match token_programs
+                    .iter()
+                    .find(|token_program| *token_program.key == escrow_info.token_program_id)
+                {
+                    Some(token_program) => token_program,
+                    None => continue,
+                };
+
+                let pda = escrow_pda_with_bump(
+                    program_id,
+                    &escrow_info.pda_seed_pubkey,
+                    escrow_info.nonce,
+                    escrow_info.bump_seed,
+                )?;
+                if !pdas_temp_token_account.is_writable || *pda_account.key != pda {
+                    continue;
+                }
+                let seeds = escrow_signer_seeds(
+                    &escrow_info.pda_seed_pubkey,
+                    escrow_info.nonce,
+                    escrow_info.bump_seed,
+                );
+                let signer_seeds: &[&[u8]] = &seeds.as_slices();
+
+                // 임시 토큰 계정의 소유권을 PDA -> 이니셜라이저로 되돌림
+                let owner_change_ix = set_owner_ix(
+                    token_program.key,
+                    pdas_temp_token_account.key,
+                    &escrow_info.initializer_pubkey,
+                    &pda,
+                )?;
+                msg!("Calling the token program to return token account ownership...");
+                invoke_signed(
+                    &owner_change_ix,
+                    &[
+                        pdas_temp_token_account.clone(),
+                        pda_account.clone(),
+                        token_program.clone(),
+                    ],
+                    &[signer_seeds],
+                )?;
+            }
+
+            escrow_info.status = EscrowStatus::Cancelled;
+            escrow_info.save(escrow_account)?;
+
+            EscrowEvent::Cancelled.emit();
+        }
+
+        Ok(())
+    }
+
+    // 예상 금액 변경 프로세스
+    // 테이커가 나타나기 전에 이니셜라이저가 가격을 조정함
+    pub fn process_update_amount(
+        accounts: &[AccountInfo],
+        new_amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        // 이니셜라이저는 반드시 서명해야 함
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // 에스크로 계정
+        let escrow_account = next_account_info(account_info_iter)?;
+        check_writable(escrow_account)?;
+        let mut escrow_info = load_escrow(escrow_account, program_id)?;
+
+        // 서명자가 에스크로를 초기화한 사람이 아니면 에러 반환
+        if escrow_info.initializer_pubkey != *initializer.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // 거래 대기 중인 에스크로만 금액을 바꿀 수 있음
+        if escrow_info.status != EscrowStatus::Active {
+            return Err(EscrowError::EscrowNotActive.into());
+        }
+
+        // 0은 unpack_amount에서 이미 걸러지지만 프로세서에서도 한 번 더 확인
+        if new_amount == 0 {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+
+        // 초기화 때와 같은 상한을 적용 (금액을 바꿔 상한을 우회하지 못하게 함)
+        if exceeds_amount_cap(new_amount) {
+            return Err(EscrowError::AmountTooLarge.into());
+        }
+
+        // 새 금액은 임시 계정에 남은 토큰 X 전체의 가격이 됨
+        // (부분 체결된 뒤라면 남은 수량도 새 금액으로 다시 시작)
+        escrow_info.expected_amount = new_amount;
+        escrow_info.remaining_amount = new_amount;
+        escrow_info.save(escrow_account)?;
+
+        Ok(())
+    }
+
+    // 예상 금액과 만료 시각을 한 번에 바꾸는 프로세스
+    // None인 값은 그대로 두고, 바꾸는 값은 모두 확인한 뒤에 한 번에 저장함
+    pub fn process_reprovision_escrow(
+        accounts: &[AccountInfo],
+        new_amount: Option<u64>,
+        new_expiry: Option<i64>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        // 이니셜라이저는 반드시 서명해야 함
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // 에스크로 계정
+        let escrow_account = next_account_info(account_info_iter)?;
+        check_writable(escrow_account)?;
+        let mut escrow_info = load_escrow(escrow_account, program_id)?;
+
+        // 서명자가 에스크로를 초기화한 사람이 아니면 에러 반환
+        if escrow_info.initializer_pubkey != *initializer.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // 거래 대기 중인 에스크로만 바꿀 수 있음
+        if escrow_info.status != EscrowStatus::Active {
+            return Err(EscrowError::EscrowNotActive.into());
+        }
+
+        // 새 금액은 UpdateExpectedAmount와 같이 남은 토큰 X 전체의 가격이 됨
+        if let Some(new_amount) = new_amount {
+            if new_amount == 0 {
+                return Err(EscrowError::InvalidInstruction.into());
+            }
+            // 초기화 때와 같은 상한을 적용
+            if exceeds_amount_cap(new_amount) {
+                return Err(EscrowError::AmountTooLarge.into());
+            }
+            escrow_info.expected_amount = new_amount;
+            escrow_info.remaining_amount = new_amount;
+        }
+
+        // 새 만료 시각은 지금보다 미래여야 함 (이미 지난 시각으로 바꾸면 바로 교환할 수 없게 됨)
+        if let Some(new_expiry) = new_expiry {
+            let clock = Clock::get()?;
+            if new_expiry <= clock.unix_timestamp {
+                return Err(EscrowError::InvalidExpiry.into());
+            }
+            escrow_info.expiry_ts = new_expiry;
+        }
+
+        escrow_info.save(escrow_account)?;
+
+        Ok(())
+    }
+
+    // 이니셜라이저 권리 이전 프로세스
+    // 거래 대기 중인 에스크로의 주인을 바꿈 (포지션을 넘기거나 파는 경우)
+    // PDA 시드는 pda_seed_pubkey에 남아 있으므로 임시 토큰 계정은 그대로 둠
+    pub fn process_transfer_initializer(
+        accounts: &[AccountInfo],
+        new_initializer: &Pubkey,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        // 지금 이니셜라이저는 반드시 서명해야 함
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // 에스크로 계정
+        let escrow_account = next_account_info(account_info_iter)?;
+        check_writable(escrow_account)?;
+        let mut escrow_info = load_escrow(escrow_account, program_id)?;
+
+        // 서명자가 지금 이니셜라이저가 아니면 에러 반환
+        if escrow_info.initializer_pubkey != *initializer.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // 거래 대기 중인 에스크로만 넘길 수 있음
+        if escrow_info.status != EscrowStatus::Active {
+            return Err(EscrowError::EscrowNotActive.into());
+        }
+
+        // 위임으로 맡긴 에스크로는 토큰 계정이 지금 이니셜라이저 소유라서
+        // 넘기면 새 이니셜라이저가 취소할 때 위임을 철회할 수 없으므로 에러 반환
+        if escrow_info.delegate_custody {
+            return Err(EscrowError::InvalidAuthority.into());
+        }
+
+        // 남은 선택 계정들: [새 받을 계정] [예전 레지스트리, 새 레지스트리, 시스템 프로그램]
+        // 레지스트리는 맨 뒤에 시스템 프로그램이 오는 것으로 받을 계정과 구별함
+        let (token_to_receive_account, registries) = match account_info_iter.as_slice() {
+            [rest @ .., old_registry_account, new_registry_account, system_program_account]
+                if system_program::check_id(system_program_account.key) =>
+            {
+                (
+                    rest.first(),
+                    Some((
+                        old_registry_account,
+                        new_registry_account,
+                        system_program_account,
+                    )),
+                )
+            }
+            rest => (rest.first(), None),
+        };
+
+        // 새 받을 계정이 있으면 토큰 Y를 받을 수 있는 계정인지 확인하고 바꿈
+        if let Some(token_to_receive_account) = token_to_receive_account {
+            if !is_token_program(token_to_receive_account.owner) {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            let token_to_receive_account_info = unpack_token_account(token_to_receive_account)?;
+            if token_to_receive_account_info.mint != escrow_info.expected_mint {
+                return Err(EscrowError::TokenMintMismatch.into());
+            }
+            escrow_info.initializer_token_to_receive_account_pubkey = *token_to_receive_account.key;
+        }
+
+        escrow_info.initializer_pubkey = *new_initializer;
+        escrow_info.save(escrow_account)?;
+
+        // 레지스트리를 넘겼으면 예전 이니셜라이저의 목록에서 빼고 새 이니셜라이저의 목록에 넣음
+        // (예전 이니셜라이저는 더 이상 닫을 수 없으므로 목록에 남기면 영영 빠지지 않음)
+        // 새 레지스트리를 만들거나 늘리는 렌트비는 서명한 예전 이니셜라이저가 냄
+        if let Some((old_registry_account, new_registry_account, system_program_account)) =
+            registries
+        {
+            remove_from_registry(
+                old_registry_account,
+                initializer.key,
+                escrow_account.key,
+                program_id,
+            )?;
+            let registry = RegistryAccounts {
+                registry_account: new_registry_account,
+                system_program_account,
+                bump_seed: check_registry(new_registry_account, new_initializer, program_id)?,
+            };
+            add_to_registry(
+                &registry,
+                initializer,
+                new_initializer,
+                escrow_account.key,
+                program_id,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // 에스크로 계정 닫기 프로세스
+    // 완료되거나 취소된 에스크로의 렌트비를 이니셜라이저에게 돌려줌
+    pub fn process_close_escrow(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        // 이니셜라이저는 반드시 서명해야 함
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // 에스크로 계정
+        let escrow_account = next_account_info(account_info_iter)?;
+        check_writable(escrow_account)?;
+        let escrow_info = load_escrow(escrow_account, program_id)?;
+
+        // 서명자가 에스크로를 초기화한 사람이 아니면 에러 반환
+        if escrow_info.initializer_pubkey != *initializer.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // 거래가 끝난(완료 또는 취소) 에스크로만 닫을 수 있음
+        if escrow_info.status != EscrowStatus::Completed
+            && escrow_info.status != EscrowStatus::Cancelled
+        {
+            return Err(EscrowError::EscrowNotActive.into());
+        }
+
+        // 교환된 베스팅 에스크로를 닫으면 테이커가 남은 토큰 X를 찾아갈 수 없으므로
+        // 모두 찾아간 뒤에만 닫을 수 있음
+        if escrow_info.vesting
+            && escrow_info.status == EscrowStatus::Completed
+            && escrow_info.claimed_amount < escrow_info.vesting_total
+        {
+            return Err(EscrowError::VestingNotClaimed.into());
+        }
+
+        // 선택 계정: 이 프로그램 소유면 레지스트리, 아니면 렌트비를 받을 계정
+        // 받을 계정을 넘기지 않으면 이니셜라이저가 받음
+        // (이니셜라이저가 서명했으므로 다른 계정으로 보내는 것도 이니셜라이저가 승인한 것)
+        let mut registry_account = None;
+        let mut destination = initializer;
+        for account in account_info_iter.take(2) {
+            if account.owner == program_id && registry_account.is_none() {
+                registry_account = Some(account);
+            } else {
+                check_writable(account)?;
+                destination = account;
+            }
+        }
+
+        // 에스크로 계정의 렌트비(lamports)를 모두 받을 계정에게 옮김
+        msg!("Closing the escrow account...");
+        **destination.try_borrow_mut_lamports()? = destination
+            .lamports()
+            .checked_add(escrow_account.lamports())
+            .ok_or(EscrowError::AmountOverflow)?;
+        **escrow_account.try_borrow_mut_lamports()? = 0;
+
+        // 데이터를 0으로 채워 초기화되지 않은 상태(Uninitialized)로 되돌림
+        escrow_account.try_borrow_mut_data()?.fill(0);
+
+        // 레지스트리를 넘겼으면 목록에서 뺌 (교환으로 완료된 에스크로는 여기서 빠짐)
+        if let Some(registry_account) = registry_account {
+            remove_from_registry(
+                registry_account,
+                initializer.key,
+                escrow_account.key,
+                program_id,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // PDA 램포트 정리 프로세스
+    // 에스크로 PDA(시스템 프로그램 소유, 데이터 없음)에 잘못 보내진 램포트는 PDA만 서명할 수 있어
+    // 누구도 꺼낼 수 없으므로 관리자가 렌트 면제 최소 금액을 넘는 만큼을 옮김
+    pub fn process_sweep_pda(
+        accounts: &[AccountInfo],
+        initializer: &Pubkey,
+        nonce: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        // 관리자만 서명할 수 있음
+        let admin = next_account_info(account_info_iter)?;
+        if !admin.is_signer || *admin.key != ADMIN_PUBKEY {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // 시드로 구한 PDA와 같아야 invoke_signed로 서명할 수 있음
+        let pda_account = next_account_info(account_info_iter)?;
+        check_writable(pda_account)?;
+        let (pda, bump_seed) = escrow_pda(program_id, initializer, nonce);
+        if *pda_account.key != pda {
+            return Err(EscrowError::InvalidPda.into());
+        }
+
+        let destination = next_account_info(account_info_iter)?;
+        check_writable(destination)?;
+
+        let system_program_account = next_account_info(account_info_iter)?;
+        if !system_program::check_id(system_program_account.key) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        // 렌트 면제 최소 금액을 넘는 만큼만 옮김 (넘는 만큼이 없으면 아무것도 하지 않음)
+        let rent = Rent::get()?;
+        let excess = pda_account
+            .lamports()
+            .saturating_sub(rent.minimum_balance(pda_account.data_len()));
+        if excess == 0 {
+            return Ok(());
+        }
+
+        let seeds = escrow_signer_seeds(initializer, nonce, bump_seed);
+        let signer_seeds: &[&[u8]] = &seeds.as_slices();
+        msg!("Calling the system program to sweep pda's lamports...");
+        invoke_signed(
+            &system_transfer_ix(pda_account.key, destination.key, excess),
+            &[
+                pda_account.clone(),
+                destination.clone(),
+                system_program_account.clone(),
+            ],
+            &[signer_seeds],
+        )?;
+
+        Ok(())
+    }
+
+    // 베스팅 찾아가기 프로세스
+    // 교환된 베스팅 에스크로에서 지금까지 풀린 토큰 X 중 아직 찾아가지 않은 만큼을 테이커에게 보냄
+    pub fn process_claim_vested(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        // 테이커는 반드시 서명해야 함
+        let taker = next_account_info(account_info_iter)?;
+        if !taker.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // 테이커가 토큰 X를 받을 계정
+        let takers_token_to_receive_account = next_account_info(account_info_iter)?;
+
+        // PDA가 소유한 임시 토큰 계정 (토큰 X 보관)
+        let pdas_temp_token_account = next_account_info(account_info_iter)?;
+        check_writable(pdas_temp_token_account)?;
+
+        // 이니셜라이저의 메인 계정 (임시 계정이 닫히면 렌트비를 돌려받음)
+        let initializers_main_account = next_account_info(account_info_iter)?;
+
+        // 에스크로 계정
+        let escrow_account = next_account_info(account_info_iter)?;
+        check_writable(escrow_account)?;
+        let mut escrow_info = load_escrow(escrow_account, program_id)?;
+
+        // 베스팅 에스크로가 아니면 찾아갈 것이 없음
+        if !escrow_info.vesting {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+
+        // 교환이 끝난 베스팅 에스크로만 찾아갈 수 있음
+        if escrow_info.status != EscrowStatus::Completed {
+            return Err(EscrowError::EscrowNotActive.into());
+        }
+
+        // 에스크로를 교환한 테이커만 찾아갈 수 있음
+        if escrow_info.last_taker != *taker.key {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if escrow_info.x_token_account_pubkey != *pdas_temp_token_account.key {
+            return Err(EscrowError::InvalidTempTokenAccount.into());
+        }
+
+        if escrow_info.initializer_pubkey != *initializers_main_account.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // 테이커가 받을 계정이 잠긴 토큰 X와 같은 민트인지 확인
+        let takers_token_to_receive_account_info =
+            unpack_token_account(takers_token_to_receive_account)?;
+        if takers_token_to_receive_account_info.mint != escrow_info.x_token_mint {
+            return Err(EscrowError::TokenMintMismatch.into());
+        }
+
+        // 토큰 프로그램
+        let token_program = next_account_info(account_info_iter)?;
+        if !is_token_program(token_program.key) {
+            return Err(EscrowError::InvalidTokenProgram.into());
+        }
+        // 이니셜라이즈 때 기록한 토큰 프로그램과 같아야 함 (Exchange와 같은 확인)
+        if *token_program.key != escrow_info.token_program_id {
+            return Err(EscrowError::InvalidTokenProgram.into());
+        }
+
+        // PDA 계정
+        let pda_account = next_account_info(account_info_iter)?;
+        let pda = escrow_pda_with_bump(
+            program_id,
+            &escrow_info.pda_seed_pubkey,
+            escrow_info.nonce,
+            escrow_info.bump_seed,
+        )?;
+        if *pda_account.key != pda {
+            return Err(EscrowError::InvalidPda.into());
+        }
+
+        // Token-2022 에스크로는 transfer_checked에 넘길 토큰 X의 민트를 더 받음
+        let x_mint = next_transfer_mint(
+            account_info_iter,
+            token_program.key,
+            &escrow_info.x_token_mint,
+        )?;
+
+        // 지금까지 풀린 수량에서 이미 찾아간 수량을 뺀 만큼만 보냄
+        let clock = Clock::get()?;
+        let claimable = escrow_info
+            .vested_amount(clock.unix_timestamp)?
+            .checked_sub(escrow_info.claimed_amount)
+            .ok_or(EscrowError::AmountOverflow)?;
+        if claimable == 0 {
+            return Ok(());
+        }
+
+        // 상태를 CPI보다 먼저 저장 (같은 수량을 두 번 찾아갈 수 없게 함)
+        escrow_info.claimed_amount = escrow_info
+            .claimed_amount
+            .checked_add(claimable)
+            .ok_or(EscrowError::AmountOverflow)?;
+        escrow_info.save(escrow_account)?;
+
+        let seeds = escrow_signer_seeds(
+            &escrow_info.pda_seed_pubkey,
+            escrow_info.nonce,
+            escrow_info.bump_seed,
+        );
+        let signer_seeds: &[&[u8]] = &seeds.as_slices();
+
+        // 마지막으로 찾아갈 때는 임시 계정에 남은 토큰 X를 모두 보내 계정을 닫을 수 있게 함
+        // (교환 뒤에 누가 임시 계정으로 토큰을 더 보내도 닫기가 막히지 않음)
+        let fully_claimed = escrow_info.claimed_amount == escrow_info.vesting_total;
+        let release_amount = if fully_claimed {
+            unpack_token_account(pdas_temp_token_account)?.amount
+        } else {
+            claimable
+        };
+
+        // 토큰 X를 PDA의 임시 계정 -> 테이커로 전송
+        let transfer_to_taker_ix = transfer_ix(
+            token_program.key,
+            pdas_temp_token_account.key,
+            x_mint.as_ref(),
+            takers_token_to_receive_account.key,
+            pda_account.key,
+            release_amount,
+        )?;
+        msg!("Calling the token program to transfer vested tokens to the taker...");
+        invoke_signed(
+            &transfer_to_taker_ix,
+            &transfer_account_infos(
+                &[
+                    pdas_temp_token_account,
+                    takers_token_to_receive_account,
+                    pda_account,
+                    token_program,
+                ],
+                x_mint.as_ref(),
+            ),
+            &[signer_seeds],
+        )?;
+
+        // 아직 풀리지 않은 토큰 X가 남아 있으면 임시 계정을 그대로 둠
+        if !fully_claimed {
+            return Ok(());
+        }
+
+        // 모두 찾아갔으면 비워진 임시 토큰 계정을 닫고 렌트비를 이니셜라이저에게 돌려줌
+        let close_pdas_temp_acc_ix = close_account_ix(
+            token_program.key,
+            pdas_temp_token_account.key,
+            initializers_main_account.key,
+            pda_account.key,
+        )?;
+        msg!("Calling the token program to close pda's temp account...");
+        invoke_signed(
+            &close_pdas_temp_acc_ix,
+            &[
+                pdas_temp_token_account.clone(),
+                initializers_main_account.clone(),
+                pda_account.clone(),
+                token_program.clone(),
+            ],
+            &[signer_seeds],
+        )?;
+
+        Ok(())
+    }
+
+    // 에스크로 조회 프로세스
+    // 다른 프로그램이 레이아웃을 몰라도 CPI로 에스크로 정보를 읽을 수 있도록
+    // 필요한 값만 리턴 데이터로 남김 (계정은 바꾸지 않음)
+    pub fn process_query_escrow(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let escrow_account = next_account_info(account_info_iter)?;
+        let escrow_info = load_escrow(escrow_account, program_id)?;
+
+        set_return_data(&query_return_data(&escrow_info));
+
+        Ok(())
+    }
+
+    // 시드로 에스크로 계정을 만들면서 초기화하는 프로세스
+    // 시스템 프로그램으로 Escrow::LEN 크기의 렌트비 면제 계정을 만들고
+    // 나머지 어카운트로 process_init_escrow를 그대로 실행함
+    #[allow(clippy::too_many_arguments)]
+    pub fn process_init_escrow_with_seed(
+        accounts: &[AccountInfo],
+        amount: u64,
+        expiry_ts: i64,
+        nonce: u64,
+        fee_bps: u16,
+        seed: &str,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        // 마지막 어카운트는 시스템 프로그램, 나머지는 InitEscrow와 같은 순서
+        let (system_program_account, init_accounts) = accounts
+            .split_last()
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if !system_program::check_id(system_program_account.key) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let account_info_iter = &mut init_accounts.iter();
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        // 임시 토큰 계정, 받을 토큰 계정 다음이 에스크로 계정
+        let escrow_account = account_info_iter
+            .nth(2)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+        // 에스크로 계정이 이니셜라이저와 시드로 만든 주소인지 확인
+        let expected_escrow = Pubkey::create_with_seed(initializer.key, seed, program_id)?;
+        if expected_escrow != *escrow_account.key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        // 렌트비 면제에 필요한 만큼 채워서 프로그램 소유로 계정을 만듦
+        // 이니셜라이저가 시드의 베이스이자 렌트비를 내는 계정이므로 invoke로 충분함
+        let rent = Rent::get()?;
+        msg!("Calling the system program to create the escrow account...");
+        invoke(
+            &create_account_with_seed_ix(
+                initializer.key,
+                escrow_account.key,
+                seed,
+                escrow_rent_exempt_lamports(&rent),
+                Escrow::LEN as u64,
+                program_id,
+            ),
+            &[
+                initializer.clone(),
+                escrow_account.clone(),
+                system_program_account.clone(),
+            ],
+        )?;
+
+        // 시스템 프로그램 바로 앞에 레지스트리를 넘겼으면 마지막 시스템 프로그램까지 넘겨서
+        // process_init_escrow가 InitEscrow처럼 [레지스트리, 시스템 프로그램]으로 읽게 함
+        // 레지스트리는 이 프로그램이나 시스템 프로그램 소유라서 그 자리에 올 수 있는 토큰 프로그램, 민트와 구별됨
+        let init_accounts = match init_accounts.last() {
+            Some(account)
+                if account.owner == program_id || system_program::check_id(account.owner) =>
+            {
+                accounts
+            }
+            _ => init_accounts,
+        };
+
+        Self::process_init_escrow(
+            init_accounts,
+            amount,
+            expiry_ts,
+            nonce,
+            fee_bps,
+            [0; 32],
+            0,
+            false,
+            program_id,
+        )
+    }
+
+    // 에스크로 여러 개 초기화 프로세스
+    // 에스크로마다 (임시 토큰 계정, 받을 토큰 계정, 에스크로 계정)을 떼어
+    // 이니셜라이저, 토큰 프로그램과 함께 process_init_escrow에 넘김
+    pub fn process_init_escrow_batch(
+        accounts: &[AccountInfo],
+        amounts: &[u64],
+        base_nonce: u64,
+        expiry_ts: i64,
+        fee_bps: u16,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        // 계정 수와 컴퓨트 한도를 넘지 않도록 배치 크기를 제한
+        if amounts.len() > MAX_BATCH_SIZE {
+            return Err(EscrowError::BatchTooLarge.into());
+        }
+
+        let (initializer, rest) = accounts
+            .split_first()
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+        // 에스크로마다 계정 3개, 그 뒤는 모든 에스크로가 같이 쓰는 계정들
+        // (토큰 프로그램, rent-sysvar-account 기능을 켜면 그 앞에 임대 시스템 변수)
+        let group_accounts_len = amounts.len() * 3;
+        if rest.len() <= group_accounts_len {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let (group_accounts, shared_accounts) = rest.split_at(group_accounts_len);
+
+        // 첫 논스에 배치 안의 순서를 더해서 에스크로마다 다른 PDA를 씀
+        // (클라이언트가 고른 첫 논스로 다른 배치나 InitEscrow의 PDA와 겹치지 않게 함)
+        for (index, (amount, group)) in amounts
+            .iter()
+            .zip(group_accounts.chunks_exact(3))
+            .enumerate()
+        {
+            let nonce = base_nonce
+                .checked_add(index as u64)
+                .ok_or(EscrowError::InvalidInstruction)?;
+
+            let mut init_accounts = Vec::with_capacity(1 + group.len() + shared_accounts.len());
+            init_accounts.push(initializer.clone());
+            init_accounts.extend_from_slice(group);
+            init_accounts.extend_from_slice(shared_accounts);
+
+            Self::process_init_escrow(
+                &init_accounts,
+                *amount,
+                expiry_ts,
+                nonce,
+                fee_bps,
+                [0; 32],
+                0,
+                false,
+                program_id,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // 토큰을 옮겨 맡기는 에스크로 초기화 프로세스
+    // 임시 토큰 계정의 소유권을 넘기는 대신 이니셜라이저의 토큰 X를
+    // 미리 만들어 둔 PDA 소유의 연관 토큰 계정(ATA)으로 전송함
+    // 교환은 InitEscrow와 같은 경로(PDA 서명으로 ATA에서 내보냄)를 쓰고 취소만 다름
+    pub fn process_init_escrow_with_transfer(
+        accounts: &[AccountInfo],
+        amount: u64,
+        deposit_amount: u64,
+        expiry_ts: i64,
+        nonce: u64,
+        fee_bps: u16,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if fee_bps > MAX_FEE_BPS {
+            return Err(EscrowError::InvalidFee.into());
+        }
+
+        if exceeds_amount_cap(amount) {
+            return Err(EscrowError::AmountTooLarge.into());
+        }
+
+        let account_info_iter = &mut accounts.iter();
+
+        // 이니셜라이저는 반드시 서명해야 함 (토큰 X 전송에 서명이 이어짐)
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // 토큰 X를 보낼 이니셜라이저의 토큰 계정 (소유자는 바뀌지 않음)
+        let source_token_account = next_account_info(account_info_iter)?;
+        check_writable(source_token_account)?;
+        let source_token_account_info = unpack_token_account(source_token_account)?;
+        if source_token_account_info.owner != *initializer.key {
+            return Err(EscrowError::InvalidAuthority.into());
+        }
+
+        // 맡길 수량이 0이면 빈 보관 계정에 토큰 Y를 받게 되므로 에러 반환
+        // 잔액이 모자라면 전송 CPI가 알아보기 힘든 토큰 프로그램 에러로 실패하기 전에 미리 확인
+        if deposit_amount == 0 || source_token_account_info.amount < deposit_amount {
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        // 토큰 Y를 받을 이니셜라이저의 토큰 계정
+        let token_to_receive_account = next_account_info(account_info_iter)?;
+        if !is_token_program(token_to_receive_account.owner) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let token_to_receive_account_info = unpack_token_account(token_to_receive_account)?;
+
+        // 에스크로 계정
+        let escrow_account = next_account_info(account_info_iter)?;
+        check_writable(escrow_account)?;
+        check_init_escrow_account(escrow_account, &Rent::get()?, program_id)?;
+
+        // 토큰 X를 받아 보관할 PDA의 연관 토큰 계정
+        let vault_account = next_account_info(account_info_iter)?;
+        check_writable(vault_account)?;
+
+        // 토큰 프로그램
+        let token_program = next_account_info(account_info_iter)?;
+        if !is_token_program(token_program.key) {
+            return Err(EscrowError::InvalidTokenProgram.into());
+        }
+
+        // Token-2022면 transfer_checked에 넘길 토큰 X의 민트
+        let x_mint = next_transfer_mint(
+            account_info_iter,
+            token_program.key,
+            &source_token_account_info.mint,
+        )?;
+
+        // 이니셜라이저의 레지스트리 (선택, InitEscrow와 같음)
+        let (registry, _) =
+            split_registry_accounts(account_info_iter.as_slice(), initializer.key, program_id)?;
+
+        // 같은 계정을 두 자리에 넘기면 보관 계정에서 자기 자신으로 보내거나 상태가 덮어써지므로 에러 반환
+        if vault_account.key == source_token_account.key
+            || vault_account.key == token_to_receive_account.key
+            || vault_account.key == escrow_account.key
+        {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // 보관 계정은 이 에스크로의 PDA 소유이고 토큰 X와 같은 민트여야 함
+        // (아니면 교환, 취소 때 PDA가 토큰을 내보낼 수 없음)
+        let (pda, bump_seed) = escrow_pda(program_id, initializer.key, nonce);
+        let vault_account_info = unpack_token_account(vault_account)?;
+        if vault_account_info.owner != pda {
+            return Err(EscrowError::InvalidPda.into());
+        }
+        if vault_account_info.mint != source_token_account_info.mint {
+            return Err(EscrowError::TokenMintMismatch.into());
+        }
+
+        let clock = Clock::get()?;
+        let escrow_info = Escrow {
+            expiry_ts,
+            x_token_mint: source_token_account_info.mint,
+            nonce,
+            expected_mint: token_to_receive_account_info.mint,
+            token_program_id: *token_program.key,
+            fee_bps,
+            created_at: clock.unix_timestamp,
+            vault_custody: true,
+            ..Escrow::new(
+                *initializer.key,
+                *vault_account.key,
+                *token_to_receive_account.key,
+                amount,
+                bump_seed,
+            )
+        };
+        escrow_info.validate()?;
+        // 예전에 쓰던 계정에 남은 값이 섞이지 않도록 먼저 0으로 채움
+        escrow_account.try_borrow_mut_data()?.fill(0);
+        escrow_info.save_active(escrow_account)?;
+
+        // 토큰 X를 이니셜라이저 -> PDA의 보관 계정으로 전송
+        // 이니셜라이저가 서명했으므로 서명이 CPI로 확장됨 (invoke)
+        let deposit_ix = transfer_ix(
+            token_program.key,
+            source_token_account.key,
+            x_mint.as_ref(),
+            vault_account.key,
+            initializer.key,
+            deposit_amount,
+        )?;
+        msg!("Calling the token program to deposit tokens into pda's vault account...");
+        invoke(
+            &deposit_ix,
+            &transfer_account_infos(
+                &[
+                    source_token_account,
+                    vault_account,
+                    initializer,
+                    token_program,
+                ],
+                x_mint.as_ref(),
+            ),
+        )?;
+
+        // 레지스트리를 넘겼으면 열린 에스크로 목록에 추가
+        if let Some(registry) = registry {
+            add_to_registry(
+                &registry,
+                initializer,
+                initializer.key,
+                escrow_account.key,
+                program_id,
+            )?;
+        }
+
+        EscrowEvent::Initialized {
+            pubkey: *escrow_account.key,
+            amount,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    // 위임으로 맡기는 에스크로 초기화 프로세스
+    // 토큰 계정의 소유권은 이니셜라이저에게 두고 PDA를 deposit_amount만큼의 위임자로 승인함
+    // 교환 때 PDA가 위임 권한으로 토큰 X를 보내고, 취소 때 이니셜라이저가 위임을 철회함
+    #[allow(clippy::too_many_arguments)]
+    pub fn process_init_escrow_with_delegate(
+        accounts: &[AccountInfo],
+        amount: u64,
+        deposit_amount: u64,
+        expiry_ts: i64,
+        nonce: u64,
+        fee_bps: u16,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if fee_bps > MAX_FEE_BPS {
+            return Err(EscrowError::InvalidFee.into());
+        }
+
+        if exceeds_amount_cap(amount) {
+            return Err(EscrowError::AmountTooLarge.into());
+        }
+
+        let account_info_iter = &mut accounts.iter();
+
+        // 이니셜라이저는 반드시 서명해야 함 (위임 승인에 서명이 이어짐)
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // PDA를 위임자로 승인할 이니셜라이저의 토큰 계정 (소유자는 바뀌지 않음)
+        let source_token_account = next_account_info(account_info_iter)?;
+        check_writable(source_token_account)?;
+        let source_token_account_info = unpack_token_account(source_token_account)?;
+        if source_token_account_info.owner != *initializer.key {
+            return Err(EscrowError::InvalidAuthority.into());
+        }
+
+        // 승인은 잔액을 확인하지 않으므로 지금 잔액으로 위임할 수량을 채울 수 있는지 먼저 확인
+        // (위임할 수량이 0이면 내보낼 토큰 X가 없으므로 에러 반환)
+        if deposit_amount == 0 || source_token_account_info.amount < deposit_amount {
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        // 토큰 Y를 받을 이니셜라이저의 토큰 계정
+        let token_to_receive_account = next_account_info(account_info_iter)?;
+        if !is_token_program(token_to_receive_account.owner) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let token_to_receive_account_info = unpack_token_account(token_to_receive_account)?;
+
+        // 에스크로 계정
+        let escrow_account = next_account_info(account_info_iter)?;
+        check_writable(escrow_account)?;
+        check_init_escrow_account(escrow_account, &Rent::get()?, program_id)?;
+
+        // 위임자가 될 PDA (이 에스크로의 PDA가 아니면 교환 때 서명할 수 없음)
+        let pda_account = next_account_info(account_info_iter)?;
+        let (pda, bump_seed) = escrow_pda(program_id, initializer.key, nonce);
+        if *pda_account.key != pda {
+            return Err(EscrowError::InvalidPda.into());
+        }
+
+        // 토큰 프로그램
+        let token_program = next_account_info(account_info_iter)?;
+        if !is_token_program(token_program.key) {
+            return Err(EscrowError::InvalidTokenProgram.into());
+        }
+
+        // 이니셜라이저의 레지스트리 (선택, InitEscrow와 같음)
+        let (registry, _) =
+            split_registry_accounts(account_info_iter.as_slice(), initializer.key, program_id)?;
+
+        // 같은 계정을 두 자리에 넘기면 상태가 덮어써지므로 에러 반환
+        if source_token_account.key == token_to_receive_account.key
+            || source_token_account.key == escrow_account.key
+        {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let clock = Clock::get()?;
+        let escrow_info = Escrow {
+            expiry_ts,
+            x_token_mint: source_token_account_info.mint,
+            nonce,
+            expected_mint: token_to_receive_account_info.mint,
+            token_program_id: *token_program.key,
+            fee_bps,
+            created_at: clock.unix_timestamp,
+            delegate_custody: true,
+            deposit_amount,
+            ..Escrow::new(
+                *initializer.key,
+                *source_token_account.key,
+                *token_to_receive_account.key,
+                amount,
+                bump_seed,
+            )
+        };
+        escrow_info.validate()?;
+        // 예전에 쓰던 계정에 남은 값이 섞이지 않도록 먼저 0으로 채움
+        escrow_account.try_borrow_mut_data()?.fill(0);
+        escrow_info.save_active(escrow_account)?;
+
+        // PDA를 이니셜라이저 토큰 계정의 위임자로 deposit_amount만큼 승인
+        // 이니셜라이저가 서명했으므로 서명이 CPI로 확장됨 (invoke)
+        let approve_delegate_ix = approve_ix(
+            token_program.key,
+            source_token_account.key,
+            &pda,
+            initializer.key,
+            deposit_amount,
+        )?;
+        msg!("Calling the token program to approve pda as delegate...");
+        invoke(
+            &approve_delegate_ix,
+            &[
+                source_token_account.clone(),
+                pda_account.clone(),
+                initializer.clone(),
+                token_program.clone(),
+            ],
+        )?;
+
+        // 레지스트리를 넘겼으면 열린 에스크로 목록에 추가
+        if let Some(registry) = registry {
+            add_to_registry(
+                &registry,
+                initializer,
+                initializer.key,
+                escrow_account.key,
+                program_id,
+            )?;
+        }
+
+        EscrowEvent::Initialized {
+            pubkey: *escrow_account.key,
+            amount,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    // SOL 에스크로 초기화 프로세스
+    // 이니셜라이저의 램포트를 에스크로 계정으로 옮기고 토큰 Y를 기다림
+    // 에스크로 계정은 이 프로그램 소유라서 교환 때 PDA 서명 없이 램포트를 내보낼 수 있음
+    pub fn process_init_sol_escrow(
+        accounts: &[AccountInfo],
+        amount: u64,
+        expected_amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        // 토큰 에스크로와 같은 예상 금액 상한을 적용
+        if exceeds_amount_cap(expected_amount) {
+            return Err(EscrowError::AmountTooLarge.into());
+        }
+
+        let account_info_iter = &mut accounts.iter();
+
+        // 이니셜라이저는 반드시 서명해야 함 (램포트를 보내므로)
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // 토큰 Y를 받을 이니셜라이저의 토큰 계정
+        let token_to_receive_account = next_account_info(account_info_iter)?;
+        if !is_token_program(token_to_receive_account.owner) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let token_to_receive_account_info = unpack_token_account(token_to_receive_account)?;
+
+        // 에스크로 어카운트 (토큰 에스크로와 같은 확인)
+        let escrow_account = next_account_info(account_info_iter)?;
+        check_writable(escrow_account)?;
+
+        // 램포트를 받기 전에 렌트비가 면제인지도 확인
+        // (맡긴 램포트는 렌트비 위로 쌓이므로 교환 뒤에도 계정이 살아 있음)
+        check_init_escrow_account(escrow_account, &Rent::get()?, program_id)?;
+
+        // 토큰 Y의 토큰 프로그램
+        let token_program = next_account_info(account_info_iter)?;
+        if !is_token_program(token_program.key) {
+            return Err(EscrowError::InvalidTokenProgram.into());
+        }
+
+        // 시스템 프로그램
+        let system_program_account = next_account_info(account_info_iter)?;
+        if !system_program::check_id(system_program_account.key) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        // 이니셜라이저의 레지스트리 (선택)
+        // 시스템 프로그램은 이미 받았으므로 레지스트리만 더 받음
+        let registry = match account_info_iter.next() {
+            Some(registry_account) => Some(RegistryAccounts {
+                registry_account,
+                system_program_account,
+                bump_seed: check_registry(registry_account, initializer.key, program_id)?,
+            }),
+            None => None,
+        };
+
+        // 램포트 X는 에스크로 계정 자체에 보관하므로
+        // 임시 "토큰" 계정 자리에 에스크로 계정을 기록함
+        let escrow_info = Escrow {
+            expected_mint: token_to_receive_account_info.mint,
+            token_program_id: *token_program.key,
+            is_native: true,
+            created_at: Clock::get()?.unix_timestamp,
+            ..Escrow::new(
+                *initializer.key,
+                *escrow_account.key,
+                *token_to_receive_account.key,
+                expected_amount,
+                0,
+            )
+        };
+        escrow_info.validate()?;
+        // 예전에 쓰던 계정에 남은 값이 섞이지 않도록 먼저 0으로 채움
+        escrow_account.try_borrow_mut_data()?.fill(0);
+        escrow_info.save_active(escrow_account)?;
+
+        // 이니셜라이저 -> 에스크로 계정으로 램포트 전송
+        // 이니셜라이저는 시스템 프로그램 소유이므로 시스템 프로그램을 호출해야 함
+        msg!("Calling the system program to transfer lamports to the escrow...");
+        invoke(
+            &system_transfer_ix(initializer.key, escrow_account.key, amount),
+            &[
+                initializer.clone(),
+                escrow_account.clone(),
+                system_program_account.clone(),
+            ],
+        )?;
+
+        // 레지스트리를 넘겼으면 열린 에스크로 목록에 추가
+        if let Some(registry) = registry {
+            add_to_registry(
+                &registry,
+                initializer,
+                initializer.key,
+                escrow_account.key,
+                program_id,
+            )?;
+        }
+
+        EscrowEvent::Initialized {
+            pubkey: *escrow_account.key,
+            amount: expected_amount,
+        }
+        .emit();
+
+        Ok(())
+    }
+}
+
+// 프로그램이 바꾸는 계정이 writable로 넘어왔는지 확인
+// 읽기 전용으로 넘기면 데이터를 쓰거나 CPI할 때 알아보기 힘든 에러가 나므로
+// 계정 메타 실수를 처음에 InvalidArgument로 바로 알려줌
+fn check_writable(account: &AccountInfo) -> ProgramResult {
+    if !account.is_writable {
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
+
+// 새 에스크로를 쓸 계정을 아래 순서대로 확인하고 각각 다른 에러를 반환함
+// 1. 이 프로그램 소유 (IncorrectProgramId)
+// 2. 크기가 Escrow::LEN (InvalidAccountData)
+// 3. 렌트비 면제 (NotRentExcept)
+// 4. 아직 초기화되지 않음 (EscrowAlreadyInitialized)
+// 순서가 정해져 있어야 같은 계정에 항상 같은 에러가 나고 감사할 때 따라가기 쉬움
+// (소유자를 먼저 확인해야 다른 프로그램의 데이터를 크기나 내용으로 판단하지 않음)
+// 초기화 경로(InitEscrow, 전송, 위임, SOL 에스크로)가 모두 이 확인을 씀
+fn check_init_escrow_account(
+    escrow_account: &AccountInfo,
+    rent: &Rent,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    // 에스크로 어카운트가 이 프로그램 소유가 아니면 에러 반환
+    // 다른 프로그램의 데이터를 믿고 쓰지 않도록 unpack 전에 확인
+    if escrow_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    // 에스크로 어카운트의 크기가 Escrow::LEN과 다르면 에러 반환
+    // 작게 할당된 계정을 패닉 대신 복구 가능한 에러로 처리
+    if escrow_account.data_len() != Escrow::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    // 렌트비가 면제가 아니면 렌트비 비면제 에러 반환
+    if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
+        return Err(EscrowError::NotRentExcept.into());
+    }
+
+    // 에스크로 어카운트가 초기화 되었다면, 이미 초기화되었다는 에러 반환
+    let current_escrow_info = Escrow::unpack_unchecked(&escrow_account.try_borrow_data()?)?;
+    if current_escrow_info.is_initialized() {
+        return Err(EscrowError::EscrowAlreadyInitialized.into());
+    }
+
+    Ok(())
+}
+
+// 레지스트리 PDA 계정을 확인하고 범프 시드를 반환
+// 이미 만든 레지스트리는 저장된 범프로, 아직 만들지 않은 레지스트리는 find_program_address로 주소를 확인
+fn check_registry(
+    registry_account: &AccountInfo,
+    initializer: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<u8, ProgramError> {
+    check_writable(registry_account)?;
+    let (pda, bump_seed) = if registry_account.owner == program_id {
+        let bump_seed = Registry::unpack(&registry_account.try_borrow_data()?)?.bump_seed;
+        (
+            registry_pda_with_bump(program_id, initializer, bump_seed)?,
+            bump_seed,
+        )
+    } else if system_program::check_id(registry_account.owner) && registry_account.data_is_empty() {
+        registry_pda(program_id, initializer)
+    } else {
+        return Err(ProgramError::IncorrectProgramId);
+    };
+
+    // 다른 사람의 레지스트리나 엉뚱한 계정이면 에러 반환
+    if *registry_account.key != pda {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(bump_seed)
+}
+
+// 남은 선택 계정 앞에 [레지스트리 PDA, 시스템 프로그램]이 있으면 확인해서 꺼내고 나머지를 돌려줌
+// 레지스트리는 뒤에 시스템 프로그램이 오는 것으로 다른 선택 계정(민트 등)과 구별함
+fn split_registry_accounts<'a, 'b>(
+    optional_accounts: &'b [AccountInfo<'a>],
+    initializer: &Pubkey,
+    program_id: &Pubkey,
+) -> Result<(Option<RegistryAccounts<'a, 'b>>, &'b [AccountInfo<'a>]), ProgramError> {
+    match optional_accounts {
+        [registry_account, system_program_account, rest @ ..]
+            if system_program::check_id(system_program_account.key) =>
+        {
+            let bump_seed = check_registry(registry_account, initializer, program_id)?;
+            let registry = RegistryAccounts {
+                registry_account,
+                system_program_account,
+                bump_seed,
+            };
+            Ok((Some(registry), rest))
+        }
+        _ => Ok((None, optional_accounts)),
+    }
+}
+
+// initializer의 레지스트리에 에스크로를 추가
+// 처음 쓰는 레지스트리는 PDA 계정에 공간을 할당하고, 목록이 늘어나면 계정 크기를 늘림
+// 렌트비 면제에 모자란 램포트는 payer가 냄
+// (보통은 이니셜라이저 자신이고, TransferInitializer에서는 넘겨주는 예전 이니셜라이저가 냄)
+fn add_to_registry(
+    registry: &RegistryAccounts,
+    payer: &AccountInfo,
+    initializer: &Pubkey,
+    escrow: &Pubkey,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let registry_account = registry.registry_account;
+    let system_program_account = registry.system_program_account;
+    let rent = Rent::get()?;
+
+    let is_new = registry_account.owner != program_id;
+    let mut registry_info = if is_new {
+        Registry {
+            bump_seed: registry.bump_seed,
+            escrows: Vec::new(),
+        }
+    } else {
+        Registry::unpack(&registry_account.try_borrow_data()?)?
+    };
+    registry_info.insert(*escrow);
+
+    let new_len = Registry::space(registry_info.escrows.len());
+    let shortfall = rent
+        .minimum_balance(new_len)
+        .saturating_sub(registry_account.lamports());
+    if shortfall > 0 {
+        msg!("Calling the system program to fund the registry account...");
+        invoke(
+            &system_transfer_ix(payer.key, registry_account.key, shortfall),
+            &[
+                payer.clone(),
+                registry_account.clone(),
+                system_program_account.clone(),
+            ],
+        )?;
+    }
+
+    if is_new {
+        // create_account는 주소에 램포트가 이미 있으면 실패하므로
+        // (누가 램포트를 미리 보내 두면 이 이니셜라이저는 레지스트리를 영영 만들 수 없음)
+        // 위에서 모자란 렌트비만 채운 뒤 공간 할당과 소유자 변경을 따로 호출함
+        // PDA이므로 레지스트리 시드로 invoke_signed 서명
+        let registry_seeds: &[&[u8]] =
+            &[REGISTRY_SEED, initializer.as_ref(), &[registry.bump_seed]];
+        msg!("Calling the system program to allocate the registry account...");
+        invoke_signed(
+            &allocate_ix(registry_account.key, new_len as u64),
+            &[registry_account.clone(), system_program_account.clone()],
+            &[registry_seeds],
+        )?;
+        msg!("Calling the system program to assign the registry account...");
+        invoke_signed(
+            &assign_ix(registry_account.key, program_id),
+            &[registry_account.clone(), system_program_account.clone()],
+            &[registry_seeds],
+        )?;
+    } else {
+        resize_account(registry_account, new_len)?;
+    }
+    registry_info.pack(&mut registry_account.try_borrow_mut_data()?)
+}
+
+// 레지스트리에서 에스크로를 뺌
+// 줄어든 만큼 계정 크기를 줄이고, 남는 램포트는 그대로 두어 다음에 늘어날 때 씀
+fn remove_from_registry(
+    registry_account: &AccountInfo,
+    initializer: &Pubkey,
+    escrow: &Pubkey,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    // 빼려면 이미 만든 레지스트리여야 함
+    if registry_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    check_registry(registry_account, initializer, program_id)?;
+
+    let mut registry_info = Registry::unpack(&registry_account.try_borrow_data()?)?;
+    if registry_info.remove(escrow) {
+        resize_account(
+            registry_account,
+            Registry::space(registry_info.escrows.len()),
+        )?;
+        registry_info.pack(&mut registry_account.try_borrow_mut_data()?)?;
+    }
+    Ok(())
+}
+
+// SPL Token 또는 Token-2022 프로그램인지 확인
+fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == spl_token::id() || *program_id == spl_token_2022::id()
+}
+
+// 랩트 SOL(SPL Token 또는 Token-2022의 네이티브 민트)인지 확인
+fn is_native_mint(mint: &Pubkey) -> bool {
+    *mint == spl_token::native_mint::id() || *mint == spl_token_2022::native_mint::id()
+}
+
+// 토큰 계정을 풂
+// Token-2022 계정은 뒤에 확장(extension) 데이터가 붙을 수 있으므로
+// StateWithExtensions로 기본 상태만 읽음 (기본 레이아웃은 SPL Token과 같음)
+fn unpack_token_account(account: &AccountInfo) -> Result<TokenAccount, ProgramError> {
+    let data = account.try_borrow_data()?;
+    Ok(StateWithExtensions::<TokenAccount>::unpack(&data)?.base)
+}
+
+// SPL Token, Token-2022 민트 계정을 풂 (Token-2022 확장이 붙어 있어도 기본 민트만 읽음)
+fn unpack_mint(account: &AccountInfo) -> Result<Mint, ProgramError> {
+    let data = account.try_borrow_data()?;
+    Ok(StateWithExtensions::<Mint>::unpack(&data)?.base)
+}
+
+// 민트의 소수점 자릿수가 MAX_DECIMALS 이하인지 확인하고 그대로 돌려줌
+// 자릿수가 너무 크면 비율, 수수료 계산이 넘칠 수 있으므로 에스크로를 만들기 전에 거절
+fn check_decimals(decimals: u8) -> Result<u8, ProgramError> {
+    if decimals > MAX_DECIMALS {
+        return Err(EscrowError::UnsupportedDecimals.into());
+    }
+    Ok(decimals)
+}
+
+// 토큰 프로그램에 맞는 빌더로 토큰 계정의 소유자를 바꾸는 명령을 만듦
+fn set_owner_ix(
+    token_program_id: &Pubkey,
+    owned: &Pubkey,
+    new_owner: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    if *token_program_id == spl_token_2022::id() {
+        spl_token_2022::instruction::set_authority(
+            token_program_id,
+            owned,
+            Some(new_owner),
+            spl_token_2022::instruction::AuthorityType::AccountOwner,
+            owner,
+            &[owner],
+        )
+    } else {
+        spl_token::instruction::set_authority(
+            token_program_id,
+            owned,
+            Some(new_owner),
+            spl_token::instruction::AuthorityType::AccountOwner,
+            owner,
+            &[owner],
+        )
+    }
+}
+
+// 토큰 프로그램에 맞는 빌더로 토큰 전송 명령을 만듦
+// Token-2022는 전송 수수료, 전송 훅 확장이 붙은 민트에서 transfer를 거절하므로
+// 민트와 자릿수를 확인하는 transfer_checked를 씀 (next_transfer_mint로 읽은 민트)
+fn transfer_ix(
+    token_program_id: &Pubkey,
+    source: &Pubkey,
+    mint: Option<&TransferMint>,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    if *token_program_id == spl_token_2022::id() {
+        let mint = mint.ok_or(ProgramError::NotEnoughAccountKeys)?;
+        spl_token_2022::instruction::transfer_checked(
+            token_program_id,
+            source,
+            mint.mint_account.key,
+            destination,
+            authority,
+            &[authority],
+            amount,
+            mint.decimals,
+        )
+    } else {
+        spl_token::instruction::transfer(
+            token_program_id,
+            source,
+            destination,
+            authority,
+            &[authority],
+            amount,
+        )
+    }
+}
+
+// 토큰 프로그램이 Token-2022면 다음 계정을 전송할 토큰의 민트로 읽음
+// 민트 주소가 에스크로의 민트와 같고 토큰 프로그램 소유인지 확인하고 자릿수를 함께 돌려줌
+// SPL Token은 민트 없이 전송하므로 계정을 읽지 않고 None 반환
+fn next_transfer_mint<'a, 'b, I: Iterator<Item = &'b AccountInfo<'a>>>(
+    account_info_iter: &mut I,
+    token_program_id: &Pubkey,
+    expected_mint: &Pubkey,
+) -> Result<Option<TransferMint<'a, 'b>>, ProgramError> {
+    if *token_program_id != spl_token_2022::id() {
+        return Ok(None);
+    }
+
+    let mint_account = next_account_info(account_info_iter)?;
+    if mint_account.key != expected_mint {
+        return Err(EscrowError::TokenMintMismatch.into());
+    }
+    if mint_account.owner != token_program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let decimals = unpack_mint(mint_account)?.decimals;
+
+    Ok(Some(TransferMint {
+        mint_account,
+        decimals,
+    }))
+}
+
+// 전송 CPI에 넘길 계정들 (Token-2022면 transfer_checked가 읽는 민트 계정을 더함)
+fn transfer_account_infos<'a>(
+    accounts: &[&AccountInfo<'a>],
+    mint: Option<&TransferMint<'a, '_>>,
+) -> Vec<AccountInfo<'a>> {
+    accounts
+        .iter()
+        .copied()
+        .chain(mint.map(|mint| mint.mint_account))
+        .cloned()
+        .collect()
+}
+
+// 토큰 프로그램에 맞는 빌더로 위임자를 승인하는 명령을 만듦
+fn approve_ix(
+    token_program_id: &Pubkey,
+    source: &Pubkey,
+    delegate: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    if *token_program_id == spl_token_2022::id() {
+        spl_token_2022::instruction::approve(
+            token_program_id,
+            source,
+            delegate,
+            owner,
+            &[owner],
+            amount,
+        )
+    } else {
+        spl_token::instruction::approve(token_program_id, source, delegate, owner, &[owner], amount)
+    }
+}
+
+// 토큰 프로그램에 맞는 빌더로 위임을 철회하는 명령을 만듦
+fn revoke_ix(
+    token_program_id: &Pubkey,
+    source: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    if *token_program_id == spl_token_2022::id() {
+        spl_token_2022::instruction::revoke(token_program_id, source, owner, &[owner])
+    } else {
+        spl_token::instruction::revoke(token_program_id, source, owner, &[owner])
+    }
+}
+
+// 토큰 프로그램에 맞는 빌더로 토큰 계정을 닫는 명령을 만듦
+fn close_account_ix(
+    token_program_id: &Pubkey,
+    account: &Pubkey,
+    destination: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    if *token_program_id == spl_token_2022::id() {
+        spl_token_2022::instruction::close_account(
+            token_program_id,
+            account,
+            destination,
+            owner,
+            &[owner],
+        )
+    } else {
+        spl_token::instruction::close_account(
+            token_program_id,
+            account,
+            destination,
+            owner,
+            &[owner],
+        )
+    }
+}
+
+// 시스템 프로그램의 램포트 전송 명령을 만듦
+#[allow(deprecated)]
+fn system_transfer_ix(from: &Pubkey, to: &Pubkey, lamports: u64) -> Instruction {
+    solana_program::system_instruction::transfer(from, to, lamports)
+}
+
+// 시스템 프로그램으로 계정의 데이터 공간을 할당하는 명령을 만듦
+#[allow(deprecated)]
+fn allocate_ix(account: &Pubkey, space: u64) -> Instruction {
+    solana_program::system_instruction::allocate(account, space)
+}
+
+// 시스템 프로그램으로 계정의 소유 프로그램을 바꾸는 명령을 만듦
+#[allow(deprecated)]
+fn assign_ix(account: &Pubkey, owner: &Pubkey) -> Instruction {
+    solana_program::system_instruction::assign(account, owner)
+}
+
+// 계정 데이터의 크기를 바꿈 (늘어난 부분은 0으로 채움)
+#[allow(deprecated)]
+fn resize_account(account: &AccountInfo, new_len: usize) -> ProgramResult {
+    account.realloc(new_len, true)
+}
+
+// 시스템 프로그램으로 시드 주소의 계정을 만드는 명령을 만듦
+// 베이스와 렌트비를 내는 계정은 모두 이니셜라이저
+#[allow(deprecated)]
+fn create_account_with_seed_ix(
+    base: &Pubkey,
+    to: &Pubkey,
+    seed: &str,
+    lamports: u64,
+    space: u64,
+    owner: &Pubkey,
+) -> Instruction {
+    solana_program::system_instruction::create_account_with_seed(
+        base, to, base, seed, lamports, space, owner,
+    )
+}
+
+// InitEscrow의 리턴 데이터
+// 직렬화된 에스크로 상태(Escrow::LEN) 다음에 PDA(32바이트)가 옴
+fn init_return_data(escrow_data: &[u8], pda: &Pubkey) -> Vec<u8> {
+    let mut return_data = escrow_data.to_vec();
+    return_data.extend_from_slice(pda.as_ref());
+    return_data
+}
+
+// 금액이 MAX_ESCROW_AMOUNT를 넘는지 확인
+// 기본 상한이 u64::MAX면 항상 false인 비교가 되므로 clippy 경고를 끔
+#[allow(clippy::absurd_extreme_comparisons)]
+fn exceeds_amount_cap(amount: u64) -> bool {
+    amount > MAX_ESCROW_AMOUNT
+}
+
+// 수량을 (수수료, 나머지)로 나눔
+// 수수료 = amount * fee_bps / 10_000 (곱셈 오버플로는 감싸지 않고 AmountOverflow 에러로 반환)
+fn split_fee(amount: u64, fee_bps: u16) -> Result<(u64, u64), ProgramError> {
+    let fee = amount
+        .checked_mul(u64::from(fee_bps))
+        .and_then(|product| product.checked_div(u64::from(MAX_FEE_BPS)))
+        .ok_or(EscrowError::AmountOverflow)?;
+    let remainder = amount.checked_sub(fee).ok_or(EscrowError::AmountOverflow)?;
+    Ok((fee, remainder))
+}
+
+// 부분 체결 때 내보낼 토큰 X의 수량
+// 남은 토큰 X * 이번에 채운 수량 / 남은 수량 (u128로 곱해서 중간값 오버플로를 피함)
+fn pro_rata_release(
+    x_balance: u64,
+    amount: u64,
+    remaining_amount: u64,
+) -> Result<u64, ProgramError> {
+    let release = u128::from(x_balance)
+        .checked_mul(u128::from(amount))
+        .and_then(|product| product.checked_div(u128::from(remaining_amount)))
+        .ok_or(EscrowError::AmountOverflow)?;
+    u64::try_from(release).map_err(|_| EscrowError::AmountOverflow.into())
+}
+
+// QueryEscrow의 리턴 데이터
+// 예상 금액(u64 LE, 8바이트) + 상태(1바이트) + 이니셜라이저(32바이트)
+fn query_return_data(escrow_info: &Escrow) -> Vec<u8> {
+    let mut return_data = escrow_info.expected_amount.to_le_bytes().to_vec();
+    return_data.push(escrow_info.status as u8);
+    return_data.extend_from_slice(escrow_info.initializer_pubkey.as_ref());
+    return_data
+}
+
+// 에스크로를 주어진 시각에 아직 취소할 수 없는지 확인
+// cancel_after_ts가 0이면 언제든 취소 가능
+fn cancel_too_early(escrow_info: &Escrow, now: i64) -> bool {
+    escrow_info.cancel_after_ts != 0 && now < escrow_info.cancel_after_ts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intruction::{amount_to_bytes, tag};
+    use crate::pda::ESCROW_SEED;
+    use crate::state::MAX_ESCROW_AGE_SECS;
+    #[cfg(feature = "rent-sysvar-account")]
+    use solana_program::sysvar;
+    use solana_program::{entrypoint::SUCCESS, program_stubs};
+    use spl_token::{
+        instruction::{AuthorityType, TokenInstruction},
+        state::{Account as TokenAccount, AccountState, Mint},
+    };
+    use std::{cell::Cell, sync::Once};
+
+    thread_local! {
+        // true면 이 스레드(테스트)의 CPI는 아무것도 바꾸지 않고 성공함 (조용히 실패한 CPI 흉내)
+        static NOOP_CPI: Cell<bool> = const { Cell::new(false) };
+        // true면 이 스레드(테스트)의 시스템 프로그램 램포트 전송을 실제로 옮김
+        // (램포트가 옮겨지지 않는다고 가정한 예전 테스트에 영향이 없도록 켠 테스트에서만)
+        static SYSTEM_TRANSFERS: Cell<bool> = const { Cell::new(false) };
+    }
+
+    // Rent::get() 등 시스템 변수 syscall을 테스트에서 쓸 수 있게 해주는 스텁
+    struct TestSyscallStubs;
+
+    impl program_stubs::SyscallStubs for TestSyscallStubs {
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe {
+                *(var_addr as *mut Rent) = Rent::default();
+            }
+            SUCCESS
+        }
+
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe {
+                *(var_addr as *mut Clock) = Clock::default();
+            }
+            SUCCESS
+        }
+
+        // 토큰 계정의 소유자를 바꾸는 SetAuthority만 흉내 내고 나머지 CPI는 아무것도 하지 않고 성공
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            _signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            if NOOP_CPI.with(Cell::get) {
+                return Ok(());
+            }
+            // 시스템 프로그램의 Transfer (bincode: 명령 번호 2(u32 LE) + 램포트(u64 LE))
+            if system_program::check_id(&instruction.program_id)
+                && SYSTEM_TRANSFERS.with(Cell::get)
+                && instruction.data.len() == 12
+                && instruction.data[..4] == 2u32.to_le_bytes()
+            {
+                let lamports = u64::from_le_bytes(instruction.data[4..].try_into().unwrap());
+                let find = |key: &Pubkey| {
+                    account_infos
+                        .iter()
+                        .find(|info| info.key == key)
+                        .ok_or(ProgramError::NotEnoughAccountKeys)
+                };
+                let from = find(&instruction.accounts[0].pubkey)?;
+                let to = find(&instruction.accounts[1].pubkey)?;
+                **from.try_borrow_mut_lamports()? = from
+                    .lamports()
+                    .checked_sub(lamports)
+                    .ok_or(ProgramError::InsufficientFunds)?;
+                **to.try_borrow_mut_lamports()? += lamports;
+                return Ok(());
+            }
+            if !is_token_program(&instruction.program_id) {
+                return Ok(());
+            }
+            if let Ok(TokenInstruction::SetAuthority {
+                authority_type: AuthorityType::AccountOwner,
+                new_authority: COption::Some(new_owner),
+            }) = TokenInstruction::unpack(&instruction.data)
+            {
+                let account = account_infos
+                    .iter()
+                    .find(|info| *info.key == instruction.accounts[0].pubkey)
+                    .ok_or(ProgramError::NotEnoughAccountKeys)?;
+                let mut data = account.try_borrow_mut_data()?;
+                if data.len() >= TokenAccount::LEN {
+                    let data = &mut data[..TokenAccount::LEN];
+                    let mut token_account = TokenAccount::unpack_unchecked(data)?;
+                    token_account.owner = new_owner;
+                    TokenAccount::pack(token_account, data)?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn set_test_syscall_stubs() {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(TestSyscallStubs));
+        });
+    }
+
+    // 테스트용 어카운트 (AccountInfo가 빌려 쓸 값들을 소유)
+    struct TestAccount {
+        key: Pubkey,
+        is_signer: bool,
+        is_writable: bool,
+        lamports: u64,
+        data: Vec<u8>,
+        owner: Pubkey,
+    }
+
+    impl TestAccount {
+        fn new(owner: Pubkey, data: Vec<u8>) -> Self {
+            Self {
+                key: Pubkey::new_unique(),
+                is_signer: false,
+                is_writable: true,
+                lamports: 0,
+                data,
+                owner,
+            }
+        }
+
+        fn signer(mut self) -> Self {
+            self.is_signer = true;
+            self
+        }
+
+        fn info(&mut self) -> AccountInfo<'_> {
+            AccountInfo::new(
+                &self.key,
+                self.is_signer,
+                self.is_writable,
+                &mut self.lamports,
+                &mut self.data,
+                &self.owner,
+                false,
+                0,
+            )
+        }
+    }
+
+    fn token_account_data(mint: &Pubkey, owner: &Pubkey) -> Vec<u8> {
+        let mut data = vec![0u8; TokenAccount::LEN];
+        let token_account = TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount: 100,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        };
+        TokenAccount::pack(token_account, &mut data).unwrap();
+        data
+    }
+
+    fn mint_data(decimals: u8) -> Vec<u8> {
+        let mut data = vec![0u8; Mint::LEN];
+        let mint = Mint {
+            decimals,
+            is_initialized: true,
+            ..Mint::default()
+        };
+        Mint::pack(mint, &mut data).unwrap();
+        data
+    }
+
+    // 렌트 시스템 변수의 데이터 (u64 + f64 + u8)
+    #[cfg(feature = "rent-sysvar-account")]
+    fn rent_sysvar_data(rent: &Rent) -> Vec<u8> {
+        let mut data = rent.lamports_per_byte_year.to_le_bytes().to_vec();
+        data.extend_from_slice(&rent.exemption_threshold.to_le_bytes());
+        data.push(rent.burn_percent);
+        data
+    }
+
+    // InitEscrow에 필요한 정상적인 어카운트들
+    struct InitAccounts {
+        initializer: TestAccount,
+        x_token: TestAccount,
+        receive: TestAccount,
+        escrow: TestAccount,
+        #[cfg(feature = "rent-sysvar-account")]
+        rent: TestAccount,
+        token_program: TestAccount,
+    }
+
+    impl InitAccounts {
+        fn new(program_id: &Pubkey) -> Self {
+            set_test_syscall_stubs();
+
+            let rent = Rent::default();
+
+            let initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+            let mut x_token = TestAccount::new(
+                spl_token::id(),
+                token_account_data(&Pubkey::new_unique(), &initializer.key),
+            );
+            x_token.lamports = rent.minimum_balance(TokenAccount::LEN);
+            let receive = TestAccount::new(
+                spl_token::id(),
+                token_account_data(&Pubkey::new_unique(), &initializer.key),
+            );
+
+            let mut escrow = TestAccount::new(*program_id, vec![0; Escrow::LEN]);
+            escrow.lamports = rent.minimum_balance(Escrow::LEN);
+
+            #[cfg(feature = "rent-sysvar-account")]
+            let rent_account = {
+                let mut rent_account = TestAccount::new(sysvar::id(), rent_sysvar_data(&rent));
+                rent_account.key = sysvar::rent::id();
+                rent_account
+            };
+
+            let mut token_program = TestAccount::new(Pubkey::default(), vec![]);
+            token_program.key = spl_token::id();
+
+            Self {
+                initializer,
+                x_token,
+                receive,
+                escrow,
+                #[cfg(feature = "rent-sysvar-account")]
+                rent: rent_account,
+                token_program,
+            }
+        }
+
+        fn infos(&mut self) -> Vec<AccountInfo<'_>> {
+            vec![
+                self.initializer.info(),
+                self.x_token.info(),
+                self.receive.info(),
+                self.escrow.info(),
+                #[cfg(feature = "rent-sysvar-account")]
+                self.rent.info(),
+                self.token_program.info(),
+            ]
+        }
+    }
+
+    fn init_escrow_data(amount: u64) -> Vec<u8> {
+        let mut data = vec![0];
+        data.extend_from_slice(&amount_to_bytes(amount));
+        data.extend_from_slice(&0i64.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data
+    }
+
+    fn escrow_with_cancel_after(cancel_after_ts: i64) -> Escrow {
+        let mut escrow_info = Escrow::default();
+        escrow_info.cancel_after_ts = cancel_after_ts;
+        escrow_info
+    }
+
+    #[test]
+    fn cancel_too_early_before_timelock() {
+        assert!(cancel_too_early(&escrow_with_cancel_after(1_000), 999));
+        assert!(cancel_too_early(&escrow_with_cancel_after(1_000), i64::MIN));
+    }
+
+    #[test]
+    fn cancel_allowed_after_timelock() {
+        assert!(!cancel_too_early(&escrow_with_cancel_after(1_000), 1_000));
+        assert!(!cancel_too_early(&escrow_with_cancel_after(1_000), 1_001));
+        // 0이면 언제든 취소 가능
+        assert!(!cancel_too_early(&escrow_with_cancel_after(0), i64::MIN));
+    }
+
+    #[test]
+    fn init_records_both_mints() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        let x_mint = Pubkey::new_unique();
+        accounts.x_token.data = token_account_data(&x_mint, &accounts.initializer.key);
+
+        Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)).unwrap();
+
+        let escrow_info = Escrow::unpack(&accounts.escrow.data).unwrap();
+        assert_eq!(escrow_info.x_token_mint, x_mint);
+        assert_eq!(
+            escrow_info.expected_mint,
+            TokenAccount::unpack(&accounts.receive.data).unwrap().mint
+        );
+        assert_eq!(escrow_info.status, EscrowStatus::Active);
+    }
+
+    #[test]
+    fn init_over_dirty_buffer_produces_clean_escrow() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+
+        // 초기화되지 않은 상태지만 예전 값이 남아 있는 계정
+        let dirty = Escrow {
+            status: EscrowStatus::Uninitialized,
+            initializer_pubkey: Pubkey::new_unique(),
+            expiry_ts: 1_700_000_000,
+            nonce: 9,
+            fee_bps: 999,
+            is_native: true,
+            ..Escrow::default()
+        };
+        Escrow::pack(dirty, &mut accounts.escrow.data).unwrap();
+
+        Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)).unwrap();
+
+        let (_, bump_seed) = escrow_pda(&program_id, &accounts.initializer.key, 0);
+        let escrow_info = Escrow::unpack(&accounts.escrow.data).unwrap();
+        assert_eq!(
+            escrow_info,
+            Escrow {
+                status: EscrowStatus::Active,
+                initializer_pubkey: accounts.initializer.key,
+                x_token_account_pubkey: accounts.x_token.key,
+                initializer_token_to_receive_account_pubkey: accounts.receive.key,
+                expected_amount: 10,
+                remaining_amount: 10,
+                bump_seed,
+                pda_seed_pubkey: accounts.initializer.key,
+                x_token_mint: TokenAccount::unpack(&accounts.x_token.data).unwrap().mint,
+                expected_mint: TokenAccount::unpack(&accounts.receive.data).unwrap().mint,
+                token_program_id: spl_token::id(),
+                ..Escrow::default()
+            }
+        );
+    }
+
+    #[test]
+    fn init_accepts_amount_at_cap() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+
+        Processor::process(
+            &program_id,
+            &accounts.infos(),
+            &init_escrow_data(MAX_ESCROW_AMOUNT),
+        )
+        .unwrap();
+
+        let escrow_info = Escrow::unpack(&accounts.escrow.data).unwrap();
+        assert_eq!(escrow_info.expected_amount, MAX_ESCROW_AMOUNT);
+        assert!(!exceeds_amount_cap(MAX_ESCROW_AMOUNT));
+    }
+
+    #[test]
+    #[cfg(feature = "amount-cap")]
+    fn init_rejects_amount_above_cap() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+
+        assert!(exceeds_amount_cap(MAX_ESCROW_AMOUNT + 1));
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &accounts.infos(),
+                &init_escrow_data(MAX_ESCROW_AMOUNT + 1),
+            ),
+            Err(ProgramError::from(EscrowError::AmountTooLarge))
+        );
+    }
+
+    fn init_batch_data(amounts: &[u64], base_nonce: u64, expiry_ts: i64, fee_bps: u16) -> Vec<u8> {
+        let mut data = vec![9];
+        data.extend_from_slice(
+            &borsh::to_vec(&(amounts.to_vec(), base_nonce, expiry_ts, fee_bps)).unwrap(),
+        );
+        data
+    }
+
+    #[test]
+    fn init_batch_creates_every_escrow() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+
+        // 같은 이니셜라이저가 소유한 임시 토큰 계정으로 에스크로 두 개를 더 만듦
+        let mut groups = Vec::new();
+        for _ in 0..2 {
+            let mut group = InitAccounts::new(&program_id);
+            group.x_token.data =
+                token_account_data(&Pubkey::new_unique(), &accounts.initializer.key);
+            groups.extend([group.x_token, group.receive, group.escrow]);
+        }
+
+        let mut infos = vec![
+            accounts.initializer.info(),
+            accounts.x_token.info(),
+            accounts.receive.info(),
+            accounts.escrow.info(),
+        ];
+        infos.extend(groups.iter_mut().map(TestAccount::info));
+        #[cfg(feature = "rent-sysvar-account")]
+        infos.push(accounts.rent.info());
+        infos.push(accounts.token_program.info());
+
+        Processor::process(
+            &program_id,
+            &infos,
+            &init_batch_data(&[10, 20, 30], 7, 1_700_000_000, 250),
+        )
+        .unwrap();
+
+        // 논스는 첫 논스부터 차례로 쓰고 만료 시각과 수수료는 모든 에스크로에 그대로 기록됨
+        let escrows = [&accounts.escrow, &groups[2], &groups[5]];
+        for (index, (escrow, amount)) in escrows.iter().zip([10, 20, 30]).enumerate() {
+            let escrow_info = Escrow::unpack(&escrow.data).unwrap();
+            assert_eq!(escrow_info.status, EscrowStatus::Active);
+            assert_eq!(escrow_info.initializer_pubkey, accounts.initializer.key);
+            assert_eq!(escrow_info.expected_amount, amount);
+            assert_eq!(escrow_info.nonce, 7 + index as u64);
+            assert_eq!(escrow_info.expiry_ts, 1_700_000_000);
+            assert_eq!(escrow_info.fee_bps, 250);
+        }
+    }
+
+    #[test]
+    fn init_batch_rejects_oversized_batch() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &accounts.infos(),
+                &init_batch_data(&[10; MAX_BATCH_SIZE + 1], 0, 0, 0),
+            ),
+            Err(ProgramError::from(EscrowError::BatchTooLarge))
+        );
+    }
+
+    #[test]
+    fn init_records_memo() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        let mut data = init_escrow_data(10);
+        data.extend_from_slice(&[7; 32]);
+
+        Processor::process(&program_id, &accounts.infos(), &data).unwrap();
+
+        let escrow_info = Escrow::unpack(&accounts.escrow.data).unwrap();
+        assert_eq!(escrow_info.memo, [7; 32]);
+    }
+
+    #[test]
+    fn init_records_fee_bps() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        let mut data = init_escrow_data(10);
+        data[25..].copy_from_slice(&250u16.to_le_bytes());
+
+        Processor::process(&program_id, &accounts.infos(), &data).unwrap();
+
+        let escrow_info = Escrow::unpack(&accounts.escrow.data).unwrap();
+        assert_eq!(escrow_info.fee_bps, 250);
+    }
+
+    #[test]
+    fn init_rejects_fee_above_max() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        let mut data = init_escrow_data(10);
+        data[25..].copy_from_slice(&(MAX_FEE_BPS + 1).to_le_bytes());
+
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &data),
+            Err(ProgramError::from(EscrowError::InvalidFee))
+        );
+    }
+
+    #[test]
+    fn split_fee_is_exact() {
+        assert_eq!(split_fee(1_000_000, 250), Ok((25_000, 975_000)));
+        assert_eq!(split_fee(1_000, 0), Ok((0, 1_000)));
+        assert_eq!(split_fee(1_000, MAX_FEE_BPS), Ok((1_000, 0)));
+
+        // 나누어 떨어지지 않아도 수수료와 나머지의 합은 항상 원래 수량
+        let (fee, remainder) = split_fee(12_345, 333).unwrap();
+        assert_eq!(fee, 411);
+        assert_eq!(fee + remainder, 12_345);
+    }
+
+    #[test]
+    fn split_fee_rejects_overflow() {
+        assert_eq!(
+            split_fee(u64::MAX, 2),
+            Err(ProgramError::from(EscrowError::AmountOverflow))
+        );
+    }
+
+    #[test]
+    fn init_return_data_has_escrow_and_pda() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)).unwrap();
+
+        let (pda, _) = escrow_pda(&program_id, &accounts.initializer.key, 0);
+        let return_data = init_return_data(&accounts.escrow.data, &pda);
+        assert_eq!(return_data.len(), Escrow::LEN + 32);
+
+        let escrow_info = Escrow::unpack(&return_data[..Escrow::LEN]).unwrap();
+        assert_eq!(escrow_info.initializer_pubkey, accounts.initializer.key);
+        assert_eq!(escrow_info.expected_amount, 10);
+        assert_eq!(&return_data[Escrow::LEN..], pda.as_ref());
+    }
+
+    #[test]
+    fn processor_derives_pda_from_escrow_seed() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)).unwrap();
+
+        // 초기화 때 저장한 범프는 ESCROW_SEED로 구한 범프
+        let escrow_info = Escrow::unpack(&accounts.escrow.data).unwrap();
+        let seeds = [
+            ESCROW_SEED,
+            accounts.initializer.key.as_ref(),
+            &0u64.to_le_bytes(),
+        ];
+        let (_, bump_seed) = Pubkey::find_program_address(&seeds, &program_id);
+        assert_eq!(escrow_info.bump_seed, bump_seed);
+
+        // 교환도 ESCROW_SEED로 구한 PDA를 받아들임
+        let mut accounts = exchange_accounts(&program_id, 100, 100);
+        let escrow_info = Escrow::unpack(&accounts[6].data).unwrap();
+        let seeds = [
+            ESCROW_SEED,
+            escrow_info.pda_seed_pubkey.as_ref(),
+            &escrow_info.nonce.to_le_bytes(),
+            &[escrow_info.bump_seed],
+        ];
+        accounts[8].key = Pubkey::create_program_address(&seeds, &program_id).unwrap();
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &exchange_data(100),
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn query_return_data_has_amount_status_and_initializer() {
+        let escrow_info = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: Pubkey::new_unique(),
+            expected_amount: 10,
+            ..Escrow::default()
+        };
+
+        let return_data = query_return_data(&escrow_info);
+        assert_eq!(return_data.len(), 41);
+        assert_eq!(&return_data[..8], &10u64.to_le_bytes());
+        assert_eq!(return_data[8], EscrowStatus::Active as u8);
+        assert_eq!(&return_data[9..], escrow_info.initializer_pubkey.as_ref());
+    }
+
+    #[test]
+    fn init_rejects_non_signer_initializer() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        accounts.initializer.is_signer = false;
+
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+
+    #[test]
+    fn init_rejects_temp_account_owned_by_someone_else() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        accounts.x_token.data = token_account_data(&Pubkey::new_unique(), &Pubkey::new_unique());
+
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+            Err(ProgramError::from(EscrowError::InvalidAuthority))
+        );
+    }
+
+    #[test]
+    fn init_rejects_escrow_account_below_rent_exemption() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        accounts.escrow.lamports -= 1;
+
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+            Err(ProgramError::from(EscrowError::NotRentExcept))
+        );
+    }
+
+    #[test]
+    fn init_rejects_temp_token_account_below_rent_exemption() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        accounts.x_token.lamports -= 1;
+
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+            Err(ProgramError::from(EscrowError::NotRentExcept))
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "rent-sysvar-account"))]
+    fn init_reads_rent_without_rent_sysvar_account() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+
+        // 렌트 시스템 변수 어카운트 없이 5개의 어카운트만 넘김
+        let infos = accounts.infos();
+        assert_eq!(infos.len(), 5);
+        assert_eq!(
+            Processor::process(&program_id, &infos, &init_escrow_data(10)),
+            Ok(())
+        );
+
+        // Rent::get()으로 읽은 렌트 기준으로 면제 여부를 계속 확인함
+        let mut accounts = InitAccounts::new(&program_id);
+        accounts.escrow.lamports -= 1;
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+            Err(ProgramError::from(EscrowError::NotRentExcept))
+        );
+    }
+
+    #[test]
+    fn init_rejects_foreign_owned_escrow_account() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        accounts.escrow.owner = Pubkey::new_unique();
+
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+            Err(ProgramError::IncorrectProgramId)
+        );
+    }
+
+    #[test]
+    fn init_rejects_undersized_escrow_account() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        accounts.escrow.data = vec![0; Escrow::LEN - 1];
+
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn init_checks_escrow_account_in_documented_order() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+
+        // 네 가지 조건을 모두 어긴 계정에서 시작해 앞의 조건부터 하나씩 고치면
+        // 매번 다음 조건의 에러가 나와야 함
+        let initialized = Escrow::new(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            10,
+            0,
+        );
+        let mut initialized_data = vec![0; Escrow::LEN];
+        Escrow::pack(initialized, &mut initialized_data).unwrap();
+        let rent_exempt_lamports = accounts.escrow.lamports;
+
+        accounts.escrow.owner = Pubkey::new_unique();
+        accounts.escrow.data = initialized_data.clone();
+        accounts.escrow.data.push(0);
+        accounts.escrow.lamports = 0;
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+            Err(ProgramError::IncorrectProgramId)
+        );
+
+        accounts.escrow.owner = program_id;
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        accounts.escrow.data = initialized_data;
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+            Err(ProgramError::from(EscrowError::NotRentExcept))
+        );
+
+        accounts.escrow.lamports = rent_exempt_lamports;
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+            Err(ProgramError::from(EscrowError::EscrowAlreadyInitialized))
+        );
+
+        accounts.escrow.data = vec![0; Escrow::LEN];
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn init_rejects_read_only_escrow_account() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        accounts.escrow.is_writable = false;
+
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn init_rejects_read_only_temp_token_account() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        accounts.x_token.is_writable = false;
+
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn init_handles_extra_trailing_account() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        let mut mint = receive_mint_account(&accounts, 6);
+        let mut extra = TestAccount::new(Pubkey::default(), vec![]);
+
+        // 선택 계정인 민트 뒤에 계정이 하나 더 붙음
+        let mut infos = accounts.infos();
+        infos.push(mint.info());
+        infos.push(extra.info());
+
+        // 엄격 모드에서는 덧붙은 계정을 거절하고 아니면 무시함
+        let expected = if cfg!(feature = "strict-accounts") {
+            Err(ProgramError::InvalidArgument)
+        } else {
+            Ok(())
+        };
+        assert_eq!(
+            Processor::process(&program_id, &infos, &init_escrow_data(10)),
+            expected
+        );
+    }
+
+    fn init_with_transfer_data(amount: u64, deposit_amount: u64) -> Vec<u8> {
+        let mut data = vec![10];
+        data.extend_from_slice(&amount_to_bytes(amount));
+        data.extend_from_slice(&amount_to_bytes(deposit_amount));
+        data.extend_from_slice(&0i64.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data
+    }
+
+    // InitEscrowWithTransfer에 필요한 정상적인 어카운트들 (EscrowInstruction의 순서)
+    // 4번(보관 계정)은 논스 0의 PDA가 소유한 토큰 X 계정
+    fn init_with_transfer_accounts(program_id: &Pubkey) -> Vec<TestAccount> {
+        set_test_syscall_stubs();
+
+        let x_mint = Pubkey::new_unique();
+        let initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let (pda, _) = escrow_pda(program_id, &initializer.key, 0);
+        let source = TestAccount::new(
+            spl_token::id(),
+            token_account_data(&x_mint, &initializer.key),
+        );
+        let receive = TestAccount::new(
+            spl_token::id(),
+            token_account_data(&Pubkey::new_unique(), &initializer.key),
+        );
+        let mut escrow = TestAccount::new(*program_id, vec![0; Escrow::LEN]);
+        escrow.lamports = Rent::default().minimum_balance(Escrow::LEN);
+        let vault = TestAccount::new(spl_token::id(), token_account_data(&x_mint, &pda));
+        let mut token_program = TestAccount::new(Pubkey::default(), vec![]);
+        token_program.key = spl_token::id();
+
+        vec![initializer, source, receive, escrow, vault, token_program]
+    }
+
+    #[test]
+    fn init_with_transfer_records_vault_custody() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = init_with_transfer_accounts(&program_id);
+        let source_data = accounts[1].data.clone();
+
+        Processor::process(
+            &program_id,
+            &account_infos(&mut accounts),
+            &init_with_transfer_data(10, 100),
+        )
+        .unwrap();
+
+        let escrow_info = Escrow::unpack(&accounts[3].data).unwrap();
+        assert_eq!(escrow_info.status, EscrowStatus::Active);
+        assert!(escrow_info.vault_custody);
+        assert_eq!(escrow_info.x_token_account_pubkey, accounts[4].key);
+        assert_eq!(escrow_info.expected_amount, 10);
+
+        // 이니셜라이저의 토큰 계정은 소유자가 바뀌지 않음
+        assert_eq!(accounts[1].data, source_data);
+    }
+
+    #[test]
+    fn init_with_transfer_rejects_vault_not_owned_by_pda() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = init_with_transfer_accounts(&program_id);
+        let x_mint = TokenAccount::unpack(&accounts[1].data).unwrap().mint;
+        accounts[4].data = token_account_data(&x_mint, &Pubkey::new_unique());
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &init_with_transfer_data(10, 100),
+            ),
+            Err(ProgramError::from(EscrowError::InvalidPda))
+        );
+    }
+
+    #[test]
+    fn init_with_transfer_rejects_vault_of_another_mint() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = init_with_transfer_accounts(&program_id);
+        let (pda, _) = escrow_pda(&program_id, &accounts[0].key, 0);
+        accounts[4].data = token_account_data(&Pubkey::new_unique(), &pda);
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &init_with_transfer_data(10, 100),
+            ),
+            Err(ProgramError::from(EscrowError::TokenMintMismatch))
+        );
+    }
+
+    #[test]
+    fn init_with_transfer_rejects_empty_or_oversized_deposit() {
+        let program_id = Pubkey::new_unique();
+
+        // 아무것도 맡기지 않거나 잔액(100개)보다 많이 맡기면 에러 반환
+        for deposit_amount in [0, 101] {
+            let mut accounts = init_with_transfer_accounts(&program_id);
+            assert_eq!(
+                Processor::process(
+                    &program_id,
+                    &account_infos(&mut accounts),
+                    &init_with_transfer_data(10, deposit_amount),
+                ),
+                Err(ProgramError::InsufficientFunds)
+            );
+            assert_eq!(accounts[3].data, vec![0; Escrow::LEN]);
+        }
+    }
+
+    fn init_with_delegate_data(amount: u64, deposit_amount: u64) -> Vec<u8> {
+        let mut data = init_with_transfer_data(amount, deposit_amount);
+        data[0] = 13;
+        data
+    }
+
+    // InitEscrowWithDelegate에 필요한 정상적인 어카운트들 (EscrowInstruction의 순서)
+    // 1번(토큰 X 100개)은 이니셜라이저 소유, 4번은 논스 0의 PDA
+    fn init_with_delegate_accounts(program_id: &Pubkey) -> Vec<TestAccount> {
+        let mut accounts = init_with_transfer_accounts(program_id);
+        let mut pda_account = TestAccount::new(Pubkey::default(), vec![]);
+        pda_account.key = escrow_pda(program_id, &accounts[0].key, 0).0;
+        accounts[4] = pda_account;
+        accounts
+    }
+
+    #[test]
+    fn init_with_delegate_records_delegate_custody() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = init_with_delegate_accounts(&program_id);
+        let source_data = accounts[1].data.clone();
+
+        Processor::process(
+            &program_id,
+            &account_infos(&mut accounts),
+            &init_with_delegate_data(10, 60),
+        )
+        .unwrap();
+
+        let escrow_info = Escrow::unpack(&accounts[3].data).unwrap();
+        assert_eq!(escrow_info.status, EscrowStatus::Active);
+        assert!(escrow_info.delegate_custody);
+        assert!(!escrow_info.vault_custody);
+        assert_eq!(escrow_info.deposit_amount, 60);
+        // 교환 때 토큰 X를 보낼 계정은 이니셜라이저의 토큰 계정 그 자체
+        assert_eq!(escrow_info.x_token_account_pubkey, accounts[1].key);
+        assert_eq!(
+            escrow_info.bump_seed,
+            escrow_pda(&program_id, &accounts[0].key, 0).1
+        );
+
+        // 소유자는 바뀌지 않음 (승인 CPI는 테스트 스텁이라 계정 데이터도 그대로)
+        assert_eq!(accounts[1].data, source_data);
+    }
+
+    #[test]
+    fn approve_ix_delegates_exact_amount_to_pda() {
+        let program_id = Pubkey::new_unique();
+        let initializer = Pubkey::new_unique();
+        let source = Pubkey::new_unique();
+        let (pda, _) = escrow_pda(&program_id, &initializer, 0);
+
+        let ix = approve_ix(&spl_token::id(), &source, &pda, &initializer, 60).unwrap();
+        assert_eq!(ix.program_id, spl_token::id());
+        assert_eq!(
+            spl_token::instruction::TokenInstruction::unpack(&ix.data),
+            Ok(spl_token::instruction::TokenInstruction::Approve { amount: 60 })
+        );
+        assert_eq!(ix.accounts[0].pubkey, source);
+        assert_eq!(ix.accounts[1].pubkey, pda);
+        assert_eq!(ix.accounts[2].pubkey, initializer);
+        assert!(ix.accounts[2].is_signer);
+    }
+
+    #[test]
+    fn transfer_ix_uses_transfer_checked_for_token_2022() {
+        let source = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let mut mint_account = TestAccount::new(spl_token_2022::id(), mint_data(6));
+        let mint_info = mint_account.info();
+        let mint = TransferMint {
+            mint_account: &mint_info,
+            decimals: 6,
+        };
+
+        let ix = transfer_ix(
+            &spl_token_2022::id(),
+            &source,
+            Some(&mint),
+            &destination,
+            &authority,
+            60,
+        )
+        .unwrap();
+        assert_eq!(ix.program_id, spl_token_2022::id());
+        assert_eq!(
+            spl_token_2022::instruction::TokenInstruction::unpack(&ix.data).unwrap(),
+            spl_token_2022::instruction::TokenInstruction::TransferChecked {
+                amount: 60,
+                decimals: 6
+            }
+        );
+        assert_eq!(ix.accounts[0].pubkey, source);
+        assert_eq!(ix.accounts[1].pubkey, *mint_info.key);
+        assert_eq!(ix.accounts[2].pubkey, destination);
+        assert_eq!(ix.accounts[3].pubkey, authority);
+
+        // Token-2022는 민트 없이 전송 명령을 만들 수 없음
+        assert_eq!(
+            transfer_ix(
+                &spl_token_2022::id(),
+                &source,
+                None,
+                &destination,
+                &authority,
+                60,
+            ),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+    }
+
+    #[test]
+    fn init_with_delegate_rejects_wrong_pda() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = init_with_delegate_accounts(&program_id);
+        accounts[4].key = Pubkey::new_unique();
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &init_with_delegate_data(10, 60),
+            ),
+            Err(ProgramError::from(EscrowError::InvalidPda))
+        );
+    }
+
+    #[test]
+    fn init_with_delegate_rejects_allowance_above_balance() {
+        let program_id = Pubkey::new_unique();
+
+        // 잔액(100개)보다 많이 위임하거나 아무것도 위임하지 않으면 에러 반환
+        for deposit_amount in [0, 101] {
+            let mut accounts = init_with_delegate_accounts(&program_id);
+            assert_eq!(
+                Processor::process(
+                    &program_id,
+                    &account_infos(&mut accounts),
+                    &init_with_delegate_data(10, deposit_amount),
+                ),
+                Err(ProgramError::InsufficientFunds)
+            );
+            assert_eq!(accounts[3].data, vec![0; Escrow::LEN]);
+        }
+    }
+
+    // InitAccounts의 받을 토큰 계정과 같은 민트의 민트 계정
+    fn receive_mint_account(accounts: &InitAccounts, decimals: u8) -> TestAccount {
+        let mut mint = TestAccount::new(spl_token::id(), mint_data(decimals));
+        mint.key = TokenAccount::unpack(&accounts.receive.data).unwrap().mint;
+        mint
+    }
+
+    #[test]
+    fn init_records_mint_decimals() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        let mut mint = receive_mint_account(&accounts, 6);
+
+        let mut infos = accounts.infos();
+        infos.push(mint.info());
+        Processor::process(&program_id, &infos, &init_escrow_data(10)).unwrap();
+        drop(infos);
+
+        let escrow_info = Escrow::unpack(&accounts.escrow.data).unwrap();
+        assert_eq!(escrow_info.expected_decimals, 6);
+    }
+
+    #[test]
+    fn init_without_mint_leaves_decimals_unknown() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+
+        Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)).unwrap();
+
+        let escrow_info = Escrow::unpack(&accounts.escrow.data).unwrap();
+        assert_eq!(escrow_info.expected_decimals, DECIMALS_UNKNOWN);
+    }
+
+    #[test]
+    fn init_accepts_nine_decimal_mints_on_both_sides() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        let mut y_mint = receive_mint_account(&accounts, 9);
+        let mut x_mint = TestAccount::new(spl_token::id(), mint_data(9));
+        x_mint.key = TokenAccount::unpack(&accounts.x_token.data).unwrap().mint;
+
+        let mut infos = accounts.infos();
+        infos.push(y_mint.info());
+        infos.push(x_mint.info());
+        Processor::process(&program_id, &infos, &init_escrow_data(10)).unwrap();
+        drop(infos);
+
+        let escrow_info = Escrow::unpack(&accounts.escrow.data).unwrap();
+        assert_eq!(escrow_info.expected_decimals, 9);
+    }
+
+    #[test]
+    fn init_rejects_mint_with_too_many_decimals() {
+        let program_id = Pubkey::new_unique();
+
+        // 토큰 Y의 민트가 20자리
+        let mut accounts = InitAccounts::new(&program_id);
+        let mut y_mint = receive_mint_account(&accounts, 20);
+        let mut infos = accounts.infos();
+        infos.push(y_mint.info());
+        assert_eq!(
+            Processor::process(&program_id, &infos, &init_escrow_data(10)),
+            Err(ProgramError::from(EscrowError::UnsupportedDecimals))
+        );
+        drop(infos);
+        assert_eq!(accounts.escrow.data, vec![0; Escrow::LEN]);
+
+        // 토큰 Y는 9자리지만 토큰 X의 민트가 20자리
+        let mut accounts = InitAccounts::new(&program_id);
+        let mut y_mint = receive_mint_account(&accounts, 9);
+        let mut x_mint = TestAccount::new(spl_token::id(), mint_data(20));
+        x_mint.key = TokenAccount::unpack(&accounts.x_token.data).unwrap().mint;
+        let mut infos = accounts.infos();
+        infos.push(y_mint.info());
+        infos.push(x_mint.info());
+        assert_eq!(
+            Processor::process(&program_id, &infos, &init_escrow_data(10)),
+            Err(ProgramError::from(EscrowError::UnsupportedDecimals))
+        );
+    }
+
+    #[test]
+    fn check_decimals_allows_up_to_max() {
+        assert_eq!(check_decimals(0), Ok(0));
+        assert_eq!(check_decimals(MAX_DECIMALS), Ok(MAX_DECIMALS));
+        assert_eq!(
+            check_decimals(MAX_DECIMALS + 1),
+            Err(ProgramError::from(EscrowError::UnsupportedDecimals))
+        );
+    }
+
+    #[test]
+    fn init_rejects_mint_of_another_token() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        let mut mint = receive_mint_account(&accounts, 6);
+        mint.key = Pubkey::new_unique();
+
+        let mut infos = accounts.infos();
+        infos.push(mint.info());
+        assert_eq!(
+            Processor::process(&program_id, &infos, &init_escrow_data(10)),
+            Err(ProgramError::from(EscrowError::TokenMintMismatch))
+        );
+    }
+
+    #[test]
+    fn init_twice_returns_escrow_already_initialized() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        let x_token_data = accounts.x_token.data.clone();
+
+        Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)).unwrap();
+
+        // 같은 에스크로 계정으로 다시 보내면 (중복 전송) 전용 에러를 반환
+        // (임시 토큰 계정의 소유자 확인에 먼저 걸리지 않도록 소유자를 이니셜라이저로 되돌림)
+        accounts.x_token.data = x_token_data;
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+            Err(ProgramError::from(EscrowError::EscrowAlreadyInitialized))
+        );
+    }
+
+    #[test]
+    fn init_rejects_temp_account_as_receive_account() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+
+        // 받을 계정 자리에 임시 토큰 계정을 한 번 더 넘김
+        let mut infos = accounts.infos();
+        infos[2] = infos[1].clone();
+
+        assert_eq!(
+            Processor::process(&program_id, &infos, &init_escrow_data(10)),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn init_rejects_uninitialized_receive_account() {
+        let program_id = Pubkey::new_unique();
+
+        // 토큰 프로그램 소유지만 초기화되지 않은 계정, 토큰 계정이 아닌 민트 계정
+        for data in [vec![0; TokenAccount::LEN], mint_data(6)] {
+            let mut accounts = InitAccounts::new(&program_id);
+            accounts.receive.data = data;
+
+            assert_eq!(
+                Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+                Err(ProgramError::InvalidAccountData)
+            );
+            assert_eq!(accounts.escrow.data, vec![0; Escrow::LEN]);
+        }
+    }
+
+    #[test]
+    fn init_rejects_temp_account_as_escrow_account() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        let x_token_data = accounts.x_token.data.clone();
+
+        // 에스크로 계정 자리에 임시 토큰 계정을 한 번 더 넘김
+        let mut infos = accounts.infos();
+        infos[3] = infos[1].clone();
+
+        assert_eq!(
+            Processor::process(&program_id, &infos, &init_escrow_data(10)),
+            Err(ProgramError::InvalidArgument)
+        );
+        drop(infos);
+
+        // 아무것도 쓰이기 전에 거절되어 토큰 계정 데이터가 그대로 남아 있음
+        assert_eq!(accounts.x_token.data, x_token_data);
+    }
+
+    #[test]
+    fn init_moves_temp_account_owner_to_pda() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+
+        Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)).unwrap();
+
+        let (pda, _) = escrow_pda(&program_id, &accounts.initializer.key, 0);
+        assert_eq!(
+            TokenAccount::unpack(&accounts.x_token.data).unwrap().owner,
+            pda
+        );
+    }
+
+    #[test]
+    fn init_rejects_when_owner_change_does_nothing() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+
+        // set_authority CPI가 에러 없이 끝났지만 소유자를 바꾸지 않은 경우
+        NOOP_CPI.with(|noop| noop.set(true));
+        let result = Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10));
+        NOOP_CPI.with(|noop| noop.set(false));
+
+        assert_eq!(
+            result,
+            Err(ProgramError::from(EscrowError::AuthorityTransferFailed))
+        );
+        assert_eq!(
+            TokenAccount::unpack(&accounts.x_token.data).unwrap().owner,
+            accounts.initializer.key
+        );
+    }
+
+    #[test]
+    fn init_records_spl_token_program() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+
+        Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)).unwrap();
+
+        let escrow_info = Escrow::unpack(&accounts.escrow.data).unwrap();
+        assert_eq!(escrow_info.token_program_id, spl_token::id());
+    }
+
+    #[test]
+    fn init_records_token_2022_program() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        accounts.x_token.owner = spl_token_2022::id();
+        accounts.receive.owner = spl_token_2022::id();
+        accounts.token_program.key = spl_token_2022::id();
+
+        Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)).unwrap();
+
+        let escrow_info = Escrow::unpack(&accounts.escrow.data).unwrap();
+        assert_eq!(escrow_info.token_program_id, spl_token_2022::id());
+    }
+
+    #[test]
+    fn init_rejects_fake_token_program() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        accounts.token_program.key = Pubkey::new_unique();
+
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+            Err(ProgramError::from(EscrowError::InvalidTokenProgram))
+        );
+    }
+
+    fn validate_init_data(amount: u64, fee_bps: u16) -> Vec<u8> {
+        let mut data = vec![11];
+        data.extend_from_slice(&amount_to_bytes(amount));
+        data.extend_from_slice(&fee_bps.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn validate_init_accepts_good_accounts_without_changes() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        let x_token_data = accounts.x_token.data.clone();
+
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &validate_init_data(10, 0)),
+            Ok(())
+        );
+
+        // 에스크로 계정과 임시 토큰 계정은 그대로
+        assert!(accounts.escrow.data.iter().all(|byte| *byte == 0));
+        assert_eq!(accounts.x_token.data, x_token_data);
+
+        // 아무것도 바꾸지 않았으므로 같은 계정으로 실제 초기화도 성공함
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &validate_init_data(10, 0)),
+            Ok(())
+        );
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &init_escrow_data(10)),
+            Ok(())
+        );
+    }
+
+    // 아직 만들지 않은 레지스트리 PDA와 시스템 프로그램
+    fn unopened_registry_accounts(
+        program_id: &Pubkey,
+        initializer: &Pubkey,
+    ) -> (TestAccount, TestAccount) {
+        let mut registry = TestAccount::new(system_program::id(), vec![]);
+        registry.key = registry_pda(program_id, initializer).0;
+        let mut system_program_account = TestAccount::new(Pubkey::default(), vec![]);
+        system_program_account.key = system_program::id();
+        (registry, system_program_account)
+    }
+
+    #[test]
+    fn validate_init_accepts_unopened_registry() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        let (mut registry, mut system_program_account) =
+            unopened_registry_accounts(&program_id, &accounts.initializer.key);
+
+        // 민트 없이 레지스트리와 시스템 프로그램만 덧붙임
+        let mut infos = accounts.infos();
+        infos.extend([registry.info(), system_program_account.info()]);
+        assert_eq!(
+            Processor::process(&program_id, &infos, &validate_init_data(10, 0)),
+            Ok(())
+        );
+        drop(infos);
+
+        // 확인만 하므로 레지스트리 계정은 만들어지지 않음
+        assert!(registry.data.is_empty());
+        assert_eq!(registry.owner, system_program::id());
+    }
+
+    #[test]
+    fn init_rejects_registry_of_another_initializer() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        let (mut registry, mut system_program_account) =
+            unopened_registry_accounts(&program_id, &Pubkey::new_unique());
+
+        let mut infos = accounts.infos();
+        infos.extend([registry.info(), system_program_account.info()]);
+        assert_eq!(
+            Processor::process(&program_id, &infos, &init_escrow_data(10)),
+            Err(ProgramError::InvalidSeeds)
+        );
+    }
+
+    #[test]
+    fn init_rejects_registry_owned_by_another_program() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = InitAccounts::new(&program_id);
+        let (mut registry, mut system_program_account) =
+            unopened_registry_accounts(&program_id, &accounts.initializer.key);
+        registry.owner = Pubkey::new_unique();
+
+        let mut infos = accounts.infos();
+        infos.extend([registry.info(), system_program_account.info()]);
+        assert_eq!(
+            Processor::process(&program_id, &infos, &init_escrow_data(10)),
+            Err(ProgramError::IncorrectProgramId)
+        );
+    }
+
+    #[test]
+    fn validate_init_returns_the_init_error_for_each_bad_account() {
+        let program_id = Pubkey::new_unique();
+        let cases: [(fn(&mut InitAccounts), ProgramError); 6] = [
+            (
+                |accounts| accounts.initializer.is_signer = false,
+                ProgramError::MissingRequiredSignature,
+            ),
+            (
+                |accounts| {
+                    accounts.x_token.data =
+                        token_account_data(&Pubkey::new_unique(), &Pubkey::new_unique())
+                },
+                EscrowError::InvalidAuthority.into(),
+            ),
+            (
+                |accounts| accounts.x_token.lamports -= 1,
+                EscrowError::NotRentExcept.into(),
+            ),
+            (
+                |accounts| accounts.escrow.lamports -= 1,
+                EscrowError::NotRentExcept.into(),
+            ),
+            (
+                |accounts| accounts.escrow.owner = Pubkey::new_unique(),
+                ProgramError::IncorrectProgramId,
+            ),
+            (
+                |accounts| accounts.token_program.key = Pubkey::new_unique(),
+                EscrowError::InvalidTokenProgram.into(),
+            ),
+        ];
+
+        for (break_account, expected) in cases {
+            let mut accounts = InitAccounts::new(&program_id);
+            break_account(&mut accounts);
+            assert_eq!(
+                Processor::process(&program_id, &accounts.infos(), &validate_init_data(10, 0)),
+                Err(expected)
+            );
+        }
+
+        // 계정이 아닌 값도 InitEscrow처럼 확인
+        let mut accounts = InitAccounts::new(&program_id);
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &accounts.infos(),
+                &validate_init_data(10, MAX_FEE_BPS + 1)
+            ),
+            Err(ProgramError::from(EscrowError::InvalidFee))
+        );
+    }
+
+    // Exchange에 필요한 정상적인 어카운트들 (EscrowInstruction::Exchange의 순서)
+    // 임시 계정에는 토큰 X가 x_balance만큼 있고 에스크로는 토큰 Y expected_amount를 기다림
+    fn exchange_accounts(
+        program_id: &Pubkey,
+        x_balance: u64,
+        expected_amount: u64,
+    ) -> Vec<TestAccount> {
+        set_test_syscall_stubs();
+
+        let x_mint = Pubkey::new_unique();
+        let y_mint = Pubkey::new_unique();
+        let initializer = TestAccount::new(Pubkey::default(), vec![]);
+        let (pda, bump_seed) = escrow_pda(program_id, &initializer.key, 0);
+
+        let taker = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let taker_send = TestAccount::new(spl_token::id(), token_account_data(&y_mint, &taker.key));
+        let taker_receive =
+            TestAccount::new(spl_token::id(), token_account_data(&x_mint, &taker.key));
+        let mut temp = TestAccount::new(spl_token::id(), token_account_data(&x_mint, &pda));
+        set_token_amount(&mut temp, x_balance);
+        let initializer_receive = TestAccount::new(
+            spl_token::id(),
+            token_account_data(&y_mint, &initializer.key),
+        );
+
+        let escrow_info = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: initializer.key,
+            x_token_account_pubkey: temp.key,
+            initializer_token_to_receive_account_pubkey: initializer_receive.key,
+            expected_amount,
+            remaining_amount: expected_amount,
+            bump_seed,
+            pda_seed_pubkey: initializer.key,
+            x_token_mint: x_mint,
+            expected_mint: y_mint,
+            token_program_id: spl_token::id(),
+            ..Escrow::default()
+        };
+        let mut escrow = TestAccount::new(*program_id, vec![0; Escrow::LEN]);
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+
+        let mut token_program = TestAccount::new(Pubkey::default(), vec![]);
+        token_program.key = spl_token::id();
+        let mut pda_account = TestAccount::new(Pubkey::default(), vec![]);
+        pda_account.key = pda;
+        let fee = TestAccount::new(
+            spl_token::id(),
+            token_account_data(&y_mint, &Pubkey::new_unique()),
+        );
+
+        vec![
+            taker,
+            taker_send,
+            taker_receive,
+            temp,
+            initializer,
+            initializer_receive,
+            escrow,
+            token_program,
+            pda_account,
+            fee,
+        ]
+    }
+
+    fn account_infos(accounts: &mut [TestAccount]) -> Vec<AccountInfo<'_>> {
+        accounts.iter_mut().map(TestAccount::info).collect()
+    }
+
+    // 토큰 계정의 잔액을 바꿈 (토큰 프로그램 CPI는 스텁이라 잔액이 움직이지 않음)
+    fn set_token_amount(account: &mut TestAccount, amount: u64) {
+        let mut token_account = TokenAccount::unpack(&account.data).unwrap();
+        token_account.amount = amount;
+        TokenAccount::pack(token_account, &mut account.data).unwrap();
+    }
+
+    fn exchange_data(amount: u64) -> Vec<u8> {
+        let mut data = vec![1];
+        data.extend_from_slice(&amount_to_bytes(amount));
+        data
+    }
+
+    #[test]
+    fn pro_rata_release_sums_to_balance() {
+        // 토큰 X 1_000개를 토큰 Y 300개에 팔 때 100 + 200으로 나눠 채움
+        let first = pro_rata_release(1_000, 100, 300).unwrap();
+        assert_eq!(first, 333);
+        let second = pro_rata_release(1_000 - first, 200, 200).unwrap();
+        assert_eq!(second, 667);
+        assert_eq!(first + second, 1_000);
+    }
+
+    #[test]
+    fn exchange_two_partial_fills_complete_escrow() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = exchange_accounts(&program_id, 1_000, 300);
+
+        Processor::process(
+            &program_id,
+            &account_infos(&mut accounts),
+            &exchange_data(100),
+        )
+        .unwrap();
+
+        let escrow_info = Escrow::unpack(&accounts[6].data).unwrap();
+        assert_eq!(escrow_info.remaining_amount, 200);
+        assert_eq!(escrow_info.status, EscrowStatus::Active);
+
+        // 첫 체결로 나간 토큰 X를 임시 계정 잔액에 반영
+        set_token_amount(&mut accounts[3], 1_000 - 333);
+        Processor::process(
+            &program_id,
+            &account_infos(&mut accounts),
+            &exchange_data(200),
+        )
+        .unwrap();
+
+        let escrow_info = Escrow::unpack(&accounts[6].data).unwrap();
+        assert_eq!(escrow_info.remaining_amount, 0);
+        assert_eq!(escrow_info.status, EscrowStatus::Completed);
+    }
+
+    // 위임으로 맡긴 에스크로를 교환할 어카운트들
+    // 3번은 이니셜라이저 소유의 토큰 계정(토큰 X 1_000개)이고 PDA에게 delegated_amount만큼 위임됨
+    // 에스크로에는 초기화 때 위임한 수량 deposit_amount가 저장되어 있음
+    fn delegate_exchange_accounts(
+        program_id: &Pubkey,
+        deposit_amount: u64,
+        delegated_amount: u64,
+        expected_amount: u64,
+    ) -> Vec<TestAccount> {
+        let mut accounts = exchange_accounts(program_id, 1_000, expected_amount);
+
+        let mut source = TokenAccount::unpack(&accounts[3].data).unwrap();
+        source.owner = accounts[4].key;
+        source.delegate = COption::Some(accounts[8].key);
+        source.delegated_amount = delegated_amount;
+        TokenAccount::pack(source, &mut accounts[3].data).unwrap();
+
+        let mut escrow_info = Escrow::unpack(&accounts[6].data).unwrap();
+        escrow_info.delegate_custody = true;
+        escrow_info.deposit_amount = deposit_amount;
+        Escrow::pack(escrow_info, &mut accounts[6].data).unwrap();
+        accounts
+    }
+
+    #[test]
+    fn exchange_of_delegate_escrow_releases_from_stored_deposit() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = delegate_exchange_accounts(&program_id, 60, 60, 30);
+
+        Processor::process(
+            &program_id,
+            &account_infos(&mut accounts),
+            &exchange_data(10),
+        )
+        .unwrap();
+
+        // 3분의 1을 채웠으므로 위임한 60개 중 20개가 나가고 40개가 남음
+        let escrow_info = Escrow::unpack(&accounts[6].data).unwrap();
+        assert_eq!(escrow_info.remaining_amount, 20);
+        assert_eq!(escrow_info.deposit_amount, 40);
+    }
+
+    #[test]
+    fn exchange_rejects_delegation_reduced_after_init() {
+        let program_id = Pubkey::new_unique();
+        // 초기화 때 60개를 위임한 뒤 이니셜라이저가 PDA에게 1개만 다시 승인함
+        let mut accounts = delegate_exchange_accounts(&program_id, 60, 1, 30);
+        let escrow_data = accounts[6].data.clone();
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &exchange_data(30),
+            ),
+            Err(ProgramError::from(EscrowError::DelegationChanged))
+        );
+        assert_eq!(accounts[6].data, escrow_data);
+    }
+
+    #[test]
+    fn exchange_rejects_revoked_delegation() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = delegate_exchange_accounts(&program_id, 60, 60, 30);
+
+        // 이니셜라이저가 위임을 철회함
+        let mut source = TokenAccount::unpack(&accounts[3].data).unwrap();
+        source.delegate = COption::None;
+        source.delegated_amount = 0;
+        TokenAccount::pack(source, &mut accounts[3].data).unwrap();
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &exchange_data(30),
+            ),
+            Err(ProgramError::from(EscrowError::DelegationChanged))
+        );
+    }
+
+    #[test]
+    fn exchange_by_three_takers_records_fills_and_last_taker() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = exchange_accounts(&program_id, 900, 300);
+
+        // 서로 다른 테이커 셋이 각각 3분의 1씩 채움
+        let mut remaining_x = 900;
+        for fill in 1..=3 {
+            let taker = Pubkey::new_unique();
+            accounts[0].key = taker;
+            set_token_amount(&mut accounts[3], remaining_x);
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &exchange_data(100),
+            )
+            .unwrap();
+            remaining_x -= 300;
+
+            let escrow_info = Escrow::unpack(&accounts[6].data).unwrap();
+            assert_eq!(escrow_info.fills, fill);
+            assert_eq!(escrow_info.last_taker, taker);
+        }
+
+        let escrow_info = Escrow::unpack(&accounts[6].data).unwrap();
+        assert_eq!(escrow_info.fills, 3);
+        assert_eq!(escrow_info.remaining_amount, 0);
+        assert_eq!(escrow_info.status, EscrowStatus::Completed);
+    }
+
+    // 교환이 끝난 베스팅 에스크로와 ClaimVested에 필요한 어카운트들 (EscrowInstruction의 순서)
+    // 임시 계정에는 토큰 X 1_000개가 있고 테이커(0번)가 교환했음
+    fn claim_vested_accounts(
+        program_id: &Pubkey,
+        created_at: i64,
+        expiry_ts: i64,
+        claimed_amount: u64,
+    ) -> Vec<TestAccount> {
+        let accounts = exchange_accounts(program_id, 1_000, 300);
+        let escrow_info = Escrow {
+            status: EscrowStatus::Completed,
+            remaining_amount: 0,
+            vesting: true,
+            vesting_total: 1_000,
+            claimed_amount,
+            created_at,
+            expiry_ts,
+            fills: 1,
+            last_taker: accounts[0].key,
+            ..Escrow::unpack(&accounts[6].data).unwrap()
+        };
+
+        // 테이커, 테이커가 받을 계정, 임시 계정, 이니셜라이저, 에스크로, 토큰 프로그램, PDA
+        let mut accounts: Vec<Option<TestAccount>> = accounts.into_iter().map(Some).collect();
+        let mut accounts: Vec<TestAccount> = [0, 2, 3, 4, 6, 7, 8]
+            .iter()
+            .map(|&i| accounts[i].take().unwrap())
+            .collect();
+        Escrow::pack(escrow_info, &mut accounts[4].data).unwrap();
+        accounts
+    }
+
+    #[test]
+    fn claim_vested_releases_linear_share() {
+        let program_id = Pubkey::new_unique();
+
+        // 테스트 Clock은 항상 0이므로 만든 시각과 만료 시각을 옮겨 0%, 50%, 100% 경과를 만듦
+        for (created_at, expiry_ts, expected) in [(0, 100, 0), (-50, 50, 500), (-100, 0, 1_000)] {
+            let mut accounts = claim_vested_accounts(&program_id, created_at, expiry_ts, 0);
+            Processor::process(&program_id, &account_infos(&mut accounts), &[14]).unwrap();
+
+            let escrow_info = Escrow::unpack(&accounts[4].data).unwrap();
+            assert_eq!(escrow_info.claimed_amount, expected);
+        }
+    }
+
+    #[test]
+    fn claim_vested_releases_only_unclaimed_share() {
+        let program_id = Pubkey::new_unique();
+
+        // 50% 경과에 이미 300개를 찾아갔으면 200개만 더 찾아감
+        let mut accounts = claim_vested_accounts(&program_id, -50, 50, 300);
+        Processor::process(&program_id, &account_infos(&mut accounts), &[14]).unwrap();
+        assert_eq!(
+            Escrow::unpack(&accounts[4].data).unwrap().claimed_amount,
+            500
+        );
+
+        // 다시 호출해도 새로 풀린 수량이 없으므로 그대로
+        Processor::process(&program_id, &account_infos(&mut accounts), &[14]).unwrap();
+        assert_eq!(
+            Escrow::unpack(&accounts[4].data).unwrap().claimed_amount,
+            500
+        );
+    }
+
+    #[test]
+    fn claim_vested_rejects_other_signer() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = claim_vested_accounts(&program_id, -50, 50, 0);
+        accounts[0].key = Pubkey::new_unique();
+
+        assert_eq!(
+            Processor::process(&program_id, &account_infos(&mut accounts), &[14]),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+
+    #[test]
+    fn claim_vested_rejects_token_program_other_than_stored() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = claim_vested_accounts(&program_id, -50, 50, 0);
+        // SPL Token으로 연 에스크로에 Token-2022를 넘김
+        accounts[5].key = spl_token_2022::id();
+        let escrow_data = accounts[4].data.clone();
+
+        assert_eq!(
+            Processor::process(&program_id, &account_infos(&mut accounts), &[14]),
+            Err(ProgramError::from(EscrowError::InvalidTokenProgram))
+        );
+        assert_eq!(accounts[4].data, escrow_data);
+    }
+
+    #[test]
+    fn claim_vested_rejects_non_vesting_escrow() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = claim_vested_accounts(&program_id, -50, 50, 0);
+        let mut escrow_info = Escrow::unpack(&accounts[4].data).unwrap();
+        escrow_info.vesting = false;
+        Escrow::pack(escrow_info, &mut accounts[4].data).unwrap();
+
+        assert_eq!(
+            Processor::process(&program_id, &account_infos(&mut accounts), &[14]),
+            Err(ProgramError::from(EscrowError::InvalidInstruction))
+        );
+    }
+
+    #[test]
+    fn exchange_of_vesting_escrow_records_total_and_requires_full_fill() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = exchange_accounts(&program_id, 1_000, 300);
+        let mut escrow_info = Escrow::unpack(&accounts[6].data).unwrap();
+        escrow_info.vesting = true;
+        escrow_info.expiry_ts = 100;
+        Escrow::pack(escrow_info, &mut accounts[6].data).unwrap();
+
+        // 나누어 채울 수 없음
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &exchange_data(100),
+            ),
+            Err(ProgramError::from(EscrowError::ExpectedAmountMismatch))
+        );
+
+        Processor::process(
+            &program_id,
+            &account_infos(&mut accounts),
+            &exchange_data(300),
+        )
+        .unwrap();
+        let escrow_info = Escrow::unpack(&accounts[6].data).unwrap();
+        assert_eq!(escrow_info.status, EscrowStatus::Completed);
+        assert_eq!(escrow_info.vesting_total, 1_000);
+        assert_eq!(escrow_info.claimed_amount, 0);
+        assert_eq!(escrow_info.last_taker, accounts[0].key);
+    }
+
+    #[test]
+    fn init_vesting_escrow_requires_future_expiry() {
+        let program_id = Pubkey::new_unique();
+
+        // 9..17바이트의 만료 시각을 바꾸고 메모, 취소 가능 시각, 베스팅 플래그를 붙임
+        let vesting_data = |expiry_ts: i64| {
+            let mut data = init_escrow_data(10);
+            data[9..17].copy_from_slice(&expiry_ts.to_le_bytes());
+            data.extend_from_slice(&[0; 32]);
+            data.extend_from_slice(&0i64.to_le_bytes());
+            data.push(1);
+            data
+        };
+
+        let mut accounts = InitAccounts::new(&program_id);
+        assert_eq!(
+            Processor::process(&program_id, &accounts.infos(), &vesting_data(0)),
+            Err(ProgramError::from(EscrowError::InvalidExpiry))
+        );
+
+        Processor::process(&program_id, &accounts.infos(), &vesting_data(100)).unwrap();
+        let escrow_info = Escrow::unpack(&accounts.escrow.data).unwrap();
+        assert!(escrow_info.vesting);
+        assert_eq!(escrow_info.expiry_ts, 100);
+    }
+
+    #[test]
+    fn exchange_rejects_mismatched_decimals() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = exchange_accounts(&program_id, 1_000, 300);
+        let mut escrow_info = Escrow::unpack(&accounts[6].data).unwrap();
+        escrow_info.expected_decimals = 6;
+        Escrow::pack(escrow_info, &mut accounts[6].data).unwrap();
+
+        // 테이커가 자릿수를 9로 잘못 알고 금액을 계산함
+        let mut data = exchange_data(300);
+        data.extend_from_slice(&[0, 9]);
+        assert_eq!(
+            Processor::process(&program_id, &account_infos(&mut accounts), &data),
+            Err(ProgramError::from(EscrowError::DecimalsMismatch))
+        );
+
+        // 자릿수가 맞으면 교환됨
+        let mut data = exchange_data(300);
+        data.extend_from_slice(&[0, 6]);
+        Processor::process(&program_id, &account_infos(&mut accounts), &data).unwrap();
+
+        let escrow_info = Escrow::unpack(&accounts[6].data).unwrap();
+        assert_eq!(escrow_info.status, EscrowStatus::Completed);
+    }
+
+    #[test]
+    fn exchange_rejects_self_trade() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = exchange_accounts(&program_id, 1_000, 300);
+        // 이니셜라이저가 테이커 자리에 서명함
+        accounts[0].key = accounts[4].key;
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &exchange_data(300),
+            ),
+            Err(ProgramError::from(EscrowError::SelfTradeNotAllowed))
+        );
+    }
+
+    #[test]
+    fn exchange_rejects_amount_above_remaining() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = exchange_accounts(&program_id, 1_000, 300);
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &exchange_data(301),
+            ),
+            Err(ProgramError::from(EscrowError::ExpectedAmountMismatch))
+        );
+    }
+
+    #[test]
+    fn exchange_rejects_read_only_escrow_account() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = exchange_accounts(&program_id, 100, 100);
+        accounts[6].is_writable = false;
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &exchange_data(100),
+            ),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn exchange_rejects_fake_token_program() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = exchange_accounts(&program_id, 100, 100);
+        accounts[7].key = Pubkey::new_unique();
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &exchange_data(100),
+            ),
+            Err(ProgramError::from(EscrowError::InvalidTokenProgram))
+        );
+    }
+
+    #[test]
+    fn exchange_rejects_token_program_other_than_stored() {
+        let program_id = Pubkey::new_unique();
+        // SPL Token으로 연 에스크로에 Token-2022를 넘김
+        let mut accounts = exchange_accounts(&program_id, 100, 100);
+        assert_eq!(
+            Escrow::unpack(&accounts[6].data).unwrap().token_program_id,
+            spl_token::id()
+        );
+        accounts[7].key = spl_token_2022::id();
+        let escrow_data = accounts[6].data.clone();
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &exchange_data(100),
+            ),
+            Err(ProgramError::from(EscrowError::InvalidTokenProgram))
+        );
+        assert_eq!(accounts[6].data, escrow_data);
+    }
+
+    #[test]
+    fn exchange_of_token_2022_escrow_requires_matching_mints() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = exchange_accounts(&program_id, 100, 100);
+        let mut escrow_info = Escrow::unpack(&accounts[6].data).unwrap();
+        escrow_info.token_program_id = spl_token_2022::id();
+        Escrow::pack(escrow_info, &mut accounts[6].data).unwrap();
+        accounts[7].key = spl_token_2022::id();
+        let escrow_data = accounts[6].data.clone();
+
+        // 토큰 Y의 민트를 넘기지 않으면 transfer_checked를 만들 수 없으므로 에러 반환
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &exchange_data(100),
+            ),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+
+        // 에스크로의 토큰 Y가 아닌 민트를 넘기면 에러 반환
+        accounts.push(TestAccount::new(spl_token_2022::id(), mint_data(6)));
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &exchange_data(100),
+            ),
+            Err(ProgramError::from(EscrowError::TokenMintMismatch))
+        );
+        assert_eq!(accounts[6].data, escrow_data);
+    }
+
+    #[test]
+    fn exchange_rejects_expired_escrow() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = exchange_accounts(&program_id, 100, 100);
+
+        // 스텁의 Clock(0)보다 앞선 만료 시각이므로 이미 만료된 에스크로
+        let mut escrow_info = Escrow::unpack(&accounts[6].data).unwrap();
+        escrow_info.expiry_ts = -1;
+        Escrow::pack(escrow_info, &mut accounts[6].data).unwrap();
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &exchange_data(100),
+            ),
+            Err(ProgramError::from(EscrowError::EscrowExpired))
+        );
+    }
+
+    #[test]
+    fn exchange_rejects_stale_escrow() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = exchange_accounts(&program_id, 100, 100);
+
+        // 스텁의 Clock(0) 기준으로 MAX_ESCROW_AGE_SECS보다 1초 더 지난 에스크로
+        let mut escrow_info = Escrow::unpack(&accounts[6].data).unwrap();
+        escrow_info.created_at = -MAX_ESCROW_AGE_SECS - 1;
+        Escrow::pack(escrow_info, &mut accounts[6].data).unwrap();
+        let escrow_data = accounts[6].data.clone();
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &exchange_data(100),
+            ),
+            Err(ProgramError::from(EscrowError::EscrowStale))
+        );
+        assert_eq!(accounts[6].data, escrow_data);
+    }
+
+    #[test]
+    fn exchange_accepts_escrow_just_under_max_age() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = exchange_accounts(&program_id, 100, 100);
+
+        // 정확히 MAX_ESCROW_AGE_SECS만큼 지난 에스크로는 아직 교환 가능
+        let mut escrow_info = Escrow::unpack(&accounts[6].data).unwrap();
+        escrow_info.created_at = -MAX_ESCROW_AGE_SECS;
+        Escrow::pack(escrow_info, &mut accounts[6].data).unwrap();
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &exchange_data(100),
+            ),
+            Ok(())
+        );
+        let escrow_info = Escrow::unpack(&accounts[6].data).unwrap();
+        assert_eq!(escrow_info.remaining_amount, 0);
+    }
+
+    #[test]
+    fn exchange_rejects_wrong_pda_account() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = exchange_accounts(&program_id, 100, 100);
+        accounts[8].key = Pubkey::new_unique();
+        let escrow_data = accounts[6].data.clone();
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &exchange_data(100),
+            ),
+            Err(ProgramError::from(EscrowError::InvalidPda))
+        );
+
+        // 상태를 바꾸기 전에 거절되므로 남은 수량과 상태는 그대로
+        assert_eq!(accounts[6].data, escrow_data);
+    }
+
+    fn init_sol_escrow_data(amount: u64, expected_amount: u64) -> Vec<u8> {
+        let mut data = vec![5];
+        data.extend_from_slice(&amount_to_bytes(amount));
+        data.extend_from_slice(&amount_to_bytes(expected_amount));
+        data
+    }
+
+    // InitSolEscrow에 필요한 정상적인 어카운트들 (EscrowInstruction::InitSolEscrow의 순서)
+    fn init_sol_escrow_accounts(program_id: &Pubkey) -> Vec<TestAccount> {
+        set_test_syscall_stubs();
+
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        initializer.lamports = 5_000_000;
+        let receive = TestAccount::new(
+            spl_token::id(),
+            token_account_data(&Pubkey::new_unique(), &initializer.key),
+        );
+        let mut escrow = TestAccount::new(*program_id, vec![0; Escrow::LEN]);
+        escrow.lamports = Rent::default().minimum_balance(Escrow::LEN);
+        let mut token_program = TestAccount::new(Pubkey::default(), vec![]);
+        token_program.key = spl_token::id();
+        let mut system_program_account = TestAccount::new(Pubkey::default(), vec![]);
+        system_program_account.key = system_program::id();
+
+        vec![
+            initializer,
+            receive,
+            escrow,
+            token_program,
+            system_program_account,
+        ]
+    }
+
+    #[test]
+    fn init_sol_escrow_accepts_amount_at_cap() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = init_sol_escrow_accounts(&program_id);
+
+        Processor::process(
+            &program_id,
+            &account_infos(&mut accounts),
+            &init_sol_escrow_data(1_000_000, MAX_ESCROW_AMOUNT),
+        )
+        .unwrap();
+
+        let escrow_info = Escrow::unpack(&accounts[2].data).unwrap();
+        assert_eq!(escrow_info.expected_amount, MAX_ESCROW_AMOUNT);
+    }
+
+    #[test]
+    #[cfg(feature = "amount-cap")]
+    fn init_sol_escrow_rejects_amount_above_cap() {
+        let program_id = Pubkey::new_unique();
+        let mut accounts = init_sol_escrow_accounts(&program_id);
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &init_sol_escrow_data(1_000_000, MAX_ESCROW_AMOUNT + 1),
+            ),
+            Err(ProgramError::from(EscrowError::AmountTooLarge))
+        );
+        assert_eq!(accounts[2].data, vec![0; Escrow::LEN]);
+    }
+
+    #[test]
+    fn sol_escrow_swaps_lamports_for_tokens() {
+        set_test_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let rent_lamports = Rent::default().minimum_balance(Escrow::LEN);
+        let y_mint = Pubkey::new_unique();
+
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        initializer.lamports = 5_000_000;
+        let mut receive = TestAccount::new(
+            spl_token::id(),
+            token_account_data(&y_mint, &initializer.key),
+        );
+        let mut escrow = TestAccount::new(program_id, vec![0; Escrow::LEN]);
+        escrow.lamports = rent_lamports;
+        let mut token_program = TestAccount::new(Pubkey::default(), vec![]);
+        token_program.key = spl_token::id();
+        let mut system_program_account = TestAccount::new(Pubkey::default(), vec![]);
+        system_program_account.key = system_program::id();
+
+        // 1. 이니셜라이저가 1_000_000 램포트를 맡기고 토큰 Y 300개를 기다림
+        Processor::process(
+            &program_id,
+            &[
+                initializer.info(),
+                receive.info(),
+                escrow.info(),
+                token_program.info(),
+                system_program_account.info(),
+            ],
+            &init_sol_escrow_data(1_000_000, 300),
+        )
+        .unwrap();
+
+        let escrow_info = Escrow::unpack(&escrow.data).unwrap();
+        assert!(escrow_info.is_native);
+        assert_eq!(escrow_info.x_token_account_pubkey, escrow.key);
+        assert_eq!(escrow_info.expected_mint, y_mint);
+
+        // 시스템 프로그램 CPI는 스텁이므로 램포트 전송을 직접 반영
+        initializer.lamports -= 1_000_000;
+        escrow.lamports += 1_000_000;
+
+        // 2. 테이커가 토큰 Y 300개를 보내고 램포트를 받음
+        let mut taker = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut taker_send =
+            TestAccount::new(spl_token::id(), token_account_data(&y_mint, &taker.key));
+        let mut taker_wallet = TestAccount::new(Pubkey::default(), vec![]);
+        let mut pda_account = TestAccount::new(Pubkey::default(), vec![]);
+        let mut fee = TestAccount::new(spl_token::id(), token_account_data(&y_mint, &taker.key));
+
+        // 램포트를 보관한 에스크로 계정이 임시 계정 자리에도 들어감
+        let escrow_account = escrow.info();
+        Processor::process(
+            &program_id,
+            &[
+                taker.info(),
+                taker_send.info(),
+                taker_wallet.info(),
+                escrow_account.clone(),
+                initializer.info(),
+                receive.info(),
+                escrow_account,
+                token_program.info(),
+                pda_account.info(),
+                fee.info(),
+            ],
+            &exchange_data(300),
+        )
+        .unwrap();
+
+        assert_eq!(taker_wallet.lamports, 1_000_000);
+        assert_eq!(escrow.lamports, rent_lamports);
+        let escrow_info = Escrow::unpack(&escrow.data).unwrap();
+        assert_eq!(escrow_info.remaining_amount, 0);
+        assert_eq!(escrow_info.status, EscrowStatus::Completed);
+    }
+
+    #[test]
+    fn cancel_rejects_closed_escrow() {
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut temp = TestAccount::new(spl_token::id(), vec![0; TokenAccount::LEN]);
+        // 취소되어 0으로 채워진 에스크로 계정
+        let mut escrow = TestAccount::new(program_id, vec![0; Escrow::LEN]);
+
+        let accounts = [initializer.info(), temp.info(), escrow.info()];
+        assert_eq!(
+            Processor::process(&program_id, &accounts, &[2]),
+            Err(ProgramError::UninitializedAccount)
+        );
+    }
+
+    #[test]
+    fn cancel_rejects_completed_escrow() {
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut temp = TestAccount::new(spl_token::id(), vec![0; TokenAccount::LEN]);
+
+        // 교환이 완료된 에스크로 계정
+        let mut escrow_info = Escrow::default();
+        escrow_info.status = EscrowStatus::Completed;
+        escrow_info.initializer_pubkey = initializer.key;
+        escrow_info.x_token_account_pubkey = temp.key;
+        let mut escrow = TestAccount::new(program_id, vec![0; Escrow::LEN]);
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+
+        let accounts = [initializer.info(), temp.info(), escrow.info()];
+        assert_eq!(
+            Processor::process(&program_id, &accounts, &[2]),
+            Err(ProgramError::from(EscrowError::EscrowNotActive))
+        );
+    }
+    #[test]
+    fn cancel_marks_escrow_cancelled_without_closing() {
+        set_test_syscall_stubs();
+
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut temp = TestAccount::new(spl_token::id(), vec![0; TokenAccount::LEN]);
+        let mut token_program = TestAccount::new(Pubkey::default(), vec![]);
+        token_program.key = spl_token::id();
+
+        let (pda, bump_seed) = escrow_pda(&program_id, &initializer.key, 0);
+        let mut pda_account = TestAccount::new(Pubkey::default(), vec![]);
+        pda_account.key = pda;
+
+        let escrow_info = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: initializer.key,
+            x_token_account_pubkey: temp.key,
+            expected_amount: 10,
+            bump_seed,
+            pda_seed_pubkey: initializer.key,
+            token_program_id: spl_token::id(),
+            ..Escrow::default()
+        };
+        let mut escrow = TestAccount::new(program_id, vec![0; Escrow::LEN]);
+        escrow.lamports = Rent::default().minimum_balance(Escrow::LEN);
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+        let escrow_lamports = escrow.lamports;
+
+        Processor::process(
+            &program_id,
+            &[
+                initializer.info(),
+                temp.info(),
+                escrow.info(),
+                token_program.info(),
+                pda_account.info(),
+            ],
+            &[2],
+        )
+        .unwrap();
+
+        // 계정은 닫히지 않고 취소 상태로 남음
+        assert_eq!(escrow.lamports, escrow_lamports);
+        let escrow_info = Escrow::unpack(&escrow.data).unwrap();
+        assert_eq!(escrow_info.status, EscrowStatus::Cancelled);
+    }
+
+    #[test]
+    fn cancel_vault_escrow_returns_tokens() {
+        set_test_syscall_stubs();
+
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let x_mint = Pubkey::new_unique();
+        let (pda, bump_seed) = escrow_pda(&program_id, &initializer.key, 0);
+        let mut vault = TestAccount::new(spl_token::id(), token_account_data(&x_mint, &pda));
+        let mut refund = TestAccount::new(
+            spl_token::id(),
+            token_account_data(&x_mint, &initializer.key),
+        );
+        let mut token_program = TestAccount::new(Pubkey::default(), vec![]);
+        token_program.key = spl_token::id();
+        let mut pda_account = TestAccount::new(Pubkey::default(), vec![]);
+        pda_account.key = pda;
+
+        let escrow_info = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: initializer.key,
+            x_token_account_pubkey: vault.key,
+            expected_amount: 10,
+            bump_seed,
+            pda_seed_pubkey: initializer.key,
+            vault_custody: true,
+            token_program_id: spl_token::id(),
+            ..Escrow::default()
+        };
+        let mut escrow = TestAccount::new(program_id, vec![0; Escrow::LEN]);
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+
+        // 토큰을 돌려받을 계정이 없으면 에러
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &[
+                    initializer.info(),
+                    vault.info(),
+                    escrow.info(),
+                    token_program.info(),
+                    pda_account.info(),
+                ],
+                &[2],
+            ),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+
+        Processor::process(
+            &program_id,
+            &[
+                initializer.info(),
+                vault.info(),
+                escrow.info(),
+                token_program.info(),
+                pda_account.info(),
+                refund.info(),
+            ],
+            &[2],
+        )
+        .unwrap();
+
+        let escrow_info = Escrow::unpack(&escrow.data).unwrap();
+        assert_eq!(escrow_info.status, EscrowStatus::Cancelled);
+    }
+
+    #[test]
+    fn cancel_allows_expired_escrow() {
+        set_test_syscall_stubs();
+
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut temp = TestAccount::new(spl_token::id(), vec![0; TokenAccount::LEN]);
+        let mut token_program = TestAccount::new(Pubkey::default(), vec![]);
+        token_program.key = spl_token::id();
+
+        let (pda, bump_seed) = escrow_pda(&program_id, &initializer.key, 0);
+        let mut pda_account = TestAccount::new(Pubkey::default(), vec![]);
+        pda_account.key = pda;
+
+        // 스텁의 Clock(0)보다 앞선 만료 시각이므로 이미 만료된 에스크로
+        let escrow_info = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: initializer.key,
+            x_token_account_pubkey: temp.key,
+            expected_amount: 10,
+            bump_seed,
+            pda_seed_pubkey: initializer.key,
+            expiry_ts: -1,
+            token_program_id: spl_token::id(),
+            ..Escrow::default()
+        };
+        assert!(escrow_info.is_expired(0));
+        let mut escrow = TestAccount::new(program_id, vec![0; Escrow::LEN]);
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+
+        Processor::process(
+            &program_id,
+            &[
+                initializer.info(),
+                temp.info(),
+                escrow.info(),
+                token_program.info(),
+                pda_account.info(),
+            ],
+            &[2],
+        )
+        .unwrap();
+
+        let escrow_info = Escrow::unpack(&escrow.data).unwrap();
+        assert_eq!(escrow_info.status, EscrowStatus::Cancelled);
+    }
+
+    #[test]
+    fn cancel_rejects_before_cancel_after_ts() {
+        set_test_syscall_stubs();
+
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut temp = TestAccount::new(spl_token::id(), vec![0; TokenAccount::LEN]);
+
+        // 테스트 스텁의 Clock은 unix_timestamp가 0이므로 1부터 취소할 수 있는 에스크로는 아직 취소 불가
+        let escrow_info = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: initializer.key,
+            x_token_account_pubkey: temp.key,
+            expected_amount: 10,
+            cancel_after_ts: 1,
+            ..Escrow::default()
+        };
+        let mut escrow = TestAccount::new(program_id, vec![0; Escrow::LEN]);
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+
+        let accounts = [initializer.info(), temp.info(), escrow.info()];
+        assert_eq!(
+            Processor::process(&program_id, &accounts, &[2]),
+            Err(ProgramError::from(EscrowError::CancelTooEarly))
+        );
+    }
+
+    #[test]
+    fn cancel_rejects_wrong_pda_account() {
+        set_test_syscall_stubs();
+
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut temp = TestAccount::new(spl_token::id(), vec![0; TokenAccount::LEN]);
+        let mut token_program = TestAccount::new(Pubkey::default(), vec![]);
+        token_program.key = spl_token::id();
+        // 에스크로의 PDA가 아닌 임의의 계정
+        let mut pda_account = TestAccount::new(Pubkey::default(), vec![]);
+
+        let (_, bump_seed) = escrow_pda(&program_id, &initializer.key, 0);
+        let escrow_info = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: initializer.key,
+            x_token_account_pubkey: temp.key,
+            expected_amount: 10,
+            bump_seed,
+            pda_seed_pubkey: initializer.key,
+            token_program_id: spl_token::id(),
+            ..Escrow::default()
+        };
+        let mut escrow = TestAccount::new(program_id, vec![0; Escrow::LEN]);
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &[
+                    initializer.info(),
+                    temp.info(),
+                    escrow.info(),
+                    token_program.info(),
+                    pda_account.info(),
+                ],
+                &[2],
+            ),
+            Err(ProgramError::from(EscrowError::InvalidPda))
+        );
+    }
+
+    #[test]
+    fn cancel_rejects_token_program_other_than_stored() {
+        set_test_syscall_stubs();
+
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut temp = TestAccount::new(spl_token::id(), vec![0; TokenAccount::LEN]);
+        // SPL Token으로 연 에스크로에 Token-2022를 넘김
+        let mut token_program = TestAccount::new(Pubkey::default(), vec![]);
+        token_program.key = spl_token_2022::id();
+
+        let (pda, bump_seed) = escrow_pda(&program_id, &initializer.key, 0);
+        let mut pda_account = TestAccount::new(Pubkey::default(), vec![]);
+        pda_account.key = pda;
+
+        let escrow_info = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: initializer.key,
+            x_token_account_pubkey: temp.key,
+            expected_amount: 10,
+            bump_seed,
+            pda_seed_pubkey: initializer.key,
+            token_program_id: spl_token::id(),
+            ..Escrow::default()
+        };
+        let mut escrow = TestAccount::new(program_id, vec![0; Escrow::LEN]);
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+        let escrow_data = escrow.data.clone();
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &[
+                    initializer.info(),
+                    temp.info(),
+                    escrow.info(),
+                    token_program.info(),
+                    pda_account.info(),
+                ],
+                &[2],
+            ),
+            Err(ProgramError::from(EscrowError::InvalidTokenProgram))
+        );
+        assert_eq!(escrow.data, escrow_data);
+    }
+
+    fn transfer_initializer_data(new_initializer: &Pubkey) -> Vec<u8> {
+        let mut data = vec![8];
+        data.extend_from_slice(new_initializer.as_ref());
+        data
+    }
+
+    #[test]
+    fn transferred_escrow_is_cancelled_by_new_initializer_only() {
+        set_test_syscall_stubs();
+
+        let program_id = Pubkey::new_unique();
+        let mut old_initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut new_initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut temp = TestAccount::new(spl_token::id(), vec![0; TokenAccount::LEN]);
+        let mut token_program = TestAccount::new(Pubkey::default(), vec![]);
+        token_program.key = spl_token::id();
+
+        let (pda, bump_seed) = escrow_pda(&program_id, &old_initializer.key, 0);
+        let mut pda_account = TestAccount::new(Pubkey::default(), vec![]);
+        pda_account.key = pda;
+
+        let y_mint = Pubkey::new_unique();
+        let escrow_info = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: old_initializer.key,
+            x_token_account_pubkey: temp.key,
+            expected_amount: 10,
+            expected_mint: y_mint,
+            bump_seed,
+            pda_seed_pubkey: old_initializer.key,
+            token_program_id: spl_token::id(),
+            ..Escrow::default()
+        };
+        let mut escrow = TestAccount::new(program_id, vec![0; Escrow::LEN]);
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+
+        // 1. 이니셜라이저 권리와 받을 계정을 새 이니셜라이저에게 넘김
+        let mut new_receive = TestAccount::new(
+            spl_token::id(),
+            token_account_data(&y_mint, &new_initializer.key),
+        );
+        Processor::process(
+            &program_id,
+            &[old_initializer.info(), escrow.info(), new_receive.info()],
+            &transfer_initializer_data(&new_initializer.key),
+        )
+        .unwrap();
+
+        let escrow_info = Escrow::unpack(&escrow.data).unwrap();
+        assert_eq!(escrow_info.initializer_pubkey, new_initializer.key);
+        assert_eq!(
+            escrow_info.initializer_token_to_receive_account_pubkey,
+            new_receive.key
+        );
+        // PDA 시드는 바뀌지 않음
+        assert_eq!(escrow_info.pda_seed_pubkey, old_initializer.key);
+
+        // 2. 예전 이니셜라이저는 더 이상 취소할 수 없음
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &[
+                    old_initializer.info(),
+                    temp.info(),
+                    escrow.info(),
+                    token_program.info(),
+                    pda_account.info(),
+                ],
+                &[2],
+            ),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+
+        // 3. 새 이니셜라이저는 원래 PDA로 임시 토큰 계정을 돌려받으며 취소할 수 있음
+        Processor::process(
+            &program_id,
+            &[
+                new_initializer.info(),
+                temp.info(),
+                escrow.info(),
+                token_program.info(),
+                pda_account.info(),
+            ],
+            &[2],
+        )
+        .unwrap();
+
+        let escrow_info = Escrow::unpack(&escrow.data).unwrap();
+        assert_eq!(escrow_info.status, EscrowStatus::Cancelled);
+    }
+
+    #[test]
+    fn transfer_initializer_rejects_non_initializer() {
+        let program_id = Pubkey::new_unique();
+        let initializer = Pubkey::new_unique();
+        let mut stranger = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut escrow = active_escrow_account(&program_id, &initializer);
+        let data = transfer_initializer_data(&stranger.key);
+
+        assert_eq!(
+            Processor::process(&program_id, &[stranger.info(), escrow.info()], &data),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        let escrow_info = Escrow::unpack(&escrow.data).unwrap();
+        assert_eq!(escrow_info.initializer_pubkey, initializer);
+    }
+
+    // 거래 대기 중인 에스크로 계정
+    fn active_escrow_account(program_id: &Pubkey, initializer: &Pubkey) -> TestAccount {
+        let escrow_info = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: *initializer,
+            expected_amount: 10,
+            ..Escrow::default()
+        };
+        let mut escrow = TestAccount::new(*program_id, vec![0; Escrow::LEN]);
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+        escrow
+    }
+
+    #[test]
+    fn transfer_initializer_rejects_delegate_custody() {
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut escrow = active_escrow_account(&program_id, &initializer.key);
+        let mut escrow_info = Escrow::unpack(&escrow.data).unwrap();
+        escrow_info.delegate_custody = true;
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+        let data = transfer_initializer_data(&Pubkey::new_unique());
+
+        assert_eq!(
+            Processor::process(&program_id, &[initializer.info(), escrow.info()], &data),
+            Err(ProgramError::from(EscrowError::InvalidAuthority))
+        );
+    }
+
+    fn update_amount_data(new_amount: u64) -> Vec<u8> {
+        let mut data = vec![3];
+        data.extend_from_slice(&amount_to_bytes(new_amount));
+        data
+    }
+
+    #[test]
+    fn update_amount_changes_expected_amount() {
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut escrow = active_escrow_account(&program_id, &initializer.key);
+
+        Processor::process(
+            &program_id,
+            &[initializer.info(), escrow.info()],
+            &update_amount_data(25),
+        )
+        .unwrap();
+
+        let escrow_info = Escrow::unpack(&escrow.data).unwrap();
+        assert_eq!(escrow_info.expected_amount, 25);
+    }
+
+    #[test]
+    fn update_amount_rejects_wrong_signer() {
+        let program_id = Pubkey::new_unique();
+        let mut stranger = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut escrow = active_escrow_account(&program_id, &Pubkey::new_unique());
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &[stranger.info(), escrow.info()],
+                &update_amount_data(25),
+            ),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        assert_eq!(Escrow::unpack(&escrow.data).unwrap().expected_amount, 10);
+    }
+
+    #[test]
+    fn update_amount_accepts_amount_at_cap() {
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut escrow = active_escrow_account(&program_id, &initializer.key);
+
+        Processor::process(
+            &program_id,
+            &[initializer.info(), escrow.info()],
+            &update_amount_data(MAX_ESCROW_AMOUNT),
+        )
+        .unwrap();
+
+        let escrow_info = Escrow::unpack(&escrow.data).unwrap();
+        assert_eq!(escrow_info.expected_amount, MAX_ESCROW_AMOUNT);
+    }
+
+    #[test]
+    #[cfg(feature = "amount-cap")]
+    fn update_amount_rejects_amount_above_cap() {
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut escrow = active_escrow_account(&program_id, &initializer.key);
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &[initializer.info(), escrow.info()],
+                &update_amount_data(MAX_ESCROW_AMOUNT + 1),
+            ),
+            Err(ProgramError::from(EscrowError::AmountTooLarge))
+        );
+        assert_eq!(Escrow::unpack(&escrow.data).unwrap().expected_amount, 10);
+    }
+
+    fn reprovision_data(new_amount: Option<u64>, new_expiry: Option<i64>) -> Vec<u8> {
+        let mut data = vec![12];
+        data.extend_from_slice(&borsh::to_vec(&(new_amount, new_expiry)).unwrap());
+        data
+    }
+
+    #[test]
+    fn reprovision_updates_only_given_values() {
+        set_test_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+
+        // (새 금액, 새 만료 시각) -> (예상 금액, 만료 시각)
+        // 기존 값은 금액 10, 만료 시각 0(만료 없음), 스텁의 Clock은 0
+        for (new_amount, new_expiry, expected_amount, expiry_ts) in [
+            (None, None, 10, 0),
+            (Some(25), None, 25, 0),
+            (None, Some(1_000), 10, 1_000),
+            (Some(25), Some(1_000), 25, 1_000),
+        ] {
+            let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+            let mut escrow = active_escrow_account(&program_id, &initializer.key);
+
+            Processor::process(
+                &program_id,
+                &[initializer.info(), escrow.info()],
+                &reprovision_data(new_amount, new_expiry),
+            )
+            .unwrap();
+
+            let escrow_info = Escrow::unpack(&escrow.data).unwrap();
+            assert_eq!(escrow_info.expected_amount, expected_amount);
+            assert_eq!(escrow_info.remaining_amount, expected_amount);
+            assert_eq!(escrow_info.expiry_ts, expiry_ts);
+        }
+    }
+
+    #[test]
+    fn reprovision_rejects_zero_amount_and_past_expiry_without_changes() {
+        set_test_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut escrow = active_escrow_account(&program_id, &initializer.key);
+        let escrow_data = escrow.data.clone();
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &[initializer.info(), escrow.info()],
+                &reprovision_data(Some(0), Some(1_000)),
+            ),
+            Err(ProgramError::from(EscrowError::InvalidInstruction))
+        );
+
+        // 스텁의 Clock(0)보다 미래가 아닌 만료 시각
+        // 금액이 맞아도 만료 시각이 틀리면 어느 값도 바뀌지 않음
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &[initializer.info(), escrow.info()],
+                &reprovision_data(Some(25), Some(0)),
+            ),
+            Err(ProgramError::from(EscrowError::InvalidExpiry))
+        );
+        assert_eq!(escrow.data, escrow_data);
+    }
+
+    #[test]
+    fn reprovision_accepts_amount_at_cap() {
+        set_test_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut escrow = active_escrow_account(&program_id, &initializer.key);
+
+        Processor::process(
+            &program_id,
+            &[initializer.info(), escrow.info()],
+            &reprovision_data(Some(MAX_ESCROW_AMOUNT), None),
+        )
+        .unwrap();
+
+        let escrow_info = Escrow::unpack(&escrow.data).unwrap();
+        assert_eq!(escrow_info.expected_amount, MAX_ESCROW_AMOUNT);
+        assert_eq!(escrow_info.remaining_amount, MAX_ESCROW_AMOUNT);
+    }
+
+    #[test]
+    #[cfg(feature = "amount-cap")]
+    fn reprovision_rejects_amount_above_cap_without_changes() {
+        set_test_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut escrow = active_escrow_account(&program_id, &initializer.key);
+        let escrow_data = escrow.data.clone();
+
+        // 만료 시각이 맞아도 금액이 상한을 넘으면 어느 값도 바뀌지 않음
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &[initializer.info(), escrow.info()],
+                &reprovision_data(Some(MAX_ESCROW_AMOUNT + 1), Some(1_000)),
+            ),
+            Err(ProgramError::from(EscrowError::AmountTooLarge))
+        );
+        assert_eq!(escrow.data, escrow_data);
+    }
+
+    #[test]
+    fn reprovision_rejects_wrong_signer_and_inactive_escrow() {
+        set_test_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let mut stranger = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut escrow = active_escrow_account(&program_id, &Pubkey::new_unique());
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &[stranger.info(), escrow.info()],
+                &reprovision_data(Some(25), None),
+            ),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut escrow = active_escrow_account(&program_id, &initializer.key);
+        let mut escrow_info = Escrow::unpack(&escrow.data).unwrap();
+        escrow_info.status = EscrowStatus::Completed;
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &[initializer.info(), escrow.info()],
+                &reprovision_data(Some(25), None),
+            ),
+            Err(ProgramError::from(EscrowError::EscrowNotActive))
+        );
+    }
+
+    #[test]
+    fn close_refunds_rent_to_initializer() {
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        initializer.lamports = 1_000;
+
+        let escrow_info = Escrow {
+            status: EscrowStatus::Completed,
+            initializer_pubkey: initializer.key,
+            expected_amount: 10,
+            ..Escrow::default()
+        };
+        let mut escrow = TestAccount::new(program_id, vec![0; Escrow::LEN]);
+        escrow.lamports = Rent::default().minimum_balance(Escrow::LEN);
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+        let escrow_lamports = escrow.lamports;
+
+        Processor::process(&program_id, &[initializer.info(), escrow.info()], &[4]).unwrap();
+
+        assert_eq!(initializer.lamports, 1_000 + escrow_lamports);
+        assert_eq!(escrow.lamports, 0);
+        assert!(!Escrow::unpack_unchecked(&escrow.data)
+            .unwrap()
+            .is_initialized());
+    }
+
+    #[test]
+    fn close_refunds_rent_to_given_destination() {
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        initializer.lamports = 1_000;
+        // 계정 생성비를 대신 낸 릴레이어
+        let mut relayer = TestAccount::new(Pubkey::default(), vec![]);
+        relayer.lamports = 500;
+
+        let escrow_info = Escrow {
+            status: EscrowStatus::Completed,
+            initializer_pubkey: initializer.key,
+            expected_amount: 10,
+            ..Escrow::default()
+        };
+        let mut escrow = TestAccount::new(program_id, vec![0; Escrow::LEN]);
+        escrow.lamports = Rent::default().minimum_balance(Escrow::LEN);
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+        let escrow_lamports = escrow.lamports;
+
+        Processor::process(
+            &program_id,
+            &[initializer.info(), escrow.info(), relayer.info()],
+            &[4],
+        )
+        .unwrap();
+
+        assert_eq!(relayer.lamports, 500 + escrow_lamports);
+        assert_eq!(initializer.lamports, 1_000);
+        assert_eq!(escrow.lamports, 0);
+    }
+
+    #[test]
+    fn close_rejects_readonly_destination() {
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        let mut relayer = TestAccount::new(Pubkey::default(), vec![]);
+        relayer.is_writable = false;
+
+        let escrow_info = Escrow {
+            status: EscrowStatus::Cancelled,
+            initializer_pubkey: initializer.key,
+            expected_amount: 10,
+            ..Escrow::default()
+        };
+        let mut escrow = TestAccount::new(program_id, vec![0; Escrow::LEN]);
+        escrow.lamports = Rent::default().minimum_balance(Escrow::LEN);
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+        let escrow_lamports = escrow.lamports;
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &[initializer.info(), escrow.info(), relayer.info()],
+                &[4],
+            ),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(escrow.lamports, escrow_lamports);
+    }
+
+    #[test]
+    fn close_rejects_vesting_escrow_until_fully_claimed() {
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+
+        let escrow_info = Escrow {
+            status: EscrowStatus::Completed,
+            initializer_pubkey: initializer.key,
+            expected_amount: 10,
+            vesting: true,
+            vesting_total: 1_000,
+            claimed_amount: 999,
+            ..Escrow::default()
+        };
+        let mut escrow = TestAccount::new(program_id, vec![0; Escrow::LEN]);
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+
+        assert_eq!(
+            Processor::process(&program_id, &[initializer.info(), escrow.info()], &[4]),
+            Err(ProgramError::from(EscrowError::VestingNotClaimed))
+        );
+
+        // 모두 찾아간 뒤에는 닫을 수 있음
+        let escrow_info = Escrow {
+            claimed_amount: 1_000,
+            ..escrow_info
+        };
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+        Processor::process(&program_id, &[initializer.info(), escrow.info()], &[4]).unwrap();
+    }
+
+    #[test]
+    fn close_rejects_lamport_overflow() {
+        let program_id = Pubkey::new_unique();
+        let mut initializer = TestAccount::new(Pubkey::default(), vec![]).signer();
+        initializer.lamports = u64::MAX;
+
+        let escrow_info = Escrow {
+            status: EscrowStatus::Cancelled,
+            initializer_pubkey: initializer.key,
+            expected_amount: 10,
+            ..Escrow::default()
+        };
+        let mut escrow = TestAccount::new(program_id, vec![0; Escrow::LEN]);
+        escrow.lamports = Rent::default().minimum_balance(Escrow::LEN);
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+        let escrow_lamports = escrow.lamports;
+
+        // 감싸서(wrapping) 적은 값이 되지 않고 에러로 끝나야 함
+        assert_eq!(
+            Processor::process(&program_id, &[initializer.info(), escrow.info()], &[4]),
+            Err(ProgramError::from(EscrowError::AmountOverflow))
+        );
+        assert_eq!(initializer.lamports, u64::MAX);
+        assert_eq!(escrow.lamports, escrow_lamports);
+    }
+
+    fn sweep_pda_data(initializer: &Pubkey, nonce: u64) -> Vec<u8> {
+        EscrowInstruction::SweepPda {
+            initializer: *initializer,
+            nonce,
+        }
+        .pack()
+    }
+
+    // 관리자, 램포트가 남은 PDA, 받을 계정, 시스템 프로그램
+    fn sweep_pda_accounts(program_id: &Pubkey, initializer: &Pubkey) -> Vec<TestAccount> {
+        let mut admin = TestAccount::new(Pubkey::default(), vec![]).signer();
+        admin.key = ADMIN_PUBKEY;
+        let mut pda = TestAccount::new(system_program::id(), vec![]);
+        pda.key = escrow_pda(program_id, initializer, 0).0;
+        pda.lamports = Rent::default().minimum_balance(0) + 5_000;
+        let mut destination = TestAccount::new(system_program::id(), vec![]);
+        destination.lamports = 1_000;
+        let mut system_program_account = TestAccount::new(Pubkey::default(), vec![]);
+        system_program_account.key = system_program::id();
+        vec![admin, pda, destination, system_program_account]
+    }
+
+    #[test]
+    fn sweep_pda_moves_lamports_above_rent_to_destination() {
+        let program_id = Pubkey::new_unique();
+        let initializer = Pubkey::new_unique();
+        let mut accounts = sweep_pda_accounts(&program_id, &initializer);
+
+        SYSTEM_TRANSFERS.with(|transfers| transfers.set(true));
+        let result = Processor::process(
+            &program_id,
+            &account_infos(&mut accounts),
+            &sweep_pda_data(&initializer, 0),
+        );
+        SYSTEM_TRANSFERS.with(|transfers| transfers.set(false));
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(accounts[1].lamports, Rent::default().minimum_balance(0));
+        assert_eq!(accounts[2].lamports, 1_000 + 5_000);
+    }
+
+    #[test]
+    fn sweep_pda_rejects_non_admin() {
+        let program_id = Pubkey::new_unique();
+        let initializer = Pubkey::new_unique();
+
+        // 관리자가 아닌 서명자
+        let mut accounts = sweep_pda_accounts(&program_id, &initializer);
+        accounts[0].key = Pubkey::new_unique();
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &sweep_pda_data(&initializer, 0),
+            ),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+
+        // 관리자 키지만 서명하지 않음
+        let mut accounts = sweep_pda_accounts(&program_id, &initializer);
+        accounts[0].is_signer = false;
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &sweep_pda_data(&initializer, 0),
+            ),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        assert_eq!(accounts[2].lamports, 1_000);
+    }
+
+    #[test]
+    fn sweep_pda_rejects_wrong_pda() {
+        let program_id = Pubkey::new_unique();
+        let initializer = Pubkey::new_unique();
+        let mut accounts = sweep_pda_accounts(&program_id, &initializer);
+
+        // 다른 논스의 PDA
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &sweep_pda_data(&initializer, 1),
+            ),
+            Err(ProgramError::from(EscrowError::InvalidPda))
+        );
+    }
+
+    // 만료 시각을 바꾼 에스크로 하나의 (에스크로 계정, 임시 토큰 계정, PDA, 이니셜라이저 키)
+    fn cancel_expired_group(
+        program_id: &Pubkey,
+        expiry_ts: i64,
+    ) -> (TestAccount, TestAccount, TestAccount, Pubkey) {
+        let mut accounts = exchange_accounts(program_id, 100, 100);
+        let mut escrow_info = Escrow::unpack(&accounts[6].data).unwrap();
+        escrow_info.expiry_ts = expiry_ts;
+        Escrow::pack(escrow_info, &mut accounts[6].data).unwrap();
+
+        let initializer = accounts[4].key;
+        let pda = accounts.remove(8);
+        let escrow = accounts.remove(6);
+        let temp = accounts.remove(3);
+        (escrow, temp, pda, initializer)
+    }
+
+    #[test]
+    fn cancel_expired_cancels_only_expired_escrows() {
+        let program_id = Pubkey::new_unique();
+
+        // 스텁의 Clock(0) 기준으로 만료됨, 만료되지 않음(100), 만료 없음(0), 만료됨
+        let groups: Vec<_> = [-1, 100, 0, -50]
+            .into_iter()
+            .map(|expiry_ts| cancel_expired_group(&program_id, expiry_ts))
+            .collect();
+        let mut token_program = TestAccount::new(Pubkey::default(), vec![]);
+        token_program.key = spl_token::id();
+
+        let initializers: Vec<Pubkey> = groups.iter().map(|group| group.3).collect();
+        let mut accounts = vec![token_program];
+        for (escrow, temp, pda, _) in groups {
+            accounts.extend([escrow, temp, pda]);
+        }
+        let before: Vec<Vec<u8>> = accounts
+            .iter()
+            .map(|account| account.data.clone())
+            .collect();
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &[tag::CANCEL_EXPIRED],
+            ),
+            Ok(())
+        );
+
+        for (i, expired) in [true, false, false, true].into_iter().enumerate() {
+            let escrow = &accounts[1 + 3 * i];
+            let temp = &accounts[2 + 3 * i];
+            if expired {
+                let escrow_info = Escrow::unpack(&escrow.data).unwrap();
+                assert_eq!(escrow_info.status, EscrowStatus::Cancelled);
+                assert_eq!(
+                    TokenAccount::unpack(&temp.data).unwrap().owner,
+                    initializers[i]
+                );
+            } else {
+                // 만료되지 않은 에스크로는 그대로
+                assert_eq!(escrow.data, before[1 + 3 * i]);
+                assert_eq!(temp.data, before[2 + 3 * i]);
+            }
+        }
+    }
+
+    #[test]
+    fn cancel_expired_skips_bad_groups_and_mixes_token_programs() {
+        let program_id = Pubkey::new_unique();
+
+        // 0: 닫힌 에스크로, 1: 다른 임시 토큰 계정, 2: Token-2022 에스크로, 3: 정상
+        let mut groups: Vec<_> = (0..4)
+            .map(|_| cancel_expired_group(&program_id, -1))
+            .collect();
+        groups[0].0.data.fill(0);
+        groups[1].1.key = Pubkey::new_unique();
+        let mut escrow_info = Escrow::unpack(&groups[2].0.data).unwrap();
+        escrow_info.token_program_id = spl_token_2022::id();
+        Escrow::pack(escrow_info, &mut groups[2].0.data).unwrap();
+
+        let mut spl_token_program = TestAccount::new(Pubkey::default(), vec![]);
+        spl_token_program.key = spl_token::id();
+        let mut token_2022_program = TestAccount::new(Pubkey::default(), vec![]);
+        token_2022_program.key = spl_token_2022::id();
+
+        let initializers: Vec<Pubkey> = groups.iter().map(|group| group.3).collect();
+        let mut accounts = vec![spl_token_program, token_2022_program];
+        for (escrow, temp, pda, _) in groups {
+            accounts.extend([escrow, temp, pda]);
+        }
+        let before: Vec<Vec<u8>> = accounts
+            .iter()
+            .map(|account| account.data.clone())
+            .collect();
+
+        // 취소할 수 없는 에스크로가 섞여 있어도 배치는 실패하지 않음
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &[tag::CANCEL_EXPIRED],
+            ),
+            Ok(())
+        );
+
+        for (i, cancelled) in [false, false, true, true].into_iter().enumerate() {
+            let escrow = &accounts[2 + 3 * i];
+            let temp = &accounts[3 + 3 * i];
+            if cancelled {
+                let escrow_info = Escrow::unpack(&escrow.data).unwrap();
+                assert_eq!(escrow_info.status, EscrowStatus::Cancelled);
+                assert_eq!(
+                    TokenAccount::unpack(&temp.data).unwrap().owner,
+                    initializers[i]
+                );
+            } else {
+                assert_eq!(escrow.data, before[2 + 3 * i]);
+                assert_eq!(temp.data, before[3 + 3 * i]);
+            }
+        }
+    }
+
+    #[test]
+    fn cancel_expired_skips_escrow_of_token_program_not_passed() {
+        let program_id = Pubkey::new_unique();
+        let (mut escrow, temp, pda, _) = cancel_expired_group(&program_id, -1);
+        let mut escrow_info = Escrow::unpack(&escrow.data).unwrap();
+        escrow_info.token_program_id = spl_token_2022::id();
+        Escrow::pack(escrow_info, &mut escrow.data).unwrap();
+        let escrow_data = escrow.data.clone();
+
+        let mut token_program = TestAccount::new(Pubkey::default(), vec![]);
+        token_program.key = spl_token::id();
+        let mut accounts = vec![token_program, escrow, temp, pda];
+
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &[tag::CANCEL_EXPIRED],
+            ),
+            Ok(())
+        );
+        assert_eq!(accounts[1].data, escrow_data);
+    }
+
+    #[test]
+    fn cancel_expired_rejects_bad_batch_sizes() {
+        let program_id = Pubkey::new_unique();
+        let mut token_program = TestAccount::new(Pubkey::default(), vec![]);
+        token_program.key = spl_token::id();
+
+        // 에스크로가 하나도 없음
+        assert_eq!(
+            Processor::process(&program_id, &[token_program.info()], &[tag::CANCEL_EXPIRED],),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+
+        // MAX_BATCH_SIZE보다 많음
+        let mut accounts = vec![token_program];
+        for _ in 0..=MAX_BATCH_SIZE {
+            let (escrow, temp, pda, _) = cancel_expired_group(&program_id, -1);
+            accounts.extend([escrow, temp, pda]);
+        }
+        let escrow_data = accounts[1].data.clone();
+        assert_eq!(
+            Processor::process(
+                &program_id,
+                &account_infos(&mut accounts),
+                &[tag::CANCEL_EXPIRED],
+            ),
+            Err(ProgramError::from(EscrowError::BatchTooLarge))
+        );
+        assert_eq!(accounts[1].data, escrow_data);
+    }
 }
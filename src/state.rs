@@ -1,13 +1,101 @@
 use solana_program::{
+    account_info::AccountInfo,
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
+    rent::Rent,
 };
 
+use crate::error::EscrowError;
+
+// 에스크로 상태
+// 한 바이트로 직렬화되며 예전의 is_initialized(0, 1)와 값이 호환됨
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscrowStatus {
+    // 초기화되지 않음
+    Uninitialized = 0,
+    // 거래 대기 중
+    Active = 1,
+    // 교환 완료
+    Completed = 2,
+    // 취소됨
+    Cancelled = 3,
+}
+
+// 한 바이트를 EscrowStatus로 바꿈
+// 범위를 벗어난 값은 어카운트 데이터가 잘못된 것이므로 InvalidAccountData 에러
+impl TryFrom<u8> for EscrowStatus {
+    type Error = ProgramError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(EscrowStatus::Uninitialized),
+            1 => Ok(EscrowStatus::Active),
+            2 => Ok(EscrowStatus::Completed),
+            3 => Ok(EscrowStatus::Cancelled),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+// 한 바이트를 bool로 바꿈
+// 0, 1만 허용하고 다른 값은 InvalidAccountData 에러 (같은 값이 여러 바이트로 표현되지 않게 함)
+fn decode_bool(value: u8) -> Result<bool, ProgramError> {
+    match value {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+// 현재 에스크로 계정 레이아웃의 버전
+// 레이아웃이 바뀌면 값을 올리고 unpack에서 예전 버전을 계속 읽을 수 있게 함
+pub const ESCROW_VERSION: u8 = 1;
+
+// 수수료의 최대값 (베이시스 포인트, 10_000 = 100%)
+pub const MAX_FEE_BPS: u16 = 10_000;
+
+// InitEscrow에서 받을 수 있는 최대 금액
+// 기본은 제한이 없고 amount-cap 기능을 켜면 배포자가 정한 상한을 씀
+// (클라이언트 실수로 터무니없는 금액이 잠기는 것을 막는 안전장치)
+#[cfg(not(feature = "amount-cap"))]
+pub const MAX_ESCROW_AMOUNT: u64 = u64::MAX;
+#[cfg(feature = "amount-cap")]
+pub const MAX_ESCROW_AMOUNT: u64 = 1_000_000_000_000_000;
+
+// 교환할 수 있는 에스크로의 최대 나이 (created_at부터 지난 초)
+// 기본은 제한이 없고 max-escrow-age 기능을 켜면 30일이 지난 에스크로는 교환할 수 없음
+// (가격이 이미 낡은 에스크로를 테이커가 채우는 것을 막는 안전장치)
+#[cfg(not(feature = "max-escrow-age"))]
+pub const MAX_ESCROW_AGE_SECS: i64 = i64::MAX;
+#[cfg(feature = "max-escrow-age")]
+pub const MAX_ESCROW_AGE_SECS: i64 = 30 * 24 * 60 * 60;
+
+// SweepPda를 호출할 수 있는 관리자
+// 빌드할 때 ESCROW_ADMIN_PUBKEY 환경 변수(base58)로 받음 (없으면 컴파일 에러)
+// 예: ESCROW_ADMIN_PUBKEY=<관리자 키> cargo build-sbf
+pub const ADMIN_PUBKEY: Pubkey = Pubkey::from_str_const(env!("ESCROW_ADMIN_PUBKEY"));
+
+// 민트 계정 없이 만든 에스크로의 expected_decimals
+// 토큰 Y의 소수점 자릿수를 모르므로 교환 때 자릿수를 확인하지 않음
+pub const DECIMALS_UNKNOWN: u8 = u8::MAX;
+
+// 받아들이는 민트의 최대 소수점 자릿수
+// 비율 계산과 수수료 계산에서 10^자릿수 단위의 값이 u64를 넘지 않도록 제한
+pub const MAX_DECIMALS: u8 = 18;
+
+// InitEscrowBatch 한 번에 만들 수 있는 (CancelExpired 한 번에 취소할 수 있는) 최대 에스크로 수
+// 에스크로마다 계정이 3개씩 늘어나므로 트랜잭션의 계정 수와 컴퓨트 한도 안에 들도록 제한
+pub const MAX_BATCH_SIZE: usize = 8;
+
 // 에스크로 구조체
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Escrow {
-    // 초기화 여부
-    pub is_initialized: bool,
+    // 계정 레이아웃 버전 (직렬화된 데이터의 첫 바이트)
+    pub version: u8,
+
+    // 에스크로 상태
+    pub status: EscrowStatus,
 
     // 초기화 실행자의 계정
     pub initializer_pubkey: Pubkey,
@@ -22,96 +110,1374 @@ pub struct Escrow {
 
     // 예상 수량
     pub expected_amount: u64,
+
+    // PDA의 범프 시드
+    // 교환, 취소 때 invoke_signed의 서명 시드를 다시 만들기 위해 저장
+    pub bump_seed: u8,
+
+    // 만료 시각 (유닉스 타임스탬프)
+    // 이 시각이 지나면 교환할 수 없고 취소만 가능, 0이면 만료되지 않음
+    pub expiry_ts: i64,
+
+    // 임시 토큰 계정에 잠긴 토큰 X의 민트
+    // 교환 때 테이커가 받는 토큰이 이 민트인지 확인
+    pub x_token_mint: Pubkey,
+
+    // PDA 시드에 쓰인 논스
+    // 이니셜라이저 한 명이 여러 에스크로를 따로 열 수 있게 함
+    pub nonce: u64,
+
+    // 이니셜라이저가 받을 토큰 Y의 민트
+    // 교환 때 테이커가 보내는 토큰이 이 민트인지 확인
+    pub expected_mint: Pubkey,
+
+    // 에스크로를 만들 때 사용한 토큰 프로그램 (SPL Token 또는 Token-2022)
+    pub token_program_id: Pubkey,
+
+    // 교환 때 테이커가 보내는 토큰 Y에서 떼는 수수료 (베이시스 포인트, 10_000 = 100%)
+    pub fee_bps: u16,
+
+    // 아직 채워지지 않은 토큰 Y의 수량 (부분 체결)
+    // 초기화 때 expected_amount와 같고 0이 되면 에스크로가 완료됨
+    pub remaining_amount: u64,
+
+    // 토큰 X 대신 SOL(램포트)을 맡긴 에스크로인지
+    // true면 램포트가 에스크로 계정 자체에 (렌트비 위로) 보관됨
+    pub is_native: bool,
+
+    // PDA 시드에 들어간 이니셜라이저
+    // TransferInitializer로 이니셜라이저가 바뀌어도 임시 토큰 계정을 가진 PDA는 그대로이므로 따로 보관
+    pub pda_seed_pubkey: Pubkey,
+
+    // 오프체인 참조용 메모 (주문 번호 등)
+    // 프론트엔드가 자기 DB의 주문과 에스크로를 연결할 때 씀, 0으로 채워져 있으면 메모 없음
+    pub memo: [u8; 32],
+
+    // 이 시각(유닉스 타임스탬프)부터 취소할 수 있음
+    // 거래 도중에 이니셜라이저가 취소해 테이커를 방해하지 못하게 함, 0이면 언제든 취소 가능
+    pub cancel_after_ts: i64,
+
+    // 토큰 Y 민트의 소수점 자릿수
+    // InitEscrow에 민트 계정을 넘기면 기록하고, 아니면 DECIMALS_UNKNOWN
+    // 교환 때 테이커가 자릿수를 잘못 계산해 훨씬 적은 양을 보내는 것을 막음
+    pub expected_decimals: u8,
+
+    // 에스크로를 만든 시각 (유닉스 타임스탬프, 초기화 때 Clock에서 읽음)
+    // 만든 뒤에는 바뀌지 않으며 만료, 취소 가능 시각을 상대 기간으로 계산할 때 씀
+    pub created_at: i64,
+
+    // 토큰 X를 맡긴 방식
+    // false면 이니셜라이저의 임시 토큰 계정 소유권을 PDA로 넘김 (InitEscrow)
+    // true면 토큰을 PDA 소유의 연관 토큰 계정(ATA)으로 옮김 (InitEscrowWithTransfer)
+    // 취소 때 소유권을 되돌릴지 토큰을 돌려보낼지를 이 값으로 고름
+    pub vault_custody: bool,
+
+    // true면 토큰 계정은 이니셜라이저 소유로 두고 PDA를 위임자로 승인함 (InitEscrowWithDelegate)
+    // 교환 때 PDA가 위임 권한으로 보내고, 취소 때 소유권 대신 위임을 철회함
+    pub delegate_custody: bool,
+
+    // 지금까지 체결된 횟수 (교환마다 1씩 늘어남)
+    // 여러 테이커가 나누어 채운 에스크로를 감사할 때 씀
+    pub fills: u32,
+
+    // 마지막으로 체결한 테이커 (아직 체결이 없으면 0으로 채워진 키)
+    pub last_taker: Pubkey,
+
+    // true면 교환 때 토큰 X를 바로 보내지 않고 created_at부터 expiry_ts까지 선형으로 풀어줌
+    // 테이커(last_taker)는 ClaimVested로 그때까지 풀린 만큼만 찾아감
+    pub vesting: bool,
+
+    // 베스팅 에스크로가 교환될 때 임시 계정에 있던 토큰 X의 수량 (풀어줄 전체 수량)
+    pub vesting_total: u64,
+
+    // 베스팅 에스크로에서 테이커가 지금까지 찾아간 토큰 X의 수량
+    pub claimed_amount: u64,
+
+    // 위임으로 맡긴 에스크로(delegate_custody)에서 아직 내보내지 않은 토큰 X의 수량
+    // 초기화 때 위임한 수량으로 채우고 교환 때 내보낸 만큼 줄어듦
+    // 이니셜라이저가 위임을 다시 승인해 바꿀 수 있으므로 토큰 계정의 delegated_amount 대신 이 값으로 계산
+    pub deposit_amount: u64,
+}
+
+// 초기화되지 않은 빈 에스크로
+// Pubkey의 기본값은 0으로 채워진 키
+impl Default for Escrow {
+    fn default() -> Self {
+        Escrow {
+            version: ESCROW_VERSION,
+            status: EscrowStatus::Uninitialized,
+            initializer_pubkey: Pubkey::default(),
+            x_token_account_pubkey: Pubkey::default(),
+            initializer_token_to_receive_account_pubkey: Pubkey::default(),
+            expected_amount: 0,
+            bump_seed: 0,
+            expiry_ts: 0,
+            x_token_mint: Pubkey::default(),
+            nonce: 0,
+            expected_mint: Pubkey::default(),
+            token_program_id: Pubkey::default(),
+            fee_bps: 0,
+            remaining_amount: 0,
+            is_native: false,
+            pda_seed_pubkey: Pubkey::default(),
+            memo: [0; 32],
+            cancel_after_ts: 0,
+            expected_decimals: DECIMALS_UNKNOWN,
+            created_at: 0,
+            vault_custody: false,
+            delegate_custody: false,
+            fills: 0,
+            last_taker: Pubkey::default(),
+            vesting: false,
+            vesting_total: 0,
+            claimed_amount: 0,
+            deposit_amount: 0,
+        }
+    }
 }
 
 impl Sealed for Escrow {}
 
 impl IsInitialized for Escrow {
+    // Uninitialized가 아닌 모든 상태는 초기화된 것으로 봄
     fn is_initialized(&self) -> bool {
-        self.is_initialized
+        self.status != EscrowStatus::Uninitialized
     }
 }
 
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 
-impl Pack for Escrow {
-    // Pack을 수행하기 위해서는 LEN을 먼저 정의해야함
-    // LEN: 우리 타입의 사이즈
-    // Escrow 스트럭트를 보면 스트럭트의 길이를
-    // 데이터 타입을 추가함으로써 어떻게 계산하는지 알 수 있음
-    // 1(bool) + 3 * 32(Pubkey) + 1 * 8(u64) = 105;
-    const LEN: usize = 105;
+impl Escrow {
+    // 거래 대기 중(Active)인 새 에스크로를 만듦
+    // 남은 수량은 예상 수량 전체이고 PDA 시드는 이니셜라이저, 나머지 필드는 기본값
+    // 필드가 늘어나면 여기서 컴파일 에러가 나도록 ..Default 없이 모든 필드를 적음
+    // (처리 경로마다 필드를 하나씩 채우다가 빠뜨리는 실수를 막음)
+    pub fn new(
+        initializer: Pubkey,
+        x_token_account: Pubkey,
+        receive_account: Pubkey,
+        expected_amount: u64,
+        bump_seed: u8,
+    ) -> Self {
+        Escrow {
+            version: ESCROW_VERSION,
+            status: EscrowStatus::Active,
+            initializer_pubkey: initializer,
+            x_token_account_pubkey: x_token_account,
+            initializer_token_to_receive_account_pubkey: receive_account,
+            expected_amount,
+            bump_seed,
+            expiry_ts: 0,
+            x_token_mint: Pubkey::default(),
+            nonce: 0,
+            expected_mint: Pubkey::default(),
+            token_program_id: Pubkey::default(),
+            fee_bps: 0,
+            remaining_amount: expected_amount,
+            is_native: false,
+            pda_seed_pubkey: initializer,
+            memo: [0; 32],
+            cancel_after_ts: 0,
+            expected_decimals: DECIMALS_UNKNOWN,
+            created_at: 0,
+            vault_custody: false,
+            delegate_custody: false,
+            fills: 0,
+            last_taker: Pubkey::default(),
+            vesting: false,
+            vesting_total: 0,
+            claimed_amount: 0,
+            deposit_amount: 0,
+        }
+    }
 
-    // unpack_from_slice: 슬라이스에서 압축해제(디시리얼라이즈: 역직렬화)
-    // Escrow 스트럭트의 길이를 정의한 후,
-    // u8 배열을 받을 후 이것을 Escrow 스트럭트의
-    // 인스턴스(복제)로 바꾸는 unpack_from_slice를 구현
+    // 필드 사이의 불변 조건을 확인
+    // 초기화된 에스크로라면 이니셜라이저가 있어야 하고 예상 수량이 0보다 커야 함
+    // (초기화되지 않은 빈 에스크로는 확인할 것이 없으므로 통과)
+    pub fn validate(&self) -> Result<(), ProgramError> {
+        if !self.is_initialized() {
+            return Ok(());
+        }
 
-    // u8 배열을 받아 Result로 Escrow 스트럭트(Self) 또는 Program_Error 반환
-    fn unpack_from_slice(src: &[u8]) -> Result<Self, solana_program::program_error::ProgramError> {
-        // array_ref를 사용하여 [u8배열, 0, Escrow 스트럭트 사이즈]로 구성된 배열을 만들고 참조함
-        let src = array_ref![src, 0, Escrow::LEN];
+        if self.initializer_pubkey == Pubkey::default() || self.expected_amount == 0 {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(())
+    }
+
+    // 에스크로가 주어진 시각에 만료되었는지 확인
+    // expiry_ts가 0이면 만료되지 않고, expiry_ts와 같은 시각부터 만료됨
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expiry_ts != 0 && now >= self.expiry_ts
+    }
+
+    // 에스크로가 주어진 시각에 MAX_ESCROW_AGE_SECS보다 오래되었는지 확인
+    // 뺄셈이 i64를 넘지 않도록 i128로 계산
+    pub fn is_stale(&self, now: i64) -> bool {
+        now as i128 - self.created_at as i128 > MAX_ESCROW_AGE_SECS as i128
+    }
+
+    // now 시각까지 풀린 베스팅 수량 (이미 찾아간 수량 포함)
+    // created_at 이전이면 0, expiry_ts 이후면 vesting_total 전체, 그 사이는 경과 시간에 비례
+    // 곱셈이 u64를 넘지 않도록 u128로 계산
+    pub fn vested_amount(&self, now: i64) -> Result<u64, ProgramError> {
+        if now <= self.created_at {
+            return Ok(0);
+        }
+        if now >= self.expiry_ts {
+            return Ok(self.vesting_total);
+        }
 
+        let elapsed = now
+            .checked_sub(self.created_at)
+            .ok_or(EscrowError::AmountOverflow)? as u128;
+        let duration = self
+            .expiry_ts
+            .checked_sub(self.created_at)
+            .ok_or(EscrowError::AmountOverflow)? as u128;
+        let vested = (self.vesting_total as u128)
+            .checked_mul(elapsed)
+            .and_then(|product| product.checked_div(duration))
+            .ok_or(EscrowError::AmountOverflow)?;
+        u64::try_from(vested).map_err(|_| EscrowError::AmountOverflow.into())
+    }
+
+    // 거래 대기 중(Active)인 에스크로만 압축
+    // 초기화 프로세스에서 상태를 Active로 바꾸는 것을 잊고 빈 에스크로를 쓰는 실수를 직렬화 시점에 잡음
+    pub fn pack_active(self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        if self.status != EscrowStatus::Active {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        Escrow::pack(self, dst)
+    }
+
+    // 에스크로를 계정 데이터에 압축해 저장
+    // 데이터를 이미 빌려 쓰고 있으면 패닉 대신 AccountBorrowFailed 에러 반환
+    // (처리 경로마다 try_borrow_mut_data와 pack을 반복하지 않도록 한 곳에 모음)
+    //
+    // 쓰기 직전에 계정 크기가 아직 LEN인지 다시 확인함
+    // 처음 읽을 때 크기를 확인했더라도 같은 명령 안의 CPI나 앞선 명령이 계정을 realloc해서
+    // 줄이거나 늘렸을 수 있음 (그 상태로 쓰면 범위를 넘거나 남은 바이트가 섞임)
+    // 값이 걸린 계정이므로 비용이 거의 없는 이 확인을 한 번 더 함
+    pub fn save(&self, account: &AccountInfo) -> Result<(), ProgramError> {
+        if account.data_len() != Escrow::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut data = account.try_borrow_mut_data()?;
+        Escrow::pack(*self, &mut data)
+    }
+
+    // 거래 대기 중(Active)인 에스크로만 계정에 저장
+    // 초기화 프로세스에서 pack_active의 상태 확인과 save의 크기 확인을 함께 거침
+    pub fn save_active(&self, account: &AccountInfo) -> Result<(), ProgramError> {
+        if self.status != EscrowStatus::Active {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        self.save(account)
+    }
+
+    // 버전 1 레이아웃을 읽음
+    fn unpack_v1(src: &[u8; Escrow::LEN]) -> Result<Self, ProgramError> {
         // 위의 src를 튜플화하여 각 값에 맞는 변수명으로 다시 할당함
         let (
-            is_initialized,
+            _version,
+            status,
             initializer_pubkey,
             x_token_account_pubkey,
             initializer_token_to_receive_account_pubkey,
             expected_amount,
-        ) = array_refs![src, 1, 32, 32, 32, 8];
+            bump_seed,
+            expiry_ts,
+            x_token_mint,
+            nonce,
+            expected_mint,
+            token_program_id,
+            fee_bps,
+            remaining_amount,
+            is_native,
+            pda_seed_pubkey,
+            memo,
+            cancel_after_ts,
+            expected_decimals,
+            created_at,
+            vault_custody,
+            delegate_custody,
+            fills,
+            last_taker,
+            vesting,
+            vesting_total,
+            claimed_amount,
+            deposit_amount,
+        ) = array_refs![
+            src, 1, 1, 32, 32, 32, 8, 1, 8, 32, 8, 32, 32, 2, 8, 1, 32, 32, 8, 1, 8, 1, 1, 4, 32,
+            1, 8, 8, 8
+        ];
 
-        // 초기화여부를 섀도잉을 통해 [0], [1]에서 True, False로 치환
-        let is_initialized = match is_initialized {
-            [0] => false,
-            [1] => true,
-            // 값이 다르다면 어카운트 데이터가 잘못된다는 에러 발생
-            _ => return Err(ProgramError::InvalidAccountData),
-        };
+        // 상태를 섀도잉을 통해 [0] ~ [3]에서 EscrowStatus로 치환
+        // 값이 다르다면 어카운트 데이터가 잘못된다는 에러 발생
+        let status = EscrowStatus::try_from(status[0])?;
+
+        // bool은 0, 1만 허용
+        let is_native = decode_bool(is_native[0])?;
 
         // 역직렬화하여 (값을 튜플로 풀어서 변수명에 각각 할당한 후)
         // 그것을 다시 Escrow 구조체로 반환
         Ok(Escrow {
-            is_initialized,
+            version: 1,
+            status,
             initializer_pubkey: Pubkey::new_from_array(*initializer_pubkey),
             x_token_account_pubkey: Pubkey::new_from_array(*x_token_account_pubkey),
             initializer_token_to_receive_account_pubkey: Pubkey::new_from_array(
                 *initializer_token_to_receive_account_pubkey,
             ),
             expected_amount: u64::from_le_bytes(*expected_amount),
+            bump_seed: bump_seed[0],
+            expiry_ts: i64::from_le_bytes(*expiry_ts),
+            x_token_mint: Pubkey::new_from_array(*x_token_mint),
+            nonce: u64::from_le_bytes(*nonce),
+            expected_mint: Pubkey::new_from_array(*expected_mint),
+            token_program_id: Pubkey::new_from_array(*token_program_id),
+            fee_bps: u16::from_le_bytes(*fee_bps),
+            remaining_amount: u64::from_le_bytes(*remaining_amount),
+            is_native,
+            pda_seed_pubkey: Pubkey::new_from_array(*pda_seed_pubkey),
+            memo: *memo,
+            cancel_after_ts: i64::from_le_bytes(*cancel_after_ts),
+            expected_decimals: expected_decimals[0],
+            created_at: i64::from_le_bytes(*created_at),
+            vault_custody: decode_bool(vault_custody[0])?,
+            delegate_custody: decode_bool(delegate_custody[0])?,
+            fills: u32::from_le_bytes(*fills),
+            last_taker: Pubkey::new_from_array(*last_taker),
+            vesting: decode_bool(vesting[0])?,
+            vesting_total: u64::from_le_bytes(*vesting_total),
+            claimed_amount: u64::from_le_bytes(*claimed_amount),
+            deposit_amount: u64::from_le_bytes(*deposit_amount),
         })
     }
+}
+
+impl Pack for Escrow {
+    // Pack을 수행하기 위해서는 LEN을 먼저 정의해야함
+    // LEN: 우리 타입의 사이즈
+    // Escrow 스트럭트를 보면 스트럭트의 길이를
+    // 데이터 타입을 추가함으로써 어떻게 계산하는지 알 수 있음
+    // 1(version) + 1(status) + 8 * 32(Pubkey) + 6 * 8(u64) + 1(u8) + 3 * 8(i64) + 1 * 2(u16) + 4 * 1(bool) + 32(memo) + 1(decimals) + 4(u32) = 374;
+    const LEN: usize = 374;
+
+    // unpack_from_slice: 슬라이스에서 압축해제(디시리얼라이즈: 역직렬화)
+    // Escrow 스트럭트의 길이를 정의한 후,
+    // u8 배열을 받을 후 이것을 Escrow 스트럭트의
+    // 인스턴스(복제)로 바꾸는 unpack_from_slice를 구현
+
+    // u8 배열을 받아 Result로 Escrow 스트럭트(Self) 또는 Program_Error 반환
+    // Pack::unpack, unpack_unchecked는 길이가 LEN과 다르면 여기 오기 전에 InvalidAccountData를 반환하지만
+    // unpack_from_slice를 직접 부를 수도 있으므로 짧은 버퍼는 패닉 대신 에러로 처리
+    // (LEN보다 긴 버퍼는 앞의 LEN 바이트만 읽음)
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, solana_program::program_error::ProgramError> {
+        if src.len() < Escrow::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // array_ref를 사용하여 [u8배열, 0, Escrow 스트럭트 사이즈]로 구성된 배열을 만들고 참조함
+        let src = array_ref![src, 0, Escrow::LEN];
+
+        // 첫 바이트(버전)에 따라 레이아웃을 골라서 읽음
+        match src[0] {
+            // 0으로 채워진 새 계정은 아직 아무 버전도 쓰이지 않은 빈 에스크로
+            0 if src.iter().all(|byte| *byte == 0) => Ok(Escrow::default()),
+            1 => Self::unpack_v1(src),
+            // 모르는 버전이면 어카운트 데이터가 잘못된다는 에러 발생
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
 
     // 슬라이스로 압축 (직렬화)
+    // 에러를 반환할 수 없으므로 dst가 LEN 이상이라고 가정함
+    // Pack::pack은 길이가 LEN과 다르면 여기 오기 전에 InvalidAccountData를 반환함
     fn pack_into_slice(&self, dst: &mut [u8]) {
         // array_mut_ref를 사용하여 [u8배열, 0, Escrow 스트럭트 사이즈]로 구성된 변경가능한 배열을 만들고 참조함
         let dst = array_mut_ref![dst, 0, Escrow::LEN];
 
         // 위의 dst를 튜플화하여 각 값에 맞는 변수명으로 다시 할당함
         let (
-            is_initialized_dst,
+            version_dst,
+            status_dst,
             initializer_pubkey_dst,
             x_token_account_pubkey_dst,
             initializer_token_to_receive_account_pubkey_dst,
             expected_amount_dst,
-        ) = mut_array_refs![dst, 1, 32, 32, 32, 8];
+            bump_seed_dst,
+            expiry_ts_dst,
+            x_token_mint_dst,
+            nonce_dst,
+            expected_mint_dst,
+            token_program_id_dst,
+            fee_bps_dst,
+            remaining_amount_dst,
+            is_native_dst,
+            pda_seed_pubkey_dst,
+            memo_dst,
+            cancel_after_ts_dst,
+            expected_decimals_dst,
+            created_at_dst,
+            vault_custody_dst,
+            delegate_custody_dst,
+            fills_dst,
+            last_taker_dst,
+            vesting_dst,
+            vesting_total_dst,
+            claimed_amount_dst,
+            deposit_amount_dst,
+        ) = mut_array_refs![
+            dst, 1, 1, 32, 32, 32, 8, 1, 8, 32, 8, 32, 32, 2, 8, 1, 32, 32, 8, 1, 8, 1, 1, 4, 32,
+            1, 8, 8, 8
+        ];
 
         // Escrow 구조체에 Self에서 값을 가져옴
         let Escrow {
-            is_initialized,
+            version,
+            status,
             initializer_pubkey,
             x_token_account_pubkey,
             initializer_token_to_receive_account_pubkey,
             expected_amount,
+            bump_seed,
+            expiry_ts,
+            x_token_mint,
+            nonce,
+            expected_mint,
+            token_program_id,
+            fee_bps,
+            remaining_amount,
+            is_native,
+            pda_seed_pubkey,
+            memo,
+            cancel_after_ts,
+            expected_decimals,
+            created_at,
+            vault_custody,
+            delegate_custody,
+            fills,
+            last_taker,
+            vesting,
+            vesting_total,
+            claimed_amount,
+            deposit_amount,
         } = self;
 
         // self의 값을 Escrow 구조체 형태로 가져와서
         // 각각의 _dst로 참조하여 카피함
-        is_initialized_dst[0] = *is_initialized as u8;
+        version_dst[0] = *version;
+        status_dst[0] = *status as u8;
         initializer_pubkey_dst.copy_from_slice(initializer_pubkey.as_ref());
         x_token_account_pubkey_dst.copy_from_slice(x_token_account_pubkey.as_ref());
         initializer_token_to_receive_account_pubkey_dst
             .copy_from_slice(initializer_token_to_receive_account_pubkey.as_ref());
         *expected_amount_dst = expected_amount.to_le_bytes();
+        bump_seed_dst[0] = *bump_seed;
+        *expiry_ts_dst = expiry_ts.to_le_bytes();
+        x_token_mint_dst.copy_from_slice(x_token_mint.as_ref());
+        *nonce_dst = nonce.to_le_bytes();
+        expected_mint_dst.copy_from_slice(expected_mint.as_ref());
+        token_program_id_dst.copy_from_slice(token_program_id.as_ref());
+        *fee_bps_dst = fee_bps.to_le_bytes();
+        *remaining_amount_dst = remaining_amount.to_le_bytes();
+        is_native_dst[0] = *is_native as u8;
+        pda_seed_pubkey_dst.copy_from_slice(pda_seed_pubkey.as_ref());
+        *memo_dst = *memo;
+        *cancel_after_ts_dst = cancel_after_ts.to_le_bytes();
+        expected_decimals_dst[0] = *expected_decimals;
+        *created_at_dst = created_at.to_le_bytes();
+        vault_custody_dst[0] = *vault_custody as u8;
+        delegate_custody_dst[0] = *delegate_custody as u8;
+        *fills_dst = fills.to_le_bytes();
+        last_taker_dst.copy_from_slice(last_taker.as_ref());
+        vesting_dst[0] = *vesting as u8;
+        *vesting_total_dst = vesting_total.to_le_bytes();
+        *claimed_amount_dst = claimed_amount.to_le_bytes();
+        *deposit_amount_dst = deposit_amount.to_le_bytes();
+    }
+}
+
+// 에스크로 계정 데이터를 풀지 않고 빌린 채로 읽는 뷰
+// 다른 프로그램이 몇 개 필드만 읽을 때 Escrow 전체(LEN 바이트)를 복사해 푸는 비용을 줄임
+// 쓰기는 지금처럼 Escrow의 Pack으로 함
+#[derive(Debug, Clone, Copy)]
+pub struct EscrowRef<'a> {
+    data: &'a [u8; Escrow::LEN],
+}
+
+impl<'a> EscrowRef<'a> {
+    // ESCROW_VERSION 레이아웃에서 필드의 오프셋
+    // 이니셜라이저는 버전 1바이트 + 상태 1바이트 뒤,
+    // 예상 금액은 그 뒤 Pubkey 3개(이니셜라이저, 임시 토큰 계정, 받을 토큰 계정) 뒤
+    const INITIALIZER_PUBKEY_OFFSET: usize = 1 + 1;
+    const EXPECTED_AMOUNT_OFFSET: usize = Self::INITIALIZER_PUBKEY_OFFSET + 32 * 3;
+
+    // 길이가 LEN보다 짧거나 ESCROW_VERSION으로 쓰인 에스크로가 아니면 InvalidAccountData 에러
+    // (빈 계정이나 모르는 버전은 오프셋이 맞지 않으므로 읽지 않음)
+    pub fn new(data: &'a [u8]) -> Result<Self, ProgramError> {
+        if data.len() < Escrow::LEN || data[0] != ESCROW_VERSION {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self {
+            data: array_ref![data, 0, Escrow::LEN],
+        })
+    }
+
+    pub fn initializer_pubkey(&self) -> Pubkey {
+        Pubkey::new_from_array(*array_ref![self.data, Self::INITIALIZER_PUBKEY_OFFSET, 32])
+    }
+
+    pub fn expected_amount(&self) -> u64 {
+        u64::from_le_bytes(*array_ref![self.data, Self::EXPECTED_AMOUNT_OFFSET, 8])
+    }
+}
+
+// 에스크로 계정의 크기 (Pack 트레이트를 가져오지 않아도 쓸 수 있도록 밖으로 꺼냄)
+pub const ESCROW_LEN: usize = Escrow::LEN;
+
+// 에스크로 계정이 렌트비 면제가 되려면 필요한 램포트
+// 클라이언트가 계정을 만들기 전에 정확히 얼마를 채워야 하는지 알 수 있음
+pub fn escrow_rent_exempt_lamports(rent: &Rent) -> u64 {
+    rent.minimum_balance(ESCROW_LEN)
+}
+
+// 에스크로 계정을 읽고 확인
+// 이 프로그램 소유인지, 크기가 Escrow::LEN인지 확인한 뒤
+// unpack(checked)으로 풀어서 초기화된 에스크로만 반환 (아니면 UninitializedAccount 에러)
+// 풀어낸 에스크로가 validate를 통과하지 못하면 InvalidAccountData 에러
+pub fn load_escrow(account: &AccountInfo, program_id: &Pubkey) -> Result<Escrow, ProgramError> {
+    // 다른 프로그램의 데이터를 믿고 쓰지 않도록 unpack 전에 확인
+    if account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    // 크기가 다른 계정은 패닉 대신 복구 가능한 에러로 처리
+    if account.data_len() != Escrow::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let escrow_info = Escrow::unpack(&account.try_borrow_data()?)?;
+    // 어떤 명령도 앞뒤가 맞지 않는 에스크로를 그대로 쓰지 않도록 여기서 한 번에 확인
+    escrow_info.validate()?;
+    Ok(escrow_info)
+}
+
+// 이니셜라이저별 레지스트리
+// 그 이니셜라이저가 레지스트리를 넘겨 연 에스크로 중 아직 열려 있는 에스크로 계정 목록
+// (프로그램의 모든 계정을 훑지 않고도 내 에스크로를 찾을 수 있게 함)
+// 모든 초기화 경로에서 넣고, CancelEscrow와 CloseEscrow에서 빼며, TransferInitializer에서 옮김
+// CancelExpired는 서명 없이 호출되므로 빼지 않고, 그 에스크로는 CloseEscrow로 닫을 때 빠짐
+//
+// 목록 길이에 따라 계정 크기가 바뀌므로 고정 크기(LEN)가 필요한 Pack 대신
+// 같은 이름의 pack, unpack을 직접 구현함
+#[derive(Debug, Default, PartialEq)]
+pub struct Registry {
+    // 레지스트리 PDA의 범프 시드 (다시 찾지 않고 주소를 확인할 때 씀)
+    pub bump_seed: u8,
+    // 열려 있는 에스크로 계정들
+    pub escrows: Vec<Pubkey>,
+}
+
+impl Registry {
+    // 범프(1바이트) + 개수(u32 LE 4바이트), 그 뒤에 에스크로 주소(32바이트)가 개수만큼
+    pub const HEADER_LEN: usize = 5;
+
+    // 에스크로 count개를 담는 레지스트리 계정의 크기
+    pub fn space(count: usize) -> usize {
+        Self::HEADER_LEN + 32 * count
+    }
+
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        // 헤더보다 짧으면 레지스트리 계정이 아님
+        if input.len() < Self::HEADER_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let header = array_ref![input, 0, Registry::HEADER_LEN];
+        let (bump_seed, count) = array_refs![header, 1, 4];
+        let count = u32::from_le_bytes(*count) as usize;
+
+        // 크기가 개수와 맞지 않으면 잘못된 데이터
+        if input.len() != Self::space(count) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let escrows = input[Self::HEADER_LEN..]
+            .chunks_exact(32)
+            .map(|chunk| Pubkey::new_from_array(*array_ref![chunk, 0, 32]))
+            .collect();
+        Ok(Self {
+            bump_seed: bump_seed[0],
+            escrows,
+        })
+    }
+
+    pub fn pack(&self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        // 계정 크기는 호출하는 쪽에서 먼저 space에 맞춰 둠
+        if dst.len() != Self::space(self.escrows.len()) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (header, body) = dst.split_at_mut(Self::HEADER_LEN);
+        header[0] = self.bump_seed;
+        header[1..].copy_from_slice(&(self.escrows.len() as u32).to_le_bytes());
+        for (chunk, escrow) in body.chunks_exact_mut(32).zip(&self.escrows) {
+            chunk.copy_from_slice(escrow.as_ref());
+        }
+        Ok(())
+    }
+
+    // 에스크로를 목록에 추가 (이미 있으면 그대로 둬서 두 번 들어가지 않게 함)
+    pub fn insert(&mut self, escrow: Pubkey) {
+        if !self.escrows.contains(&escrow) {
+            self.escrows.push(escrow);
+        }
+    }
+
+    // 에스크로를 목록에서 뺌 (연 순서는 유지, 없었으면 false)
+    pub fn remove(&mut self, escrow: &Pubkey) -> bool {
+        match self.escrows.iter().position(|key| key == escrow) {
+            Some(index) => {
+                self.escrows.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_escrow_is_uninitialized() {
+        let escrow = Escrow::default();
+        assert!(!escrow.is_initialized());
+        assert_eq!(escrow.status, EscrowStatus::Uninitialized);
+        assert_eq!(Escrow::unpack_unchecked(&[0u8; Escrow::LEN]), Ok(escrow));
+    }
+
+    #[test]
+    fn pack_round_trip_keeps_bump_seed() {
+        let escrow = Escrow {
+            version: ESCROW_VERSION,
+            status: EscrowStatus::Active,
+            initializer_pubkey: Pubkey::new_unique(),
+            x_token_account_pubkey: Pubkey::new_unique(),
+            initializer_token_to_receive_account_pubkey: Pubkey::new_unique(),
+            expected_amount: 42,
+            bump_seed: 254,
+            expiry_ts: 1_700_000_000,
+            x_token_mint: Pubkey::new_unique(),
+            nonce: 7,
+            expected_mint: Pubkey::new_unique(),
+            token_program_id: Pubkey::new_unique(),
+            fee_bps: 250,
+            remaining_amount: 40,
+            is_native: true,
+            pda_seed_pubkey: Pubkey::new_unique(),
+            memo: [7; 32],
+            cancel_after_ts: 1_700_000_100,
+            expected_decimals: 9,
+            created_at: 1_699_999_000,
+            vault_custody: true,
+            delegate_custody: true,
+            fills: 3,
+            last_taker: Pubkey::new_unique(),
+            vesting: true,
+            vesting_total: 1_000,
+            claimed_amount: 250,
+            deposit_amount: 60,
+        };
+
+        let mut packed = [0u8; Escrow::LEN];
+        Escrow::pack(escrow, &mut packed).unwrap();
+
+        let unpacked = Escrow::unpack(&packed).unwrap();
+        assert_eq!(unpacked, escrow);
+        assert_eq!(unpacked.bump_seed, 254);
+        assert_eq!(unpacked.expected_amount, 42);
+        assert_eq!(unpacked.expiry_ts, 1_700_000_000);
+    }
+
+    #[test]
+    fn pack_round_trip_keeps_memo() {
+        let mut memo = [0u8; 32];
+        memo[..12].copy_from_slice(b"order-000042");
+        let escrow = Escrow {
+            status: EscrowStatus::Active,
+            memo,
+            ..Escrow::default()
+        };
+
+        let mut packed = [0u8; Escrow::LEN];
+        Escrow::pack(escrow, &mut packed).unwrap();
+
+        // 메모 뒤에는 cancel_after_ts(8바이트), expected_decimals(1바이트), created_at(8바이트),
+        // vault_custody(1바이트), delegate_custody(1바이트), fills(4바이트), last_taker(32바이트),
+        // vesting(1바이트), vesting_total(8바이트), claimed_amount(8바이트), deposit_amount(8바이트)가 옴
+        assert_eq!(&packed[Escrow::LEN - 112..Escrow::LEN - 80], &memo);
+        assert_eq!(Escrow::unpack(&packed).unwrap().memo, memo);
+    }
+
+    #[test]
+    fn len_matches_serialized_size() {
+        // 필드 크기를 모두 더한 값이 LEN과 같아야 함
+        // version, status, Pubkey 8개, u64 6개, bump_seed, i64 3개, fee_bps, bool 4개, memo, expected_decimals, fills
+        // bool은 1바이트씩이므로 4개를 그냥 4로 더함
+        assert_eq!(
+            1 + 1 + 32 * 8 + 8 * 6 + 1 + 8 * 3 + 2 + 4 + 32 + 1 + 4,
+            Escrow::LEN
+        );
+
+        // 모든 필드를 채운 에스크로가 LEN 크기 버퍼에 정확히 들어가고 그대로 풀림
+        let escrow = Escrow {
+            version: ESCROW_VERSION,
+            status: EscrowStatus::Cancelled,
+            initializer_pubkey: Pubkey::new_unique(),
+            x_token_account_pubkey: Pubkey::new_unique(),
+            initializer_token_to_receive_account_pubkey: Pubkey::new_unique(),
+            expected_amount: u64::MAX,
+            bump_seed: u8::MAX,
+            expiry_ts: i64::MIN,
+            x_token_mint: Pubkey::new_unique(),
+            nonce: u64::MAX,
+            expected_mint: Pubkey::new_unique(),
+            token_program_id: Pubkey::new_unique(),
+            fee_bps: u16::MAX,
+            remaining_amount: u64::MAX,
+            is_native: true,
+            pda_seed_pubkey: Pubkey::new_unique(),
+            memo: [u8::MAX; 32],
+            cancel_after_ts: i64::MAX,
+            expected_decimals: u8::MAX,
+            created_at: i64::MIN,
+            vault_custody: true,
+            delegate_custody: true,
+            fills: u32::MAX,
+            last_taker: Pubkey::new_unique(),
+            vesting: true,
+            vesting_total: u64::MAX,
+            claimed_amount: u64::MAX,
+            deposit_amount: u64::MAX,
+        };
+        let mut packed = [0u8; Escrow::LEN];
+        Escrow::pack(escrow, &mut packed).unwrap();
+        assert_eq!(Escrow::unpack_unchecked(&packed), Ok(escrow));
+
+        // 크기가 다른 버퍼에는 압축할 수 없음
+        let mut short = [0u8; Escrow::LEN - 1];
+        assert_eq!(
+            Escrow::pack(escrow, &mut short),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn unpack_rejects_short_buffer() {
+        // 패닉으로 프로그램이 중단되지 않고 에러가 반환되어야 함
+        assert_eq!(
+            Escrow::unpack(&[0u8; 10]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            Escrow::unpack_unchecked(&[0u8; 10]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            Escrow::unpack_from_slice(&[0u8; 10]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn pack_round_trip_keeps_status() {
+        for status in [
+            EscrowStatus::Uninitialized,
+            EscrowStatus::Active,
+            EscrowStatus::Completed,
+            EscrowStatus::Cancelled,
+        ] {
+            let escrow = Escrow {
+                status,
+                initializer_pubkey: Pubkey::new_unique(),
+                expected_amount: 42,
+                ..Escrow::default()
+            };
+
+            let mut packed = [0u8; Escrow::LEN];
+            Escrow::pack_into_slice(&escrow, &mut packed);
+
+            let unpacked = Escrow::unpack_unchecked(&packed).unwrap();
+            assert_eq!(unpacked, escrow);
+        }
+    }
+
+    #[test]
+    fn pack_writes_version_first() {
+        let escrow = Escrow {
+            status: EscrowStatus::Active,
+            expected_amount: 42,
+            ..Escrow::default()
+        };
+
+        let mut packed = [0u8; Escrow::LEN];
+        Escrow::pack_into_slice(&escrow, &mut packed);
+        assert_eq!(packed[0], ESCROW_VERSION);
+        assert_eq!(packed[1], EscrowStatus::Active as u8);
+
+        let unpacked = Escrow::unpack(&packed).unwrap();
+        assert_eq!(unpacked.version, 1);
+        assert_eq!(unpacked, escrow);
+    }
+
+    #[test]
+    fn unpack_rejects_unknown_version() {
+        let escrow = Escrow {
+            status: EscrowStatus::Active,
+            ..Escrow::default()
+        };
+
+        let mut packed = [0u8; Escrow::LEN];
+        Escrow::pack_into_slice(&escrow, &mut packed);
+        packed[0] = ESCROW_VERSION + 1;
+        assert_eq!(
+            Escrow::unpack_unchecked(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        // 버전이 0인데 데이터가 남아 있어도 잘못된 데이터
+        packed[0] = 0;
+        assert_eq!(
+            Escrow::unpack_unchecked(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn unpack_rejects_non_canonical_bytes() {
+        assert_eq!(decode_bool(0), Ok(false));
+        assert_eq!(decode_bool(1), Ok(true));
+        assert_eq!(decode_bool(2), Err(ProgramError::InvalidAccountData));
+        assert_eq!(EscrowStatus::try_from(3), Ok(EscrowStatus::Cancelled));
+        assert_eq!(
+            EscrowStatus::try_from(4),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        let escrow = Escrow {
+            status: EscrowStatus::Active,
+            ..Escrow::default()
+        };
+        let mut packed = [0u8; Escrow::LEN];
+        Escrow::pack_into_slice(&escrow, &mut packed);
+
+        // is_native는 version부터 remaining_amount까지(229바이트) 다음 바이트
+        let mut bad_bool = packed;
+        bad_bool[229] = 2;
+        assert_eq!(
+            Escrow::unpack_unchecked(&bad_bool),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        // status는 version 다음 바이트
+        let mut bad_status = packed;
+        bad_status[1] = 4;
+        assert_eq!(
+            Escrow::unpack_unchecked(&bad_status),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn load_escrow_returns_initialized_escrow() {
+        let program_id = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let escrow = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: Pubkey::new_unique(),
+            expected_amount: 42,
+            ..Escrow::default()
+        };
+        let mut data = vec![0u8; Escrow::LEN];
+        Escrow::pack(escrow, &mut data).unwrap();
+        let mut lamports = 0;
+        let account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &program_id,
+            false,
+            0,
+        );
+
+        assert_eq!(load_escrow(&account, &program_id), Ok(escrow));
+        // 다른 프로그램 소유로 보고 불러오면 에러
+        assert_eq!(
+            load_escrow(&account, &Pubkey::new_unique()),
+            Err(ProgramError::IncorrectProgramId)
+        );
+    }
+
+    #[test]
+    fn rent_exempt_lamports_match_escrow_len() {
+        let rent = Rent::default();
+        assert_eq!(ESCROW_LEN, Escrow::LEN);
+        assert_eq!(
+            escrow_rent_exempt_lamports(&rent),
+            rent.minimum_balance(Escrow::LEN)
+        );
+
+        // 렌트비 면제 최소 금액이면 면제, 1 램포트라도 모자라면 면제가 아님
+        let lamports = escrow_rent_exempt_lamports(&rent);
+        assert!(rent.is_exempt(lamports, ESCROW_LEN));
+        assert!(!rent.is_exempt(lamports - 1, ESCROW_LEN));
+    }
+
+    #[test]
+    fn validate_accepts_well_formed_escrow() {
+        // 빈 에스크로는 확인할 것이 없음
+        assert_eq!(Escrow::default().validate(), Ok(()));
+
+        let escrow = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: Pubkey::new_unique(),
+            expected_amount: 42,
+            ..Escrow::default()
+        };
+        assert_eq!(escrow.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_inconsistent_escrow() {
+        let escrow = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: Pubkey::new_unique(),
+            expected_amount: 42,
+            ..Escrow::default()
+        };
+
+        // 이니셜라이저가 없는 초기화된 에스크로
+        let no_initializer = Escrow {
+            initializer_pubkey: Pubkey::default(),
+            ..escrow
+        };
+        assert_eq!(
+            no_initializer.validate(),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        // 예상 수량이 0인 초기화된 에스크로
+        let no_amount = Escrow {
+            expected_amount: 0,
+            ..escrow
+        };
+        assert_eq!(no_amount.validate(), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn new_escrow_is_active_and_valid() {
+        let initializer = Pubkey::new_unique();
+        let x_token_account = Pubkey::new_unique();
+        let receive_account = Pubkey::new_unique();
+        let escrow = Escrow::new(initializer, x_token_account, receive_account, 42, 254);
+
+        assert!(escrow.is_initialized());
+        assert_eq!(escrow.validate(), Ok(()));
+        assert_eq!(escrow.status, EscrowStatus::Active);
+        assert_eq!(escrow.initializer_pubkey, initializer);
+        assert_eq!(escrow.x_token_account_pubkey, x_token_account);
+        assert_eq!(
+            escrow.initializer_token_to_receive_account_pubkey,
+            receive_account
+        );
+        assert_eq!(escrow.expected_amount, 42);
+        assert_eq!(escrow.remaining_amount, 42);
+        assert_eq!(escrow.bump_seed, 254);
+        assert_eq!(escrow.pda_seed_pubkey, initializer);
+
+        // 압축했다가 풀어도 같은 에스크로
+        let mut packed = [0u8; Escrow::LEN];
+        Escrow::pack(escrow, &mut packed).unwrap();
+        assert_eq!(Escrow::unpack(&packed), Ok(escrow));
+    }
+
+    #[test]
+    fn escrow_ref_matches_unpacked_escrow() {
+        let escrow = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: Pubkey::new_unique(),
+            x_token_account_pubkey: Pubkey::new_unique(),
+            initializer_token_to_receive_account_pubkey: Pubkey::new_unique(),
+            expected_amount: 0x0102_0304_0506_0708,
+            remaining_amount: 42,
+            ..Escrow::default()
+        };
+        let mut packed = [0u8; Escrow::LEN];
+        Escrow::pack(escrow, &mut packed).unwrap();
+
+        let unpacked = Escrow::unpack(&packed).unwrap();
+        let escrow_ref = EscrowRef::new(&packed).unwrap();
+        assert_eq!(escrow_ref.initializer_pubkey(), unpacked.initializer_pubkey);
+        assert_eq!(escrow_ref.expected_amount(), unpacked.expected_amount);
+
+        // 오프셋이 Pack 레이아웃과 같아야 함
+        assert_eq!(EscrowRef::EXPECTED_AMOUNT_OFFSET, 98);
+        assert_eq!(
+            packed[EscrowRef::EXPECTED_AMOUNT_OFFSET..EscrowRef::EXPECTED_AMOUNT_OFFSET + 8],
+            0x0102_0304_0506_0708u64.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn escrow_ref_rejects_short_or_unwritten_data() {
+        assert_eq!(
+            EscrowRef::new(&[1u8; Escrow::LEN - 1]).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+        assert_eq!(
+            EscrowRef::new(&[0u8; Escrow::LEN]).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+    }
+
+    #[test]
+    fn save_then_load_round_trips_through_account_info() {
+        let program_id = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = vec![0u8; Escrow::LEN];
+        let account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &program_id,
+            false,
+            0,
+        );
+
+        let escrow = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: Pubkey::new_unique(),
+            expected_amount: 42,
+            remaining_amount: 42,
+            ..Escrow::default()
+        };
+        escrow.save(&account).unwrap();
+        assert_eq!(load_escrow(&account, &program_id), Ok(escrow));
+
+        // 다른 곳에서 데이터를 빌려 쓰는 중이면 에러 반환
+        let _borrowed = account.try_borrow_data().unwrap();
+        assert_eq!(
+            escrow.save(&account),
+            Err(ProgramError::AccountBorrowFailed)
+        );
+    }
+
+    #[test]
+    fn save_rejects_resized_account() {
+        let program_id = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let escrow = Escrow::new(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            42,
+            254,
+        );
+
+        // 다른 명령이 계정을 줄이거나 늘린 뒤라면 쓰지 않고 에러 반환
+        for len in [Escrow::LEN - 1, Escrow::LEN + 1] {
+            let mut lamports = 0;
+            let mut data = vec![0u8; len];
+            let account = AccountInfo::new(
+                &key,
+                false,
+                true,
+                &mut lamports,
+                &mut data,
+                &program_id,
+                false,
+                0,
+            );
+            assert_eq!(escrow.save(&account), Err(ProgramError::InvalidAccountData));
+            drop(account);
+            assert!(data.iter().all(|byte| *byte == 0));
+        }
+    }
+
+    #[test]
+    fn save_active_checks_status_and_size() {
+        let program_id = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let active = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: Pubkey::new_unique(),
+            expected_amount: 42,
+            remaining_amount: 42,
+            ..Escrow::default()
+        };
+
+        // Active가 아니면 쓰지 않고 에러 반환
+        let mut lamports = 0;
+        let mut data = vec![0u8; Escrow::LEN];
+        let account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &program_id,
+            false,
+            0,
+        );
+        assert_eq!(
+            Escrow::default().save_active(&account),
+            Err(ProgramError::UninitializedAccount)
+        );
+        assert!(account.data.borrow().iter().all(|byte| *byte == 0));
+
+        active.save_active(&account).unwrap();
+        assert_eq!(load_escrow(&account, &program_id), Ok(active));
+
+        // 크기가 바뀐 계정에는 Active여도 쓰지 않음
+        let mut lamports = 0;
+        let mut data = vec![0u8; Escrow::LEN + 1];
+        let account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &program_id,
+            false,
+            0,
+        );
+        assert_eq!(
+            active.save_active(&account),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn vested_amount_is_linear_between_created_at_and_expiry() {
+        let escrow = Escrow {
+            vesting: true,
+            vesting_total: 1_000,
+            created_at: 1_000,
+            expiry_ts: 2_000,
+            ..Escrow::default()
+        };
+
+        // 0%, 50%, 100% 경과
+        assert_eq!(escrow.vested_amount(1_000), Ok(0));
+        assert_eq!(escrow.vested_amount(1_500), Ok(500));
+        assert_eq!(escrow.vested_amount(2_000), Ok(1_000));
+
+        // 시작 전과 끝난 뒤에는 범위 밖으로 나가지 않음
+        assert_eq!(escrow.vested_amount(0), Ok(0));
+        assert_eq!(escrow.vested_amount(i64::MAX), Ok(1_000));
+
+        // 나누어 떨어지지 않으면 내림
+        assert_eq!(escrow.vested_amount(1_333), Ok(333));
+    }
+
+    #[test]
+    fn vested_amount_does_not_overflow_for_large_totals() {
+        // vesting_total * 경과 시간이 u64를 넘어도 u128로 계산
+        let escrow = Escrow {
+            vesting: true,
+            vesting_total: u64::MAX,
+            created_at: 0,
+            expiry_ts: 1_000_000,
+            ..Escrow::default()
+        };
+        assert_eq!(escrow.vested_amount(500_000), Ok(u64::MAX / 2));
+    }
+
+    #[test]
+    fn pack_active_rejects_inactive_escrow() {
+        let mut packed = [0u8; Escrow::LEN];
+        assert_eq!(
+            Escrow::default().pack_active(&mut packed),
+            Err(ProgramError::UninitializedAccount)
+        );
+        // 에러가 나면 아무것도 쓰지 않음
+        assert_eq!(packed, [0u8; Escrow::LEN]);
+
+        let escrow = Escrow {
+            status: EscrowStatus::Active,
+            initializer_pubkey: Pubkey::new_unique(),
+            expected_amount: 42,
+            ..Escrow::default()
+        };
+        escrow.pack_active(&mut packed).unwrap();
+        assert_eq!(Escrow::unpack(&packed), Ok(escrow));
+    }
+
+    #[test]
+    fn load_escrow_rejects_inconsistent_escrow() {
+        let program_id = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        // 상태는 Active인데 이니셜라이저와 예상 수량이 비어 있음
+        let escrow = Escrow {
+            status: EscrowStatus::Active,
+            ..Escrow::default()
+        };
+        let mut data = vec![0u8; Escrow::LEN];
+        Escrow::pack(escrow, &mut data).unwrap();
+        let mut lamports = 0;
+        let account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &program_id,
+            false,
+            0,
+        );
+
+        assert_eq!(
+            load_escrow(&account, &program_id),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn load_escrow_rejects_uninitialized_escrow() {
+        let program_id = Pubkey::new_unique();
+        let key = Pubkey::new_unique();
+        let mut data = vec![0u8; Escrow::LEN];
+        let mut lamports = 0;
+        let account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &program_id,
+            false,
+            0,
+        );
+
+        assert_eq!(
+            load_escrow(&account, &program_id),
+            Err(ProgramError::UninitializedAccount)
+        );
+    }
+
+    #[test]
+    fn registry_pack_round_trip() {
+        let registry = Registry {
+            bump_seed: 254,
+            escrows: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+        };
+        let mut data = vec![0u8; Registry::space(2)];
+        registry.pack(&mut data).unwrap();
+
+        assert_eq!(data.len(), Registry::HEADER_LEN + 64);
+        assert_eq!(data[0], 254);
+        assert_eq!(&data[1..5], &2u32.to_le_bytes());
+        assert_eq!(Registry::unpack(&data), Ok(registry));
+
+        // 빈 레지스트리는 헤더만
+        let mut data = vec![0u8; Registry::space(0)];
+        Registry::default().pack(&mut data).unwrap();
+        assert_eq!(Registry::unpack(&data), Ok(Registry::default()));
+    }
+
+    #[test]
+    fn registry_rejects_size_that_does_not_match_count() {
+        let registry = Registry {
+            bump_seed: 1,
+            escrows: vec![Pubkey::new_unique()],
+        };
+
+        // 목록보다 작거나 큰 계정에는 쓰지 않음
+        assert_eq!(
+            registry.pack(&mut [0u8; Registry::HEADER_LEN]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        let mut data = vec![0u8; Registry::space(1)];
+        registry.pack(&mut data).unwrap();
+
+        // 개수와 크기가 다르거나 헤더보다 짧은 데이터
+        data.push(0);
+        assert_eq!(
+            Registry::unpack(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            Registry::unpack(&data[..4]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn registry_insert_and_remove_keep_order_without_duplicates() {
+        let (first, second, third) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let mut registry = Registry::default();
+        registry.insert(first);
+        registry.insert(second);
+        registry.insert(first);
+        registry.insert(third);
+        assert_eq!(registry.escrows, vec![first, second, third]);
+
+        assert!(registry.remove(&second));
+        assert!(!registry.remove(&second));
+        assert_eq!(registry.escrows, vec![first, third]);
+    }
+
+    fn escrow_with_expiry(expiry_ts: i64) -> Escrow {
+        Escrow {
+            expiry_ts,
+            ..Escrow::default()
+        }
+    }
+
+    #[test]
+    fn is_expired_at_boundaries() {
+        let escrow = escrow_with_expiry(1_000);
+        assert!(!escrow.is_expired(999));
+        assert!(escrow.is_expired(1_000));
+        assert!(escrow.is_expired(1_001));
+    }
+
+    #[test]
+    fn is_expired_never_when_expiry_is_zero() {
+        let escrow = escrow_with_expiry(0);
+        assert!(!escrow.is_expired(-1));
+        assert!(!escrow.is_expired(0));
+        assert!(!escrow.is_expired(i64::MAX));
+    }
+
+    #[test]
+    fn is_stale_after_max_age() {
+        let escrow = Escrow {
+            created_at: -MAX_ESCROW_AGE_SECS - 1,
+            ..Escrow::default()
+        };
+        assert!(!escrow.is_stale(-1));
+        assert!(escrow.is_stale(0));
+
+        let escrow = Escrow {
+            created_at: 0,
+            ..Escrow::default()
+        };
+        assert!(!escrow.is_stale(MAX_ESCROW_AGE_SECS));
     }
 }
@@ -0,0 +1,1302 @@
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program::invoke_signed,
+    program_option::COption,
+};
+use solana_program_test::{processor, BanksClient, BanksClientError, ProgramTest};
+#[cfg(feature = "rent-sysvar-account")]
+use solana_sdk::sysvar;
+use solana_sdk::{
+    clock::Clock,
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+    system_instruction, system_program,
+    transaction::Transaction,
+};
+use spl_token::state::{Account as TokenAccount, Mint};
+use test_escrow::{
+    intruction::amount_to_bytes,
+    pda::{escrow_pda, registry_pda},
+    processor::Processor,
+    state::{escrow_rent_exempt_lamports, Escrow, EscrowStatus, Registry, ESCROW_LEN},
+};
+
+// 에스크로 프로그램과 토큰 프로그램이 올라간 테스트 은행
+struct TestContext {
+    banks_client: BanksClient,
+    payer: Keypair,
+    recent_blockhash: Hash,
+    rent: Rent,
+}
+
+impl TestContext {
+    async fn new(program_id: &Pubkey) -> Self {
+        let program_test =
+            ProgramTest::new("test_escrow", *program_id, processor!(Processor::process));
+        Self::start(program_test).await
+    }
+
+    // 에스크로 프로그램을 CPI로 호출하는 라우터 프로그램도 함께 올림
+    async fn new_with_router(program_id: &Pubkey, router_id: &Pubkey) -> Self {
+        let mut program_test =
+            ProgramTest::new("test_escrow", *program_id, processor!(Processor::process));
+        program_test.add_program("router", *router_id, processor!(process_router));
+        Self::start(program_test).await
+    }
+
+    // 은행을 slot까지 건너뛴 뒤 시작 (Clock이 바뀐 상태에서 테스트)
+    async fn new_warped(program_id: &Pubkey, slot: u64) -> Self {
+        let program_test =
+            ProgramTest::new("test_escrow", *program_id, processor!(Processor::process));
+        let mut context = program_test.start_with_context().await;
+        context.warp_to_slot(slot).unwrap();
+
+        let mut banks_client = context.banks_client;
+        let rent = banks_client.get_rent().await.unwrap();
+        let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+
+        Self {
+            banks_client,
+            payer: context.payer,
+            recent_blockhash,
+            rent,
+        }
+    }
+
+    async fn start(program_test: ProgramTest) -> Self {
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let rent = banks_client.get_rent().await.unwrap();
+
+        Self {
+            banks_client,
+            payer,
+            recent_blockhash,
+            rent,
+        }
+    }
+
+    // payer와 추가 서명자로 트랜잭션을 보냄
+    async fn process(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), BanksClientError> {
+        let mut all_signers = vec![&self.payer];
+        all_signers.extend_from_slice(signers);
+
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer.pubkey()),
+            &all_signers[..],
+            self.recent_blockhash,
+        );
+        self.banks_client.process_transaction(transaction).await
+    }
+
+    async fn create_mint(&mut self) -> Pubkey {
+        let mint = Keypair::new();
+        let instructions = [
+            system_instruction::create_account(
+                &self.payer.pubkey(),
+                &mint.pubkey(),
+                self.rent.minimum_balance(Mint::LEN),
+                Mint::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_mint(
+                &spl_token::id(),
+                &mint.pubkey(),
+                &self.payer.pubkey(),
+                None,
+                0,
+            )
+            .unwrap(),
+        ];
+        self.process(&instructions, &[&mint]).await.unwrap();
+        mint.pubkey()
+    }
+
+    async fn create_token_account(&mut self, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+        let account = Keypair::new();
+        let mut instructions = vec![
+            system_instruction::create_account(
+                &self.payer.pubkey(),
+                &account.pubkey(),
+                self.rent.minimum_balance(TokenAccount::LEN),
+                TokenAccount::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                &account.pubkey(),
+                mint,
+                owner,
+            )
+            .unwrap(),
+        ];
+        if amount > 0 {
+            instructions.push(
+                spl_token::instruction::mint_to(
+                    &spl_token::id(),
+                    mint,
+                    &account.pubkey(),
+                    &self.payer.pubkey(),
+                    &[],
+                    amount,
+                )
+                .unwrap(),
+            );
+        }
+        self.process(&instructions, &[&account]).await.unwrap();
+        account.pubkey()
+    }
+
+    // amount 램포트를 감싼 랩트 SOL 토큰 계정
+    // 네이티브 계정은 초기화할 때 렌트비 위의 램포트가 잔액이 됨
+    async fn create_wsol_account(&mut self, owner: &Pubkey, amount: u64) -> Pubkey {
+        let account = Keypair::new();
+        let instructions = [
+            system_instruction::create_account(
+                &self.payer.pubkey(),
+                &account.pubkey(),
+                self.rent.minimum_balance(TokenAccount::LEN) + amount,
+                TokenAccount::LEN as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                &account.pubkey(),
+                &spl_token::native_mint::id(),
+                owner,
+            )
+            .unwrap(),
+        ];
+        self.process(&instructions, &[&account]).await.unwrap();
+        account.pubkey()
+    }
+
+    // 렌트비가 면제되는 빈 에스크로 계정
+    async fn create_escrow_account(&mut self, program_id: &Pubkey) -> Pubkey {
+        let account = Keypair::new();
+        let instructions = [system_instruction::create_account(
+            &self.payer.pubkey(),
+            &account.pubkey(),
+            escrow_rent_exempt_lamports(&self.rent),
+            ESCROW_LEN as u64,
+            program_id,
+        )];
+        self.process(&instructions, &[&account]).await.unwrap();
+        account.pubkey()
+    }
+
+    async fn account_data(&mut self, pubkey: &Pubkey) -> Vec<u8> {
+        self.banks_client
+            .get_account(*pubkey)
+            .await
+            .unwrap()
+            .unwrap()
+            .data
+    }
+}
+
+// 라우터 PDA의 시드
+const ROUTER_SEED: &[u8] = b"router";
+
+// 테스트용 라우터 프로그램
+// 받은 명령 데이터를 그대로 에스크로 프로그램의 InitEscrow로 넘기고
+// 자기 PDA(이니셜라이저)로 invoke_signed 서명함
+// 계정: InitEscrow의 계정들 (0번은 라우터 PDA), 마지막은 에스크로 프로그램
+fn process_router(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let (escrow_program, init_accounts) = accounts.split_last().unwrap();
+    let (router_pda, bump_seed) = Pubkey::find_program_address(&[ROUTER_SEED], program_id);
+
+    let account_metas = init_accounts
+        .iter()
+        .map(|account| AccountMeta {
+            pubkey: *account.key,
+            is_signer: account.is_signer || *account.key == router_pda,
+            is_writable: account.is_writable,
+        })
+        .collect();
+    let init_ix = Instruction {
+        program_id: *escrow_program.key,
+        accounts: account_metas,
+        data: instruction_data.to_vec(),
+    };
+    invoke_signed(&init_ix, init_accounts, &[&[ROUTER_SEED, &[bump_seed]]])
+}
+
+fn init_escrow_ix(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    temp_token_account: &Pubkey,
+    receive_account: &Pubkey,
+    escrow_account: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    // 태그(0) + 금액 + 만료 시각(0) + 논스(0) + 수수료(0)
+    let mut data = vec![0];
+    data.extend_from_slice(&amount_to_bytes(amount));
+    data.extend_from_slice(&0i64.to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*initializer, true),
+            AccountMeta::new(*temp_token_account, false),
+            AccountMeta::new_readonly(*receive_account, false),
+            AccountMeta::new(*escrow_account, false),
+            #[cfg(feature = "rent-sysvar-account")]
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data,
+    }
+}
+
+#[tokio::test]
+async fn init_escrow_locks_temp_account() {
+    let program_id = Pubkey::new_unique();
+    let mut context = TestContext::new(&program_id).await;
+    let initializer = context.payer.pubkey();
+
+    let mint_x = context.create_mint().await;
+    let mint_y = context.create_mint().await;
+    let temp = context
+        .create_token_account(&mint_x, &initializer, 100)
+        .await;
+    let receive = context.create_token_account(&mint_y, &initializer, 0).await;
+    let escrow = context.create_escrow_account(&program_id).await;
+
+    context
+        .process(
+            &[init_escrow_ix(
+                &program_id,
+                &initializer,
+                &temp,
+                &receive,
+                &escrow,
+                50,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+    let (pda, bump_seed) = escrow_pda(&program_id, &initializer, 0);
+
+    let escrow_info = Escrow::unpack(&context.account_data(&escrow).await).unwrap();
+    assert_eq!(escrow_info.status, EscrowStatus::Active);
+    assert_eq!(escrow_info.initializer_pubkey, initializer);
+    assert_eq!(escrow_info.x_token_account_pubkey, temp);
+    assert_eq!(
+        escrow_info.initializer_token_to_receive_account_pubkey,
+        receive
+    );
+    assert_eq!(escrow_info.expected_amount, 50);
+    assert_eq!(escrow_info.x_token_mint, mint_x);
+    assert_eq!(escrow_info.bump_seed, bump_seed);
+
+    // 임시 토큰 계정의 소유자가 PDA로 바뀌었는지 확인
+    let temp_info = TokenAccount::unpack(&context.account_data(&temp).await).unwrap();
+    assert_eq!(temp_info.owner, pda);
+    assert_eq!(temp_info.amount, 100);
+}
+
+#[tokio::test]
+async fn init_escrow_records_bank_clock_as_created_at() {
+    let program_id = Pubkey::new_unique();
+    let mut context = TestContext::new_warped(&program_id, 1_000).await;
+    let initializer = context.payer.pubkey();
+
+    let mint_x = context.create_mint().await;
+    let mint_y = context.create_mint().await;
+    let temp = context
+        .create_token_account(&mint_x, &initializer, 100)
+        .await;
+    let receive = context.create_token_account(&mint_y, &initializer, 0).await;
+    let escrow = context.create_escrow_account(&program_id).await;
+
+    context
+        .process(
+            &[init_escrow_ix(
+                &program_id,
+                &initializer,
+                &temp,
+                &receive,
+                &escrow,
+                50,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+    let clock = context.banks_client.get_sysvar::<Clock>().await.unwrap();
+    assert_eq!(clock.slot, 1_000);
+
+    let escrow_info = Escrow::unpack(&context.account_data(&escrow).await).unwrap();
+    assert_eq!(escrow_info.created_at, clock.unix_timestamp);
+}
+
+#[tokio::test]
+async fn cancel_returns_temp_account_and_keeps_escrow() {
+    let program_id = Pubkey::new_unique();
+    let mut context = TestContext::new(&program_id).await;
+    let initializer = context.payer.pubkey();
+
+    let mint_x = context.create_mint().await;
+    let mint_y = context.create_mint().await;
+    let temp = context
+        .create_token_account(&mint_x, &initializer, 100)
+        .await;
+    let receive = context.create_token_account(&mint_y, &initializer, 0).await;
+    let escrow = context.create_escrow_account(&program_id).await;
+
+    context
+        .process(
+            &[init_escrow_ix(
+                &program_id,
+                &initializer,
+                &temp,
+                &receive,
+                &escrow,
+                50,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+    let (pda, _) = escrow_pda(&program_id, &initializer, 0);
+    let cancel_ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(initializer, true),
+            AccountMeta::new(temp, false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(pda, false),
+        ],
+        data: vec![2],
+    };
+    context.process(&[cancel_ix], &[]).await.unwrap();
+
+    // 임시 토큰 계정의 소유권이 이니셜라이저에게 돌아옴
+    let temp_info = TokenAccount::unpack(&context.account_data(&temp).await).unwrap();
+    assert_eq!(temp_info.owner, initializer);
+    assert_eq!(temp_info.amount, 100);
+
+    // 에스크로 계정은 닫히지 않고 취소 상태로 남음
+    let escrow_info = Escrow::unpack(&context.account_data(&escrow).await).unwrap();
+    assert_eq!(escrow_info.status, EscrowStatus::Cancelled);
+}
+
+// InitEscrow에 논스를 넣고 이니셜라이저의 레지스트리와 시스템 프로그램을 붙인 명령
+fn init_escrow_with_registry_ix(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    temp_token_account: &Pubkey,
+    receive_account: &Pubkey,
+    escrow_account: &Pubkey,
+    nonce: u64,
+) -> Instruction {
+    let mut init_ix = init_escrow_ix(
+        program_id,
+        initializer,
+        temp_token_account,
+        receive_account,
+        escrow_account,
+        50,
+    );
+    // 논스는 태그(1) + 금액(8) + 만료 시각(8) 뒤
+    init_ix.data[17..25].copy_from_slice(&nonce.to_le_bytes());
+    // 레지스트리를 만들거나 늘릴 때 렌트비를 내므로 writable
+    init_ix.accounts[0].is_writable = true;
+    init_ix.accounts.extend([
+        AccountMeta::new(registry_pda(program_id, initializer).0, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]);
+    init_ix
+}
+
+#[tokio::test]
+async fn registry_tracks_open_escrows_until_cancelled() {
+    let program_id = Pubkey::new_unique();
+    let mut context = TestContext::new(&program_id).await;
+    let initializer = context.payer.pubkey();
+    let (registry, _) = registry_pda(&program_id, &initializer);
+
+    // 같은 이니셜라이저가 레지스트리를 넘겨 에스크로 두 개를 엶
+    let mint_x = context.create_mint().await;
+    let mint_y = context.create_mint().await;
+    let receive = context.create_token_account(&mint_y, &initializer, 0).await;
+    let mut temps = Vec::new();
+    let mut escrows = Vec::new();
+    for nonce in 0..2 {
+        let temp = context
+            .create_token_account(&mint_x, &initializer, 100)
+            .await;
+        let escrow = context.create_escrow_account(&program_id).await;
+        context
+            .process(
+                &[init_escrow_with_registry_ix(
+                    &program_id,
+                    &initializer,
+                    &temp,
+                    &receive,
+                    &escrow,
+                    nonce,
+                )],
+                &[],
+            )
+            .await
+            .unwrap();
+        temps.push(temp);
+        escrows.push(escrow);
+    }
+
+    // 처음 연 에스크로가 레지스트리 계정을 만들고 두 번째가 크기를 늘림
+    let account = context
+        .banks_client
+        .get_account(registry)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.owner, program_id);
+    assert_eq!(account.data.len(), Registry::space(2));
+    assert!(account.lamports >= context.rent.minimum_balance(Registry::space(2)));
+    assert_eq!(Registry::unpack(&account.data).unwrap().escrows, escrows);
+
+    // 첫 번째 에스크로를 취소하면서 레지스트리를 넘기면 목록에서 빠짐
+    let (pda, _) = escrow_pda(&program_id, &initializer, 0);
+    let cancel_ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(initializer, true),
+            AccountMeta::new(temps[0], false),
+            AccountMeta::new(escrows[0], false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(pda, false),
+            AccountMeta::new(registry, false),
+        ],
+        data: vec![2],
+    };
+    context.process(&[cancel_ix], &[]).await.unwrap();
+
+    let data = context.account_data(&registry).await;
+    assert_eq!(data.len(), Registry::space(1));
+    assert_eq!(Registry::unpack(&data).unwrap().escrows, vec![escrows[1]]);
+}
+
+#[tokio::test]
+async fn registry_is_created_even_if_address_was_prefunded() {
+    let program_id = Pubkey::new_unique();
+    let mut context = TestContext::new(&program_id).await;
+    let initializer = context.payer.pubkey();
+    let (registry, _) = registry_pda(&program_id, &initializer);
+
+    // 누군가 레지스트리 주소로 램포트를 미리 보내 둠
+    // (새 계정은 렌트비 면제 이상이어야 하므로 데이터 없는 계정의 최소 램포트)
+    let prefund = context.rent.minimum_balance(0);
+    context
+        .process(
+            &[system_instruction::transfer(
+                &initializer,
+                &registry,
+                prefund,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+    let mint_x = context.create_mint().await;
+    let mint_y = context.create_mint().await;
+    let receive = context.create_token_account(&mint_y, &initializer, 0).await;
+    let temp = context
+        .create_token_account(&mint_x, &initializer, 100)
+        .await;
+    let escrow = context.create_escrow_account(&program_id).await;
+    context
+        .process(
+            &[init_escrow_with_registry_ix(
+                &program_id,
+                &initializer,
+                &temp,
+                &receive,
+                &escrow,
+                0,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+    let account = context
+        .banks_client
+        .get_account(registry)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.owner, program_id);
+    assert_eq!(account.data.len(), Registry::space(1));
+    assert!(account.lamports >= context.rent.minimum_balance(Registry::space(1)));
+    assert_eq!(
+        Registry::unpack(&account.data).unwrap().escrows,
+        vec![escrow]
+    );
+}
+
+#[tokio::test]
+async fn init_escrow_with_seed_creates_and_initializes_escrow() {
+    let program_id = Pubkey::new_unique();
+    let mut context = TestContext::new(&program_id).await;
+    let initializer = context.payer.pubkey();
+
+    let mint_x = context.create_mint().await;
+    let mint_y = context.create_mint().await;
+    let temp = context
+        .create_token_account(&mint_x, &initializer, 100)
+        .await;
+    let receive = context.create_token_account(&mint_y, &initializer, 0).await;
+
+    // 에스크로 계정을 미리 만들지 않고 시드 주소만 계산함
+    let seed = "escrow-0";
+    let escrow = Pubkey::create_with_seed(&initializer, seed, &program_id).unwrap();
+
+    // 태그(7) + 금액 + 만료 시각(0) + 논스(0) + 수수료(0) + 시드
+    let mut data = vec![7];
+    data.extend_from_slice(&50u64.to_le_bytes());
+    data.extend_from_slice(&0i64.to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+    data.extend_from_slice(seed.as_bytes());
+
+    let init_ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(initializer, true),
+            AccountMeta::new(temp, false),
+            AccountMeta::new_readonly(receive, false),
+            AccountMeta::new(escrow, false),
+            #[cfg(feature = "rent-sysvar-account")]
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    };
+    context.process(&[init_ix], &[]).await.unwrap();
+
+    // 한 명령 안에서 렌트비 면제 계정이 만들어지고 바로 초기화됨
+    let account = context
+        .banks_client
+        .get_account(escrow)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.owner, program_id);
+    assert_eq!(account.data.len(), ESCROW_LEN);
+    assert_eq!(account.lamports, escrow_rent_exempt_lamports(&context.rent));
+
+    let escrow_info = Escrow::unpack(&account.data).unwrap();
+    assert_eq!(escrow_info.status, EscrowStatus::Active);
+    assert_eq!(escrow_info.x_token_account_pubkey, temp);
+    assert_eq!(escrow_info.expected_amount, 50);
+}
+
+#[tokio::test]
+async fn query_escrow_returns_amount_status_and_initializer() {
+    let program_id = Pubkey::new_unique();
+    let mut context = TestContext::new(&program_id).await;
+    let initializer = context.payer.pubkey();
+
+    let mint_x = context.create_mint().await;
+    let mint_y = context.create_mint().await;
+    let temp = context
+        .create_token_account(&mint_x, &initializer, 100)
+        .await;
+    let receive = context.create_token_account(&mint_y, &initializer, 0).await;
+    let escrow = context.create_escrow_account(&program_id).await;
+
+    context
+        .process(
+            &[init_escrow_ix(
+                &program_id,
+                &initializer,
+                &temp,
+                &receive,
+                &escrow,
+                50,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+    // 계정을 바꾸지 않는 명령이므로 시뮬레이션으로 리턴 데이터를 읽음
+    let query_ix = Instruction {
+        program_id,
+        accounts: vec![AccountMeta::new_readonly(escrow, false)],
+        data: vec![6],
+    };
+    let transaction = Transaction::new_signed_with_payer(
+        &[query_ix],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        context.recent_blockhash,
+    );
+    let simulation = context
+        .banks_client
+        .simulate_transaction(transaction)
+        .await
+        .unwrap();
+    simulation.result.unwrap().unwrap();
+
+    let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, program_id);
+
+    // 예상 금액(8바이트) + 상태(1바이트) + 이니셜라이저(32바이트)
+    assert_eq!(return_data.data.len(), 41);
+    assert_eq!(&return_data.data[..8], &50u64.to_le_bytes());
+    assert_eq!(return_data.data[8], EscrowStatus::Active as u8);
+    assert_eq!(&return_data.data[9..], initializer.as_ref());
+}
+
+#[tokio::test]
+async fn router_program_initializes_escrow_with_pda_signer() {
+    let program_id = Pubkey::new_unique();
+    let router_id = Pubkey::new_unique();
+    let mut context = TestContext::new_with_router(&program_id, &router_id).await;
+
+    // 이니셜라이저는 개인키가 없는 라우터의 PDA
+    let (router_pda, _) = Pubkey::find_program_address(&[ROUTER_SEED], &router_id);
+
+    let mint_x = context.create_mint().await;
+    let mint_y = context.create_mint().await;
+    let temp = context
+        .create_token_account(&mint_x, &router_pda, 100)
+        .await;
+    let receive = context.create_token_account(&mint_y, &router_pda, 0).await;
+    let escrow = context.create_escrow_account(&program_id).await;
+
+    // InitEscrow 명령의 이니셜라이저 서명을 빼고 에스크로 프로그램을 붙여 라우터로 보냄
+    let mut router_ix = init_escrow_ix(&program_id, &router_pda, &temp, &receive, &escrow, 50);
+    router_ix.program_id = router_id;
+    router_ix.accounts[0].is_signer = false;
+    router_ix
+        .accounts
+        .push(AccountMeta::new_readonly(program_id, false));
+    context.process(&[router_ix], &[]).await.unwrap();
+
+    let escrow_info = Escrow::unpack(&context.account_data(&escrow).await).unwrap();
+    assert_eq!(escrow_info.status, EscrowStatus::Active);
+    assert_eq!(escrow_info.initializer_pubkey, router_pda);
+
+    // PDA의 서명이 set_authority CPI까지 이어져 임시 토큰 계정이 에스크로 PDA로 넘어감
+    let (pda, _) = escrow_pda(&program_id, &router_pda, 0);
+    let temp_info = TokenAccount::unpack(&context.account_data(&temp).await).unwrap();
+    assert_eq!(temp_info.owner, pda);
+}
+
+#[tokio::test]
+async fn exchange_unwraps_wrapped_sol_for_taker() {
+    let program_id = Pubkey::new_unique();
+    let mut context = TestContext::new(&program_id).await;
+    let initializer = context.payer.pubkey();
+    let taker = Keypair::new();
+
+    // 이니셜라이저는 랩트 SOL 1_000_000 램포트를 맡기고 토큰 Y 50개를 기다림
+    let mint_y = context.create_mint().await;
+    let temp = context.create_wsol_account(&initializer, 1_000_000).await;
+    let receive = context.create_token_account(&mint_y, &initializer, 0).await;
+    let escrow = context.create_escrow_account(&program_id).await;
+    context
+        .process(
+            &[init_escrow_ix(
+                &program_id,
+                &initializer,
+                &temp,
+                &receive,
+                &escrow,
+                50,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+    // 테이커는 토큰 Y와 랩트 SOL을 잠깐 받을 빈 계정을 준비함
+    let taker_send = context
+        .create_token_account(&mint_y, &taker.pubkey(), 50)
+        .await;
+    let taker_receive = context.create_wsol_account(&taker.pubkey(), 0).await;
+    let fee_account = context.create_token_account(&mint_y, &initializer, 0).await;
+    let (pda, _) = escrow_pda(&program_id, &initializer, 0);
+
+    // 태그(1) + 금액 + unwrap_sol(1)
+    let mut data = vec![1];
+    data.extend_from_slice(&50u64.to_le_bytes());
+    data.push(1);
+    let exchange_ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(taker.pubkey(), true),
+            AccountMeta::new(taker_send, false),
+            AccountMeta::new(taker_receive, false),
+            AccountMeta::new(temp, false),
+            AccountMeta::new(initializer, false),
+            AccountMeta::new(receive, false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(pda, false),
+            AccountMeta::new(fee_account, false),
+        ],
+        data,
+    };
+    context.process(&[exchange_ix], &[&taker]).await.unwrap();
+
+    // 랩트 SOL 계정은 닫히고 잔액과 렌트비가 테이커의 램포트로 들어옴
+    let taker_lamports = context
+        .banks_client
+        .get_balance(taker.pubkey())
+        .await
+        .unwrap();
+    assert_eq!(
+        taker_lamports,
+        1_000_000 + context.rent.minimum_balance(TokenAccount::LEN)
+    );
+    assert!(context
+        .banks_client
+        .get_account(taker_receive)
+        .await
+        .unwrap()
+        .is_none());
+
+    let escrow_info = Escrow::unpack(&context.account_data(&escrow).await).unwrap();
+    assert_eq!(escrow_info.status, EscrowStatus::Completed);
+}
+
+fn init_escrow_with_delegate_ix(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    source_token_account: &Pubkey,
+    receive_account: &Pubkey,
+    escrow_account: &Pubkey,
+    amount: u64,
+    deposit_amount: u64,
+) -> Instruction {
+    // 태그(13) + 금액 + 위임할 수량 + 만료 시각(0) + 논스(0) + 수수료(0)
+    let mut data = vec![13];
+    data.extend_from_slice(&amount_to_bytes(amount));
+    data.extend_from_slice(&amount_to_bytes(deposit_amount));
+    data.extend_from_slice(&0i64.to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+
+    let (pda, _) = escrow_pda(program_id, initializer, 0);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*initializer, true),
+            AccountMeta::new(*source_token_account, false),
+            AccountMeta::new_readonly(*receive_account, false),
+            AccountMeta::new(*escrow_account, false),
+            AccountMeta::new_readonly(pda, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data,
+    }
+}
+
+#[tokio::test]
+async fn delegate_custody_approves_pda_and_exchange_spends_allowance() {
+    let program_id = Pubkey::new_unique();
+    let mut context = TestContext::new(&program_id).await;
+    let initializer = context.payer.pubkey();
+    let taker = Keypair::new();
+
+    // 이니셜라이저는 토큰 X 100개 중 60개만 PDA에게 위임하고 토큰 Y 50개를 기다림
+    let mint_x = context.create_mint().await;
+    let mint_y = context.create_mint().await;
+    let source = context
+        .create_token_account(&mint_x, &initializer, 100)
+        .await;
+    let receive = context.create_token_account(&mint_y, &initializer, 0).await;
+    let escrow = context.create_escrow_account(&program_id).await;
+    context
+        .process(
+            &[init_escrow_with_delegate_ix(
+                &program_id,
+                &initializer,
+                &source,
+                &receive,
+                &escrow,
+                50,
+                60,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+    // 소유자는 그대로이고 PDA가 정확히 60개만큼의 위임자가 됨
+    let (pda, _) = escrow_pda(&program_id, &initializer, 0);
+    let source_info = TokenAccount::unpack(&context.account_data(&source).await).unwrap();
+    assert_eq!(source_info.owner, initializer);
+    assert_eq!(source_info.delegate, COption::Some(pda));
+    assert_eq!(source_info.delegated_amount, 60);
+
+    let escrow_info = Escrow::unpack(&context.account_data(&escrow).await).unwrap();
+    assert!(escrow_info.delegate_custody);
+    assert_eq!(escrow_info.x_token_account_pubkey, source);
+
+    let taker_send = context
+        .create_token_account(&mint_y, &taker.pubkey(), 50)
+        .await;
+    let taker_receive = context
+        .create_token_account(&mint_x, &taker.pubkey(), 0)
+        .await;
+    let fee_account = context.create_token_account(&mint_y, &initializer, 0).await;
+
+    // 태그(1) + 금액 + unwrap_sol(0)
+    let mut data = vec![1];
+    data.extend_from_slice(&amount_to_bytes(50));
+    data.push(0);
+    let exchange_ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(taker.pubkey(), true),
+            AccountMeta::new(taker_send, false),
+            AccountMeta::new(taker_receive, false),
+            AccountMeta::new(source, false),
+            AccountMeta::new(initializer, false),
+            AccountMeta::new(receive, false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(pda, false),
+            AccountMeta::new(fee_account, false),
+        ],
+        data,
+    };
+    context.process(&[exchange_ix], &[&taker]).await.unwrap();
+
+    // 위임한 60개만 테이커에게 가고 이니셜라이저의 계정은 닫히지 않음
+    let taker_receive_info =
+        TokenAccount::unpack(&context.account_data(&taker_receive).await).unwrap();
+    assert_eq!(taker_receive_info.amount, 60);
+    let source_info = TokenAccount::unpack(&context.account_data(&source).await).unwrap();
+    assert_eq!(source_info.owner, initializer);
+    assert_eq!(source_info.amount, 40);
+    assert_eq!(source_info.delegated_amount, 0);
+    assert_eq!(source_info.delegate, COption::None);
+
+    let escrow_info = Escrow::unpack(&context.account_data(&escrow).await).unwrap();
+    assert_eq!(escrow_info.status, EscrowStatus::Completed);
+}
+
+#[tokio::test]
+async fn delegate_exchange_fails_after_initializer_reapproves_less() {
+    let program_id = Pubkey::new_unique();
+    let mut context = TestContext::new(&program_id).await;
+    let initializer = context.payer.pubkey();
+    let taker = Keypair::new();
+
+    let mint_x = context.create_mint().await;
+    let mint_y = context.create_mint().await;
+    let source = context
+        .create_token_account(&mint_x, &initializer, 100)
+        .await;
+    let receive = context.create_token_account(&mint_y, &initializer, 0).await;
+    let escrow = context.create_escrow_account(&program_id).await;
+    context
+        .process(
+            &[init_escrow_with_delegate_ix(
+                &program_id,
+                &initializer,
+                &source,
+                &receive,
+                &escrow,
+                50,
+                60,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+    // 초기화 뒤에 이니셜라이저가 PDA에게 1개만 다시 승인함
+    let (pda, _) = escrow_pda(&program_id, &initializer, 0);
+    context
+        .process(
+            &[spl_token::instruction::approve(
+                &spl_token::id(),
+                &source,
+                &pda,
+                &initializer,
+                &[],
+                1,
+            )
+            .unwrap()],
+            &[],
+        )
+        .await
+        .unwrap();
+
+    let taker_send = context
+        .create_token_account(&mint_y, &taker.pubkey(), 50)
+        .await;
+    let taker_receive = context
+        .create_token_account(&mint_x, &taker.pubkey(), 0)
+        .await;
+    let fee_account = context.create_token_account(&mint_y, &initializer, 0).await;
+
+    // 태그(1) + 금액 + unwrap_sol(0)
+    let mut data = vec![1];
+    data.extend_from_slice(&amount_to_bytes(50));
+    data.push(0);
+    let exchange_ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(taker.pubkey(), true),
+            AccountMeta::new(taker_send, false),
+            AccountMeta::new(taker_receive, false),
+            AccountMeta::new(source, false),
+            AccountMeta::new(initializer, false),
+            AccountMeta::new(receive, false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(pda, false),
+            AccountMeta::new(fee_account, false),
+        ],
+        data,
+    };
+    assert!(context.process(&[exchange_ix], &[&taker]).await.is_err());
+
+    // 테이커의 토큰 Y는 그대로이고 에스크로도 거래 대기 중으로 남음
+    let taker_send_info = TokenAccount::unpack(&context.account_data(&taker_send).await).unwrap();
+    assert_eq!(taker_send_info.amount, 50);
+    let escrow_info = Escrow::unpack(&context.account_data(&escrow).await).unwrap();
+    assert_eq!(escrow_info.status, EscrowStatus::Active);
+    assert_eq!(escrow_info.deposit_amount, 60);
+}
+
+// 레지스트리 계정에 기록된 열린 에스크로 목록
+async fn registry_escrows(
+    context: &mut TestContext,
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+) -> Vec<Pubkey> {
+    let (registry, _) = registry_pda(program_id, initializer);
+    Registry::unpack(&context.account_data(&registry).await)
+        .unwrap()
+        .escrows
+}
+
+#[tokio::test]
+async fn registry_tracks_escrows_from_every_init_path() {
+    let program_id = Pubkey::new_unique();
+    let mut context = TestContext::new(&program_id).await;
+    let initializer = context.payer.pubkey();
+    let (registry, _) = registry_pda(&program_id, &initializer);
+
+    let mint_x = context.create_mint().await;
+    let mint_y = context.create_mint().await;
+    let receive = context.create_token_account(&mint_y, &initializer, 0).await;
+
+    // InitEscrowWithDelegate (논스 0): 토큰 프로그램 뒤에 레지스트리와 시스템 프로그램
+    let source = context
+        .create_token_account(&mint_x, &initializer, 100)
+        .await;
+    let delegate_escrow = context.create_escrow_account(&program_id).await;
+    let mut delegate_ix = init_escrow_with_delegate_ix(
+        &program_id,
+        &initializer,
+        &source,
+        &receive,
+        &delegate_escrow,
+        50,
+        60,
+    );
+    delegate_ix.accounts[0].is_writable = true;
+    delegate_ix.accounts.extend([
+        AccountMeta::new(registry, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]);
+    context.process(&[delegate_ix], &[]).await.unwrap();
+
+    // InitEscrowWithTransfer (논스 1): PDA 소유의 보관 계정으로 토큰 X를 옮김
+    let (pda, _) = escrow_pda(&program_id, &initializer, 1);
+    let vault = context.create_token_account(&mint_x, &pda, 0).await;
+    let transfer_escrow = context.create_escrow_account(&program_id).await;
+    // 태그(10) + 금액 + 맡길 수량 + 만료 시각(0) + 논스(1) + 수수료(0)
+    let mut data = vec![10];
+    data.extend_from_slice(&amount_to_bytes(50));
+    data.extend_from_slice(&amount_to_bytes(40));
+    data.extend_from_slice(&0i64.to_le_bytes());
+    data.extend_from_slice(&1u64.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+    let transfer_ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(initializer, true),
+            AccountMeta::new(source, false),
+            AccountMeta::new_readonly(receive, false),
+            AccountMeta::new(transfer_escrow, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(registry, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    };
+    context.process(&[transfer_ix], &[]).await.unwrap();
+
+    // InitSolEscrow: 이미 받는 시스템 프로그램 뒤에 레지스트리만 붙임
+    let sol_escrow = context.create_escrow_account(&program_id).await;
+    // 태그(5) + 램포트 + 예상 금액
+    let mut data = vec![5];
+    data.extend_from_slice(&amount_to_bytes(1_000_000));
+    data.extend_from_slice(&amount_to_bytes(50));
+    let sol_ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(initializer, true),
+            AccountMeta::new_readonly(receive, false),
+            AccountMeta::new(sol_escrow, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(registry, false),
+        ],
+        data,
+    };
+    context.process(&[sol_ix], &[]).await.unwrap();
+
+    // InitEscrowWithSeed (논스 2): 마지막 시스템 프로그램 바로 앞에 레지스트리
+    let temp = context
+        .create_token_account(&mint_x, &initializer, 100)
+        .await;
+    let seed = "escrow-2";
+    let seed_escrow = Pubkey::create_with_seed(&initializer, seed, &program_id).unwrap();
+    // 태그(7) + 금액 + 만료 시각(0) + 논스(2) + 수수료(0) + 시드
+    let mut data = vec![7];
+    data.extend_from_slice(&amount_to_bytes(50));
+    data.extend_from_slice(&0i64.to_le_bytes());
+    data.extend_from_slice(&2u64.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+    data.extend_from_slice(seed.as_bytes());
+    let seed_ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(initializer, true),
+            AccountMeta::new(temp, false),
+            AccountMeta::new_readonly(receive, false),
+            AccountMeta::new(seed_escrow, false),
+            #[cfg(feature = "rent-sysvar-account")]
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(registry, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    };
+    context.process(&[seed_ix], &[]).await.unwrap();
+
+    // InitEscrowBatch (논스 3, 4): 같이 쓰는 토큰 프로그램 뒤에 레지스트리와 시스템 프로그램
+    // 태그(9) + 개수(2) + 금액들 + 첫 논스(3) + 만료 시각(0) + 수수료(0)
+    let mut data = vec![9];
+    data.extend_from_slice(&2u32.to_le_bytes());
+    data.extend_from_slice(&50u64.to_le_bytes());
+    data.extend_from_slice(&60u64.to_le_bytes());
+    data.extend_from_slice(&3u64.to_le_bytes());
+    data.extend_from_slice(&0i64.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes());
+    let mut accounts = vec![AccountMeta::new(initializer, true)];
+    let mut batch_escrows = Vec::new();
+    for _ in 0..2 {
+        let temp = context
+            .create_token_account(&mint_x, &initializer, 100)
+            .await;
+        let escrow = context.create_escrow_account(&program_id).await;
+        accounts.extend([
+            AccountMeta::new(temp, false),
+            AccountMeta::new_readonly(receive, false),
+            AccountMeta::new(escrow, false),
+        ]);
+        batch_escrows.push(escrow);
+    }
+    accounts.extend([
+        #[cfg(feature = "rent-sysvar-account")]
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(registry, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]);
+    let batch_ix = Instruction {
+        program_id,
+        accounts,
+        data,
+    };
+    context.process(&[batch_ix], &[]).await.unwrap();
+
+    assert_eq!(
+        registry_escrows(&mut context, &program_id, &initializer).await,
+        vec![
+            delegate_escrow,
+            transfer_escrow,
+            sol_escrow,
+            seed_escrow,
+            batch_escrows[0],
+            batch_escrows[1],
+        ]
+    );
+}
+
+#[tokio::test]
+async fn transfer_initializer_moves_registry_entry() {
+    let program_id = Pubkey::new_unique();
+    let mut context = TestContext::new(&program_id).await;
+    let initializer = context.payer.pubkey();
+    let new_initializer = Keypair::new();
+    let (old_registry, _) = registry_pda(&program_id, &initializer);
+    let (new_registry, _) = registry_pda(&program_id, &new_initializer.pubkey());
+
+    let mint_x = context.create_mint().await;
+    let mint_y = context.create_mint().await;
+    let receive = context.create_token_account(&mint_y, &initializer, 0).await;
+    let temp = context
+        .create_token_account(&mint_x, &initializer, 100)
+        .await;
+    let escrow = context.create_escrow_account(&program_id).await;
+    context
+        .process(
+            &[init_escrow_with_registry_ix(
+                &program_id,
+                &initializer,
+                &temp,
+                &receive,
+                &escrow,
+                0,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+
+    // 태그(8) + 새 이니셜라이저
+    // 새 레지스트리는 아직 없으므로 지금 이니셜라이저가 렌트비를 내고 만듦
+    let mut data = vec![8];
+    data.extend_from_slice(new_initializer.pubkey().as_ref());
+    let transfer_ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(initializer, true),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new(old_registry, false),
+            AccountMeta::new(new_registry, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data,
+    };
+    context.process(&[transfer_ix], &[]).await.unwrap();
+
+    assert!(registry_escrows(&mut context, &program_id, &initializer)
+        .await
+        .is_empty());
+    assert_eq!(
+        registry_escrows(&mut context, &program_id, &new_initializer.pubkey()).await,
+        vec![escrow]
+    );
+
+    // 새 이니셜라이저가 취소하면서 자기 레지스트리를 넘기면 목록에서 빠짐
+    let (pda, _) = escrow_pda(&program_id, &initializer, 0);
+    let cancel_ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(new_initializer.pubkey(), true),
+            AccountMeta::new(temp, false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(pda, false),
+            AccountMeta::new(new_registry, false),
+        ],
+        data: vec![2],
+    };
+    context
+        .process(&[cancel_ix], &[&new_initializer])
+        .await
+        .unwrap();
+    assert!(
+        registry_escrows(&mut context, &program_id, &new_initializer.pubkey())
+            .await
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn cancel_expired_keeps_registry_entry_until_close() {
+    let program_id = Pubkey::new_unique();
+    let mut context = TestContext::new(&program_id).await;
+    let initializer = context.payer.pubkey();
+    let (registry, _) = registry_pda(&program_id, &initializer);
+
+    let mint_x = context.create_mint().await;
+    let mint_y = context.create_mint().await;
+    let receive = context.create_token_account(&mint_y, &initializer, 0).await;
+    let temp = context
+        .create_token_account(&mint_x, &initializer, 100)
+        .await;
+    let escrow = context.create_escrow_account(&program_id).await;
+    let mut init_ix =
+        init_escrow_with_registry_ix(&program_id, &initializer, &temp, &receive, &escrow, 0);
+    // 만료 시각(태그 1 + 금액 8 뒤)을 이미 지난 시각으로 바꿈
+    init_ix.data[9..17].copy_from_slice(&1i64.to_le_bytes());
+    context.process(&[init_ix], &[]).await.unwrap();
+
+    // 정리 봇이 서명 없이 취소함 (태그 16)
+    let (pda, _) = escrow_pda(&program_id, &initializer, 0);
+    let cancel_expired_ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new(temp, false),
+            AccountMeta::new_readonly(pda, false),
+        ],
+        data: vec![16],
+    };
+    context.process(&[cancel_expired_ix], &[]).await.unwrap();
+
+    let escrow_info = Escrow::unpack(&context.account_data(&escrow).await).unwrap();
+    assert_eq!(escrow_info.status, EscrowStatus::Cancelled);
+    // 레지스트리는 건드리지 않으므로 에스크로가 아직 목록에 있음
+    assert_eq!(
+        registry_escrows(&mut context, &program_id, &initializer).await,
+        vec![escrow]
+    );
+
+    // 이니셜라이저가 레지스트리를 넘겨 닫으면 목록에서 빠짐 (태그 4)
+    let close_ix = Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(initializer, true),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new(registry, false),
+        ],
+        data: vec![4],
+    };
+    context.process(&[close_ix], &[]).await.unwrap();
+    assert!(registry_escrows(&mut context, &program_id, &initializer)
+        .await
+        .is_empty());
+}